@@ -0,0 +1,15 @@
+#!/usr/bin/env node
+// Runs a test binary built for wasm32-unknown-unknown under Node.
+//
+// Binaries for the target have no imports and export their entry point as `main`. A panic traps,
+// which is reported as a failure.
+const fs = require('fs');
+
+const wasm = new WebAssembly.Module(fs.readFileSync(process.argv[2]));
+const instance = new WebAssembly.Instance(wasm, {});
+try {
+    process.exit(instance.exports.main(0, 0));
+} catch (e) {
+    console.error(e);
+    process.exit(101);
+}
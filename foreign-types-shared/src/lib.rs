@@ -2,14 +2,24 @@
 
 #![no_std]
 #![warn(missing_docs)]
-#![doc(html_root_url="https://docs.rs/foreign-types-shared/0.1")]
+#![doc(html_root_url="https://docs.rs/foreign-types-shared/0.2")]
 
+use core::any;
 use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::fmt;
+use core::hash;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
 
 /// An opaque type used to define `ForeignTypeRef` types.
 ///
 /// A type implementing `ForeignTypeRef` should simply be a newtype wrapper around this type.
-pub struct Opaque(UnsafeCell<()>);
+///
+/// `Opaque` is neither `Send` nor `Sync`, so a type wrapping it is only thread-safe if it says so
+/// with explicit `unsafe impl`s.
+pub struct Opaque(PhantomData<UnsafeCell<*mut ()>>);
 
 /// A type implemented by wrappers over foreign types.
 pub trait ForeignType: Sized {
@@ -19,11 +29,204 @@ pub trait ForeignType: Sized {
     /// The type representing a reference to this type.
     type Ref: ForeignTypeRef<CType = Self::CType>;
 
+    /// The value of a pointer which does not refer to an object.
+    ///
+    /// This is null by default, but some APIs use a different sentinel value.
+    const INVALID: *mut Self::CType = ptr::null_mut();
+
     /// Constructs an instance of this type from its raw type.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid, owned instance of the C type.
     unsafe fn from_ptr(ptr: *mut Self::CType) -> Self;
 
+    /// Constructs an instance of this type from its raw type, returning `None` if `ptr` is
+    /// `INVALID`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must either be `INVALID` or a valid, owned instance of the C type.
+    #[inline]
+    #[must_use]
+    unsafe fn from_ptr_opt(ptr: *mut Self::CType) -> Option<Self> {
+        if ptr == Self::INVALID {
+            None
+        } else {
+            Some(Self::from_ptr(ptr))
+        }
+    }
+
+    /// Constructs an instance of this type from a non-null pointer to its raw type.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid, owned instance of the C type.
+    #[inline]
+    unsafe fn from_non_null(ptr: NonNull<Self::CType>) -> Self {
+        Self::from_ptr(ptr.as_ptr())
+    }
+
     /// Returns a raw pointer to the wrapped value.
     fn as_ptr(&self) -> *mut Self::CType;
+
+    /// Returns a non-null pointer to the wrapped value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the wrapped pointer is null, which is only possible for types whose `INVALID`
+    /// value is not null.
+    #[inline]
+    fn as_non_null(&self) -> NonNull<Self::CType> {
+        NonNull::new(self.as_ptr()).expect("foreign type wraps a null pointer")
+    }
+
+    /// Consumes the wrapper and returns the raw pointer.
+    ///
+    /// The caller becomes responsible for freeing the value.
+    #[inline]
+    fn into_ptr(self) -> *mut Self::CType {
+        let ptr = self.as_ptr();
+        mem::forget(self);
+        ptr
+    }
+
+    /// Consumes the wrapper, marking its value as owned by C.
+    ///
+    /// This should be used instead of `into_ptr` when ownership is handed to a C API while the
+    /// pointer is kept for later use. The returned `Detached` value can be compared with other
+    /// pointers, but it can only be used to access the value or to take back ownership through
+    /// `unsafe` methods.
+    #[inline]
+    fn detach(self) -> Detached<Self> {
+        Detached {
+            ptr: self.into_ptr(),
+            _p: PhantomData,
+        }
+    }
+
+    /// Converts this wrapper into another wrapper over the same C type, transferring ownership.
+    #[inline]
+    fn into_other<T>(self) -> T
+    where
+        Self: ForeignTypeCompat,
+        T: ForeignType<CType = Self::CType> + ForeignTypeCompat,
+    {
+        unsafe { T::from_ptr(self.into_ptr()) }
+    }
+}
+
+/// A pointer to a value whose ownership has been given to C.
+///
+/// Returned by `ForeignType::detach`. The value is not freed when this is dropped.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate foreign_types_shared;
+/// use foreign_types_shared::{ForeignType, ForeignTypeRef, Opaque};
+///
+/// # pub struct FooRef(Opaque);
+/// # impl ForeignTypeRef for FooRef { type CType = u8; }
+/// # pub struct Foo(*mut u8);
+/// # impl ForeignType for Foo {
+/// #     type CType = u8;
+/// #     type Ref = FooRef;
+/// #     unsafe fn from_ptr(ptr: *mut u8) -> Foo { Foo(ptr) }
+/// #     fn as_ptr(&self) -> *mut u8 { self.0 }
+/// # }
+/// # fn main() {
+/// let mut value = 0u8;
+/// let foo = unsafe { Foo::from_ptr(&mut value) };
+///
+/// // Ownership is given to C, which later hands it back.
+/// let detached = foo.detach();
+/// assert!(detached == &mut value as *mut u8);
+/// let foo = unsafe { detached.reclaim() };
+/// # drop(foo);
+/// # }
+/// ```
+pub struct Detached<T>
+where
+    T: ForeignType,
+{
+    ptr: *mut T::CType,
+    _p: PhantomData<T>,
+}
+
+impl<T> Detached<T>
+where
+    T: ForeignType,
+{
+    /// Returns the raw pointer to the value.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut T::CType {
+        self.ptr
+    }
+
+    /// Returns a shared reference to the value.
+    ///
+    /// # Safety
+    ///
+    /// The value must still be alive, and must not be modified for the lifetime of the reference.
+    #[inline]
+    pub unsafe fn get(&self) -> &T::Ref {
+        T::Ref::from_ptr(self.ptr)
+    }
+
+    /// Takes back ownership of the value.
+    ///
+    /// # Safety
+    ///
+    /// The C library must have released ownership of the value, so that nothing else will free it.
+    #[inline]
+    pub unsafe fn reclaim(self) -> T {
+        T::from_ptr(self.ptr)
+    }
+}
+
+impl<T> PartialEq for Detached<T>
+where
+    T: ForeignType,
+{
+    #[inline]
+    fn eq(&self, other: &Detached<T>) -> bool {
+        self.ptr == other.ptr
+    }
+}
+
+impl<T> Eq for Detached<T> where T: ForeignType {}
+
+impl<T> PartialEq<*mut T::CType> for Detached<T>
+where
+    T: ForeignType,
+{
+    #[inline]
+    fn eq(&self, other: &*mut T::CType) -> bool {
+        self.ptr == *other
+    }
+}
+
+impl<T> hash::Hash for Detached<T>
+where
+    T: ForeignType,
+{
+    #[inline]
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: hash::Hasher,
+    {
+        self.ptr.hash(state)
+    }
+}
+
+impl<T> fmt::Debug for Detached<T>
+where
+    T: ForeignType,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("Detached").field(&self.ptr).finish()
+    }
 }
 
 /// A trait implemented by types which reference borrowed foreign types.
@@ -31,21 +234,418 @@ pub trait ForeignTypeRef: Sized {
     /// The raw C type.
     type CType;
 
+    /// The value of a pointer which does not refer to an object.
+    ///
+    /// This is null by default, but some APIs use a different sentinel value.
+    const INVALID: *mut Self::CType = ptr::null_mut();
+
     /// Constructs a shared instance of this type from its raw type.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid, immutable, instance of the C type.
     #[inline]
     unsafe fn from_ptr<'a>(ptr: *mut Self::CType) -> &'a Self {
         &*(ptr as *mut _)
     }
 
     /// Constructs a mutable reference of this type from its raw type.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid, unique, instance of the C type.
     #[inline]
     unsafe fn from_ptr_mut<'a>(ptr: *mut Self::CType) -> &'a mut Self {
         &mut *(ptr as *mut _)
     }
 
+    /// Constructs a shared instance of this type from its raw type, returning `None` if `ptr` is
+    /// `INVALID`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must either be `INVALID` or a valid, immutable, instance of the C type.
+    #[inline]
+    #[must_use]
+    unsafe fn from_ptr_opt<'a>(ptr: *mut Self::CType) -> Option<&'a Self> {
+        if ptr == Self::INVALID {
+            None
+        } else {
+            Some(Self::from_ptr(ptr))
+        }
+    }
+
+    /// Constructs a mutable reference of this type from its raw type, returning `None` if `ptr` is
+    /// `INVALID`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must either be `INVALID` or a valid, unique, instance of the C type.
+    #[inline]
+    #[must_use]
+    unsafe fn from_ptr_mut_opt<'a>(ptr: *mut Self::CType) -> Option<&'a mut Self> {
+        if ptr == Self::INVALID {
+            None
+        } else {
+            Some(Self::from_ptr_mut(ptr))
+        }
+    }
+
+    /// Constructs a shared instance of this type from a non-null pointer to its raw type.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid, immutable, instance of the C type.
+    #[inline]
+    unsafe fn from_non_null<'a>(ptr: NonNull<Self::CType>) -> &'a Self {
+        Self::from_ptr(ptr.as_ptr())
+    }
+
+    /// Constructs a mutable reference of this type from a non-null pointer to its raw type.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid, unique, instance of the C type.
+    #[inline]
+    unsafe fn from_non_null_mut<'a>(ptr: NonNull<Self::CType>) -> &'a mut Self {
+        Self::from_ptr_mut(ptr.as_ptr())
+    }
+
+    /// Calls `f` with a shared reference to the value at `ptr`.
+    ///
+    /// This is intended for callbacks in which C lends a pointer only for the duration of the call.
+    /// Unlike `from_ptr`, the lifetime of the reference is limited to the closure, so it cannot
+    /// escape the callback.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid, immutable, instance of the C type for the duration of the call.
+    #[inline]
+    unsafe fn with_ref<F, R>(ptr: *mut Self::CType, f: F) -> R
+    where
+        F: FnOnce(&Self) -> R,
+    {
+        f(Self::from_ptr(ptr))
+    }
+
+    /// Calls `f` with a mutable reference to the value at `ptr`.
+    ///
+    /// Like `with_ref`, the lifetime of the reference is limited to the closure.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid, unique, instance of the C type for the duration of the call.
+    #[inline]
+    unsafe fn with_mut<F, R>(ptr: *mut Self::CType, f: F) -> R
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        f(Self::from_ptr_mut(ptr))
+    }
+
     /// Returns a raw pointer to the wrapped value.
     #[inline]
     fn as_ptr(&self) -> *mut Self::CType {
         self as *const _ as *mut _
     }
+
+    /// Returns a non-null pointer to the wrapped value.
+    #[inline]
+    fn as_non_null(&self) -> NonNull<Self::CType> {
+        NonNull::from(self).cast()
+    }
+
+    /// Reborrows this value as a reference to another wrapper over the same C type.
+    #[inline]
+    fn as_other_ref<T>(&self) -> &T
+    where
+        Self: ForeignTypeCompat,
+        T: ForeignTypeRef<CType = Self::CType> + ForeignTypeCompat,
+    {
+        unsafe { T::from_ptr(self.as_ptr()) }
+    }
+
+    /// Mutably reborrows this value as a reference to another wrapper over the same C type.
+    #[inline]
+    fn as_other_mut<T>(&mut self) -> &mut T
+    where
+        Self: ForeignTypeCompat,
+        T: ForeignTypeMut<CType = Self::CType> + ForeignTypeCompat,
+    {
+        unsafe { T::from_ptr_mut(self.as_ptr()) }
+    }
+
+    /// Reinterprets this value as a reference to a different foreign type.
+    ///
+    /// # Safety
+    ///
+    /// The C API must guarantee that a pointer to this value may be used as a pointer to `U`'s C
+    /// type, for example because one struct is a prefix of the other, for as long as the returned
+    /// reference is alive.
+    #[inline]
+    unsafe fn cast_ref<U>(&self) -> &U
+    where
+        U: ForeignTypeRef,
+    {
+        U::from_ptr(self.as_ptr() as *mut U::CType)
+    }
+
+    /// Reinterprets this value as a mutable reference to a different foreign type.
+    ///
+    /// # Safety
+    ///
+    /// The C API must guarantee that a pointer to this value may be used as a pointer to `U`'s C
+    /// type, including for modifications, for as long as the returned reference is alive.
+    #[inline]
+    unsafe fn cast_mut<U>(&mut self) -> &mut U
+    where
+        U: ForeignTypeRef,
+    {
+        U::from_ptr_mut(self.as_ptr() as *mut U::CType)
+    }
+}
+
+/// A marker trait for borrowed types which may be mutated through a `&mut` reference.
+///
+/// Generic code which produces a mutable reference to a borrowed type, such as
+/// `MaybeOwned`'s `DerefMut` implementation, requires this trait, so that wrappers over C objects
+/// which are logically constant cannot be mutated by safe code. `foreign_type!` implements it
+/// unless the type is declared with `const MUTABLE: bool = false;`.
+///
+/// # Safety
+///
+/// The C library must allow the object to be modified through a uniquely borrowed pointer.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be mutated",
+    note = "types declared with `const MUTABLE: bool = false;` only allow shared access"
+)]
+pub unsafe trait ForeignTypeMut: ForeignTypeRef {}
+
+/// A marker trait for wrappers which can be converted into other wrappers over the same C type.
+///
+/// Independent bindings crates often wrap the same C type. Implementing this trait for both the
+/// owned and borrowed types of each wrapper allows values to be converted between them with
+/// `ForeignType::into_other` and `ForeignTypeRef::as_other_ref`.
+///
+/// # Safety
+///
+/// The wrapper must not rely on any invariants beyond those of the C type itself, and its ownership
+/// and thread-safety semantics must be the canonical ones for the C type: `from_ptr` takes
+/// ownership of the value and `Drop` releases it with the library's own destructor.
+pub unsafe trait ForeignTypeCompat {}
+
+/// A type implemented by wrappers over foreign handles which are passed by value.
+///
+/// Some C APIs identify objects with a small `Copy` struct or integer, rather than a pointer. This
+/// trait is the counterpart of `ForeignType` for them: the wrapper stores the handle itself, and
+/// destroys the object it identifies when dropped.
+pub trait ForeignHandle: Sized {
+    /// The raw C handle type.
+    type CType: Copy;
+
+    /// The type representing a reference to this type.
+    type Ref: ForeignHandleRef<CType = Self::CType>;
+
+    /// The value of a handle which does not refer to an object, if the API has one.
+    ///
+    /// Integer handles often use a sentinel such as `-1` for a failed call. This is `None` by
+    /// default, in which case every handle is assumed to be valid.
+    const INVALID: Option<Self::CType> = None;
+
+    /// Constructs an instance of this type from its raw handle.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must identify a valid, owned object.
+    unsafe fn from_handle(handle: Self::CType) -> Self;
+
+    /// Constructs an instance of this type from its raw handle, returning `None` if `handle` is
+    /// `INVALID`.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must either be `INVALID` or identify a valid, owned object.
+    #[inline]
+    #[must_use]
+    unsafe fn from_handle_opt(handle: Self::CType) -> Option<Self>
+    where
+        Self::CType: PartialEq,
+    {
+        if Self::INVALID == Some(handle) {
+            None
+        } else {
+            Some(Self::from_handle(handle))
+        }
+    }
+
+    /// Returns the raw handle.
+    fn as_handle(&self) -> Self::CType;
+
+    /// Consumes the wrapper and returns the raw handle.
+    ///
+    /// The caller becomes responsible for destroying the object.
+    #[inline]
+    fn into_handle(self) -> Self::CType {
+        let handle = self.as_handle();
+        mem::forget(self);
+        handle
+    }
+}
+
+/// A trait implemented by types which reference borrowed foreign handles.
+///
+/// # Safety
+///
+/// The default methods reinterpret a reference to the handle as a reference to the wrapper and
+/// back, so an implementing type must be a `#[repr(transparent)]` newtype around `CType`, with no
+/// other non-zero-sized fields.
+pub unsafe trait ForeignHandleRef: Sized {
+    /// The raw C handle type.
+    type CType: Copy;
+
+    /// Constructs a shared instance of this type from a reference to its raw handle.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must identify a valid object, which must not be modified or destroyed while the
+    /// returned reference is alive.
+    #[inline]
+    unsafe fn from_handle(handle: &Self::CType) -> &Self {
+        &*(handle as *const Self::CType as *const Self)
+    }
+
+    /// Constructs a mutable instance of this type from a reference to its raw handle.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must identify a valid object, which must not be used through any other handle or
+    /// destroyed while the returned reference is alive.
+    #[inline]
+    unsafe fn from_handle_mut(handle: &mut Self::CType) -> &mut Self {
+        &mut *(handle as *mut Self::CType as *mut Self)
+    }
+
+    /// Returns the raw handle.
+    #[inline]
+    fn as_handle(&self) -> Self::CType {
+        unsafe { *(self as *const Self as *const Self::CType) }
+    }
+}
+
+/// A trait for values which can provide a raw pointer to a C type.
+///
+/// `foreign_type!` implements this trait for both the owned and borrowed types it generates, and
+/// it is implemented for references to any implementing type. Functions which only need a raw
+/// pointer can use it to accept owned values, borrowed values, and references to either with a
+/// single bound. Handwritten wrappers should implement it for both of their types.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate foreign_types_shared;
+/// use foreign_types_shared::{AsForeignPtr, ForeignType, ForeignTypeRef, Opaque};
+///
+/// # pub struct FooRef(Opaque);
+/// # impl ForeignTypeRef for FooRef { type CType = u8; }
+/// # pub struct Foo(*mut u8);
+/// # impl ForeignType for Foo {
+/// #     type CType = u8;
+/// #     type Ref = FooRef;
+/// #     unsafe fn from_ptr(ptr: *mut u8) -> Foo { Foo(ptr) }
+/// #     fn as_ptr(&self) -> *mut u8 { self.0 }
+/// # }
+/// # impl AsForeignPtr<u8> for Foo { fn as_foreign_ptr(&self) -> *mut u8 { self.0 } }
+/// # impl AsForeignPtr<u8> for FooRef { fn as_foreign_ptr(&self) -> *mut u8 { self.as_ptr() } }
+/// fn address<P>(value: P) -> usize
+/// where
+///     P: AsForeignPtr<u8>,
+/// {
+///     value.as_foreign_ptr() as usize
+/// }
+///
+/// # fn main() {
+/// let mut value = 0u8;
+/// let foo = unsafe { Foo::from_ptr(&mut value) };
+/// let foo_ref: &FooRef = unsafe { FooRef::from_ptr(foo.as_ptr()) };
+///
+/// assert_eq!(address(&foo), address(foo_ref));
+/// # }
+/// ```
+pub trait AsForeignPtr<C> {
+    /// Returns a raw pointer to the C value.
+    fn as_foreign_ptr(&self) -> *mut C;
+}
+
+impl<C, T> AsForeignPtr<C> for &T
+where
+    T: AsForeignPtr<C> + ?Sized,
+{
+    #[inline]
+    fn as_foreign_ptr(&self) -> *mut C {
+        (**self).as_foreign_ptr()
+    }
+}
+
+impl<C, T> AsForeignPtr<C> for &mut T
+where
+    T: AsForeignPtr<C> + ?Sized,
+{
+    #[inline]
+    fn as_foreign_ptr(&self) -> *mut C {
+        (**self).as_foreign_ptr()
+    }
+}
+
+/// An object-safe view of a wrapper over a foreign type.
+///
+/// `ForeignType` is not object safe, so wrappers of different foreign types cannot be stored
+/// together as trait objects. This trait exposes type-erased operations on them instead, and is
+/// implemented for every `ForeignType`. Dropping a `Box<dyn AnyForeignType>` frees the value
+/// through the wrapper's own `Drop` implementation.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate foreign_types_shared;
+/// use foreign_types_shared::{AnyForeignType, ForeignType, ForeignTypeRef, Opaque};
+///
+/// # pub struct FooRef(Opaque);
+/// # impl ForeignTypeRef for FooRef { type CType = u8; }
+/// # pub struct Foo(*mut u8);
+/// # impl ForeignType for Foo {
+/// #     type CType = u8;
+/// #     type Ref = FooRef;
+/// #     unsafe fn from_ptr(ptr: *mut u8) -> Foo { Foo(ptr) }
+/// #     fn as_ptr(&self) -> *mut u8 { self.0 }
+/// # }
+/// # fn main() {
+/// let mut value = 0u8;
+/// let foo = unsafe { Foo::from_ptr(&mut value) };
+/// let registry: Vec<Box<dyn AnyForeignType>> = vec![Box::new(foo)];
+///
+/// assert_eq!(registry[0].as_raw_ptr(), &mut value as *mut u8 as *mut _);
+/// assert!(registry[0].type_name().ends_with("Foo"));
+/// # }
+/// ```
+pub trait AnyForeignType {
+    /// Returns the address of the wrapped value.
+    fn as_raw_ptr(&self) -> *mut c_void;
+
+    /// Returns the name of the wrapper type.
+    fn type_name(&self) -> &'static str;
+}
+
+impl<T> AnyForeignType for T
+where
+    T: ForeignType,
+{
+    #[inline]
+    fn as_raw_ptr(&self) -> *mut c_void {
+        self.as_ptr() as *mut c_void
+    }
+
+    #[inline]
+    fn type_name(&self) -> &'static str {
+        any::type_name::<T>()
+    }
 }
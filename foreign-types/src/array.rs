@@ -0,0 +1,287 @@
+//! Iteration over null-terminated arrays of pointers returned by C.
+//!
+//! C APIs such as `X **X_list(void)` often return an array of pointers terminated by a null
+//! pointer. `NullTerminatedIter` walks such an array, yielding references to the elements. When
+//! the caller owns the array, `NullTerminatedArray` frees it when dropped, and
+//! `NullTerminatedIntoIter` additionally takes ownership of each element.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::array::NullTerminatedIntoIter;
+//!
+//! # mod cert_sys {
+//! #     pub struct CERT(pub u32);
+//! #     pub unsafe extern "C" fn CERT_free(cert: *mut CERT) { drop(Box::from_raw(cert)) }
+//! #     pub unsafe fn CERT_id(cert: *mut CERT) -> u32 { (*cert).0 }
+//! #     pub unsafe fn STORE_get_certs() -> *mut *mut CERT {
+//! #         let mut certs = (0..3).map(|i| Box::into_raw(Box::new(CERT(i)))).collect::<Vec<_>>();
+//! #         certs.push(0 as *mut _);
+//! #         Box::into_raw(certs.into_boxed_slice()) as *mut *mut CERT
+//! #     }
+//! #     pub unsafe fn CERT_list_free(certs: *mut *mut CERT) {
+//! #         drop(Box::from_raw(std::slice::from_raw_parts_mut(certs, 4)))
+//! #     }
+//! # }
+//! foreign_type! {
+//!     type CType = cert_sys::CERT;
+//!     fn drop = cert_sys::CERT_free;
+//!     getters {
+//!         fn id -> u32 = cert_sys::CERT_id;
+//!     }
+//!     /// A certificate.
+//!     pub struct Cert;
+//!     /// A borrowed certificate.
+//!     pub struct CertRef;
+//! }
+//!
+//! fn certs() -> NullTerminatedIntoIter<Cert, impl FnOnce(*mut *mut cert_sys::CERT)> {
+//!     unsafe {
+//!         NullTerminatedIntoIter::new(cert_sys::STORE_get_certs(), |certs| {
+//!             cert_sys::CERT_list_free(certs)
+//!         })
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let ids = certs().map(|cert| cert.id()).collect::<Vec<_>>();
+//! assert_eq!(ids, [0, 1, 2]);
+//! # }
+//! ```
+use core::fmt;
+use core::marker::PhantomData;
+
+use {ForeignType, ForeignTypeRef};
+
+/// An iterator over a borrowed null-terminated array of pointers.
+pub struct NullTerminatedIter<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    ptr: *const *mut R::CType,
+    _p: PhantomData<&'a R>,
+}
+
+impl<'a, R> NullTerminatedIter<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    /// Creates an iterator over the array starting at `ptr`.
+    ///
+    /// A null `ptr` is treated as an empty array.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must either be null or point to an array of valid pointers terminated by a null
+    /// pointer. The array and its elements must remain valid for the lifetime `'a`.
+    #[inline]
+    pub unsafe fn new(ptr: *const *mut R::CType) -> NullTerminatedIter<'a, R> {
+        NullTerminatedIter {
+            ptr,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<'a, R> Iterator for NullTerminatedIter<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    type Item = &'a R;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a R> {
+        if self.ptr.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let item = *self.ptr;
+            if item.is_null() {
+                None
+            } else {
+                self.ptr = self.ptr.add(1);
+                Some(R::from_ptr(item))
+            }
+        }
+    }
+}
+
+impl<'a, R> Clone for NullTerminatedIter<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    #[inline]
+    fn clone(&self) -> NullTerminatedIter<'a, R> {
+        NullTerminatedIter {
+            ptr: self.ptr,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<'a, R> fmt::Debug for NullTerminatedIter<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("NullTerminatedIter")
+            .field("ptr", &self.ptr)
+            .finish()
+    }
+}
+
+/// An owned null-terminated array of borrowed pointers, which is freed when dropped.
+///
+/// The elements are not freed; they must remain valid for the lifetime `'a`.
+pub struct NullTerminatedArray<'a, R, F>
+where
+    R: ForeignTypeRef + 'a,
+    F: FnOnce(*mut *mut R::CType),
+{
+    ptr: *mut *mut R::CType,
+    free: Option<F>,
+    _p: PhantomData<&'a R>,
+}
+
+impl<'a, R, F> Drop for NullTerminatedArray<'a, R, F>
+where
+    R: ForeignTypeRef + 'a,
+    F: FnOnce(*mut *mut R::CType),
+{
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(free) = self.free.take() {
+            if !self.ptr.is_null() {
+                free(self.ptr);
+            }
+        }
+    }
+}
+
+impl<'a, R, F> NullTerminatedArray<'a, R, F>
+where
+    R: ForeignTypeRef + 'a,
+    F: FnOnce(*mut *mut R::CType),
+{
+    /// Takes ownership of the array starting at `ptr`, which is passed to `free` when dropped.
+    ///
+    /// A null `ptr` is treated as an empty array, and is not passed to `free`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must either be null or point to an owned array of valid pointers terminated by a
+    /// null pointer. The elements must remain valid for the lifetime `'a`.
+    #[inline]
+    pub unsafe fn new(ptr: *mut *mut R::CType, free: F) -> NullTerminatedArray<'a, R, F> {
+        NullTerminatedArray {
+            ptr,
+            free: Some(free),
+            _p: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the elements of the array.
+    #[inline]
+    pub fn iter(&self) -> NullTerminatedIter<'_, R> {
+        unsafe { NullTerminatedIter::new(self.ptr) }
+    }
+}
+
+impl<'a, 'b, R, F> IntoIterator for &'b NullTerminatedArray<'a, R, F>
+where
+    R: ForeignTypeRef + 'a,
+    F: FnOnce(*mut *mut R::CType),
+{
+    type Item = &'b R;
+    type IntoIter = NullTerminatedIter<'b, R>;
+
+    #[inline]
+    fn into_iter(self) -> NullTerminatedIter<'b, R> {
+        self.iter()
+    }
+}
+
+/// An iterator which takes ownership of the elements of a null-terminated array.
+///
+/// Elements which have not been yielded are freed when the iterator is dropped, after which the
+/// array itself is freed.
+pub struct NullTerminatedIntoIter<T, F>
+where
+    T: ForeignType,
+    F: FnOnce(*mut *mut T::CType),
+{
+    ptr: *mut *mut T::CType,
+    pos: usize,
+    free: Option<F>,
+    _p: PhantomData<T>,
+}
+
+impl<T, F> Drop for NullTerminatedIntoIter<T, F>
+where
+    T: ForeignType,
+    F: FnOnce(*mut *mut T::CType),
+{
+    #[inline]
+    fn drop(&mut self) {
+        for _ in &mut *self {}
+        if let Some(free) = self.free.take() {
+            if !self.ptr.is_null() {
+                free(self.ptr);
+            }
+        }
+    }
+}
+
+impl<T, F> NullTerminatedIntoIter<T, F>
+where
+    T: ForeignType,
+    F: FnOnce(*mut *mut T::CType),
+{
+    /// Takes ownership of the array starting at `ptr` and each of its elements. The array is
+    /// passed to `free` when the iterator is dropped.
+    ///
+    /// A null `ptr` is treated as an empty array, and is not passed to `free`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must either be null or point to an owned array of valid, owned pointers terminated by
+    /// a null pointer. `free` must free the array without freeing its elements.
+    #[inline]
+    pub unsafe fn new(ptr: *mut *mut T::CType, free: F) -> NullTerminatedIntoIter<T, F> {
+        NullTerminatedIntoIter {
+            ptr,
+            pos: 0,
+            free: Some(free),
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<T, F> Iterator for NullTerminatedIntoIter<T, F>
+where
+    T: ForeignType,
+    F: FnOnce(*mut *mut T::CType),
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        if self.ptr.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let item = *self.ptr.add(self.pos);
+            if item.is_null() {
+                None
+            } else {
+                self.pos += 1;
+                Some(T::from_ptr(item))
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Collections of owned values freed with a single C call.
+//!
+//! Some C libraries free an array of objects with one call of the form `X_free_all(ptrs, n)`,
+//! which can be faster than, or required instead of, freeing them one by one. Types defined with a
+//! `fn free_all = ...;` entry in `foreign_type!` implement `BatchFree`, and can be collected into a
+//! `BatchOwned`, which frees all of its values together when it is dropped.
+//!
+//! This module requires the `std` feature, which is enabled by default.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::batch::BatchOwned;
+//!
+//! # mod obj_sys {
+//! #     pub struct OBJ(pub u32);
+//! #     pub unsafe fn OBJ_new(n: u32) -> *mut OBJ { Box::into_raw(Box::new(OBJ(n))) }
+//! #     pub unsafe extern "C" fn OBJ_free(obj: *mut OBJ) { drop(Box::from_raw(obj)) }
+//! #     pub unsafe fn OBJ_free_all(objs: *mut *mut OBJ, n: usize) {
+//! #         for i in 0..n { OBJ_free(*objs.add(i)) }
+//! #     }
+//! # }
+//! foreign_type! {
+//!     type CType = obj_sys::OBJ;
+//!     fn drop = obj_sys::OBJ_free;
+//!     fn free_all = obj_sys::OBJ_free_all;
+//!     /// An object.
+//!     pub struct Obj;
+//!     /// A borrowed object.
+//!     pub struct ObjRef;
+//! }
+//!
+//! # fn main() {
+//! use foreign_types::{ForeignType, ForeignTypeRef};
+//!
+//! let objs = (0..3)
+//!     .map(|n| unsafe { Obj::from_ptr(obj_sys::OBJ_new(n)) })
+//!     .collect::<BatchOwned<Obj>>();
+//! assert_eq!(objs.len(), 3);
+//! assert_eq!(unsafe { (*objs.get(1).unwrap().as_ptr()).0 }, 1);
+//! // All three objects are freed with one call to `OBJ_free_all`.
+//! drop(objs);
+//! # }
+//! ```
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+use core::slice;
+use std::vec::Vec;
+
+use {ForeignType, ForeignTypeMut, ForeignTypeRef};
+
+/// A type whose values can be freed together with a single C call.
+///
+/// This is implemented by `foreign_type!` for types with a `fn free_all = ...;` entry.
+pub trait BatchFree: ForeignType {
+    /// Frees each of the `len` values in the array starting at `ptrs`.
+    ///
+    /// # Safety
+    ///
+    /// `ptrs` must point to `len` valid, owned pointers, none of which may be used afterwards.
+    unsafe fn free_all(ptrs: *mut *mut Self::CType, len: usize);
+}
+
+/// A collection of owned values which are freed together when it is dropped.
+pub struct BatchOwned<T>
+where
+    T: BatchFree,
+{
+    ptrs: Vec<*mut T::CType>,
+    _p: PhantomData<T>,
+}
+
+unsafe impl<T> Send for BatchOwned<T> where T: BatchFree + Send {}
+
+unsafe impl<T> Sync for BatchOwned<T> where T: BatchFree + Sync {}
+
+impl<T> Drop for BatchOwned<T>
+where
+    T: BatchFree,
+{
+    #[inline]
+    fn drop(&mut self) {
+        if !self.ptrs.is_empty() {
+            unsafe { T::free_all(self.ptrs.as_mut_ptr(), self.ptrs.len()) }
+        }
+    }
+}
+
+impl<T> BatchOwned<T>
+where
+    T: BatchFree,
+{
+    /// Creates an empty collection.
+    #[inline]
+    pub fn new() -> BatchOwned<T> {
+        BatchOwned {
+            ptrs: Vec::new(),
+            _p: PhantomData,
+        }
+    }
+
+    /// Creates an empty collection with space for at least `capacity` values.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> BatchOwned<T> {
+        BatchOwned {
+            ptrs: Vec::with_capacity(capacity),
+            _p: PhantomData,
+        }
+    }
+
+    /// Returns the number of values in the collection.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ptrs.len()
+    }
+
+    /// Returns `true` if the collection contains no values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ptrs.is_empty()
+    }
+
+    /// Adds a value to the end of the collection.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.ptrs.push(value.into_ptr());
+    }
+
+    /// Removes the last value from the collection and returns it, to be freed on its own.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.ptrs.pop().map(|ptr| unsafe { T::from_ptr(ptr) })
+    }
+
+    /// Returns a shared reference to the value at `index`.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T::Ref> {
+        self.ptrs
+            .get(index)
+            .map(|&ptr| unsafe { T::Ref::from_ptr(ptr) })
+    }
+
+    /// Returns a mutable reference to the value at `index`.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T::Ref>
+    where
+        T::Ref: ForeignTypeMut,
+    {
+        self.ptrs
+            .get(index)
+            .map(|&ptr| unsafe { T::Ref::from_ptr_mut(ptr) })
+    }
+
+    /// Returns an iterator over shared references to the values in the collection.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            ptrs: self.ptrs.iter(),
+            _p: PhantomData,
+        }
+    }
+
+    /// Returns the values in the collection as an array of raw pointers.
+    #[inline]
+    pub fn as_ptrs(&self) -> &[*mut T::CType] {
+        &self.ptrs
+    }
+
+    /// Converts the collection into individual owned values, each of which is freed on its own.
+    #[inline]
+    pub fn into_vec(mut self) -> Vec<T> {
+        self.ptrs
+            .drain(..)
+            .map(|ptr| unsafe { T::from_ptr(ptr) })
+            .collect()
+    }
+}
+
+impl<T> Default for BatchOwned<T>
+where
+    T: BatchFree,
+{
+    #[inline]
+    fn default() -> BatchOwned<T> {
+        BatchOwned::new()
+    }
+}
+
+impl<T> Extend<T> for BatchOwned<T>
+where
+    T: BatchFree,
+{
+    #[inline]
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.ptrs.extend(iter.into_iter().map(T::into_ptr));
+    }
+}
+
+impl<T> FromIterator<T> for BatchOwned<T>
+where
+    T: BatchFree,
+{
+    #[inline]
+    fn from_iter<I>(iter: I) -> BatchOwned<T>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut batch = BatchOwned::new();
+        batch.extend(iter);
+        batch
+    }
+}
+
+impl<T> From<Vec<T>> for BatchOwned<T>
+where
+    T: BatchFree,
+{
+    #[inline]
+    fn from(values: Vec<T>) -> BatchOwned<T> {
+        values.into_iter().collect()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BatchOwned<T>
+where
+    T: BatchFree,
+{
+    type Item = &'a T::Ref;
+    type IntoIter = Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// An iterator over shared references to the values in a `BatchOwned`.
+pub struct Iter<'a, T>
+where
+    T: BatchFree + 'a,
+{
+    ptrs: slice::Iter<'a, *mut T::CType>,
+    _p: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T>
+where
+    T: BatchFree + 'a,
+{
+    type Item = &'a T::Ref;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a T::Ref> {
+        self.ptrs.next().map(|&ptr| unsafe { T::Ref::from_ptr(ptr) })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ptrs.size_hint()
+    }
+}
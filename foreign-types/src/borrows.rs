@@ -0,0 +1,184 @@
+//! Runtime detection of aliasing borrows.
+//!
+//! C callbacks can re-enter Rust code with a pointer to an object which is already borrowed, and
+//! converting that pointer into a reference can create a `&mut` which aliases another reference to
+//! the same object. Neither the borrow checker nor Miri can see across the FFI boundary to catch
+//! this.
+//!
+//! When the `debug-borrows` feature is enabled, the methods generated by `foreign_type!` record a
+//! shared or exclusive borrow of their receiver's pointer for the duration of each call, and panic
+//! if it conflicts with a borrow which is already active. Methods taking `&self` record a shared
+//! borrow, while methods taking `&mut self` and `Drop` record an exclusive borrow. References
+//! themselves are not tracked, so conflicts are only detected while a generated method is running.
+//!
+//! `BorrowGuard` can be used to record borrows in handwritten methods in the same way.
+//!
+//! This module requires the `debug-borrows` feature.
+//!
+//! # Examples
+//!
+//! ```should_panic
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::ForeignTypeRef;
+//! use std::os::raw::c_int;
+//!
+//! # mod cb_sys {
+//! #     use std::os::raw::c_int;
+//! #     pub struct CTX { pub flags: c_int }
+//! #     pub unsafe extern "C" fn CTX_free(ctx: *mut CTX) { drop(Box::from_raw(ctx)) }
+//! #     pub unsafe fn CTX_set_flags(ctx: *mut CTX, flags: c_int) { (*ctx).flags = flags }
+//! #     pub unsafe fn CTX_get_flags(ctx: *mut CTX) -> c_int {
+//! #         // The library calls back into Rust with the context while reading its flags.
+//! #         ::on_callback(ctx);
+//! #         (*ctx).flags
+//! #     }
+//! # }
+//! foreign_type! {
+//!     type CType = cb_sys::CTX;
+//!     fn drop = cb_sys::CTX_free;
+//!     getters {
+//!         fn flags -> c_int = cb_sys::CTX_get_flags;
+//!     }
+//!     setters {
+//!         fn set_flags(c_int) = cb_sys::CTX_set_flags;
+//!     }
+//!     /// A context.
+//!     pub struct Ctx;
+//!     /// A borrowed context.
+//!     pub struct CtxRef;
+//! }
+//!
+//! fn on_callback(ctx: *mut cb_sys::CTX) {
+//!     // This creates a `&mut CtxRef` aliasing the `&CtxRef` borrowed by `flags`.
+//!     let ctx = unsafe { CtxRef::from_ptr_mut(ctx) };
+//!     ctx.set_flags(1);
+//! }
+//!
+//! # fn main() {
+//! # let ctx: Ctx = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(cb_sys::CTX { flags: 0 }))) };
+//! ctx.flags();
+//! # }
+//! ```
+use core::ffi::c_void;
+use core::fmt;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+
+use ForeignTypeRef;
+
+const EXCLUSIVE: isize = -1;
+
+static ACTIVE: Mutex<BTreeMap<usize, isize>> = Mutex::new(BTreeMap::new());
+
+fn active() -> MutexGuard<'static, BTreeMap<usize, isize>> {
+    ACTIVE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A record of an active borrow, which is released when the guard is dropped.
+pub struct BorrowGuard {
+    ptr: usize,
+    exclusive: bool,
+    tracked: bool,
+}
+
+impl BorrowGuard {
+    /// Records a shared borrow of `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is exclusively borrowed.
+    #[inline]
+    pub fn shared<T>(value: &T) -> BorrowGuard
+    where
+        T: ForeignTypeRef,
+    {
+        __shared(core::any::type_name::<T>(), value.as_ptr() as *mut c_void)
+    }
+
+    /// Records an exclusive borrow of `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is already borrowed.
+    #[inline]
+    pub fn exclusive<T>(value: &mut T) -> BorrowGuard
+    where
+        T: ForeignTypeRef,
+    {
+        __exclusive(core::any::type_name::<T>(), value.as_ptr() as *mut c_void)
+    }
+}
+
+impl Drop for BorrowGuard {
+    fn drop(&mut self) {
+        if !self.tracked {
+            return;
+        }
+
+        let mut active = active();
+        let remove = match active.get_mut(&self.ptr) {
+            Some(state) if !self.exclusive && *state > 1 => {
+                *state -= 1;
+                false
+            }
+            Some(_) => true,
+            None => false,
+        };
+        if remove {
+            active.remove(&self.ptr);
+        }
+    }
+}
+
+impl fmt::Debug for BorrowGuard {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BorrowGuard")
+            .field("ptr", &(self.ptr as *mut c_void))
+            .field("exclusive", &self.exclusive)
+            .finish()
+    }
+}
+
+#[doc(hidden)]
+pub fn __shared(type_name: &'static str, ptr: *mut c_void) -> BorrowGuard {
+    let mut active = active();
+    let state = active.entry(ptr as usize).or_insert(0);
+    if *state == EXCLUSIVE {
+        drop(active);
+        panic!("{} at {:p} borrowed while it is mutably borrowed", type_name, ptr);
+    }
+    *state += 1;
+    BorrowGuard {
+        ptr: ptr as usize,
+        exclusive: false,
+        tracked: true,
+    }
+}
+
+#[doc(hidden)]
+pub fn __exclusive(type_name: &'static str, ptr: *mut c_void) -> BorrowGuard {
+    let mut active = active();
+    let state = active.entry(ptr as usize).or_insert(0);
+    if *state != 0 {
+        // Values dropped while unwinding from a detected conflict are still borrowed by the
+        // frames being unwound, so the conflict is not reported again.
+        if thread::panicking() {
+            return BorrowGuard {
+                ptr: ptr as usize,
+                exclusive: true,
+                tracked: false,
+            };
+        }
+        drop(active);
+        panic!("{} at {:p} mutably borrowed while it is already borrowed", type_name, ptr);
+    }
+    *state = EXCLUSIVE;
+    BorrowGuard {
+        ptr: ptr as usize,
+        exclusive: true,
+        tracked: true,
+    }
+}
@@ -0,0 +1,335 @@
+//! Owned byte buffers allocated by C.
+//!
+//! Many C functions return a buffer as a pointer and a length, which the caller is responsible for
+//! freeing with a library-specific function. `ForeignBytes` owns such a buffer, dereferences to
+//! its contents, and frees it with a `Deleter` when dropped.
+//!
+//! # Examples
+//!
+//! ```
+//! extern crate foreign_types;
+//!
+//! use foreign_types::buffer::{Deleter, ForeignBytes};
+//!
+//! # mod crypto_sys {
+//! #     pub unsafe fn CRYPTO_encode(out: *mut *mut u8, len: *mut usize) {
+//! #         let buf = b"encoded".to_vec().into_boxed_slice();
+//! #         *len = buf.len();
+//! #         *out = Box::into_raw(buf) as *mut u8;
+//! #     }
+//! #     pub unsafe fn CRYPTO_free(ptr: *mut u8, len: usize) {
+//! #         drop(Box::from_raw(std::slice::from_raw_parts_mut(ptr, len)))
+//! #     }
+//! # }
+//! /// Frees buffers allocated by the crypto library.
+//! pub enum CryptoFree {}
+//!
+//! unsafe impl Deleter for CryptoFree {
+//!     unsafe fn delete(ptr: *mut u8, len: usize) {
+//!         crypto_sys::CRYPTO_free(ptr, len)
+//!     }
+//! }
+//!
+//! fn encode() -> ForeignBytes<CryptoFree> {
+//!     let mut ptr = 0 as *mut u8;
+//!     let mut len = 0;
+//!     unsafe {
+//!         crypto_sys::CRYPTO_encode(&mut ptr, &mut len);
+//!         ForeignBytes::from_raw_parts(ptr, len)
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let buf = encode();
+//! assert_eq!(&*buf, b"encoded");
+//! assert_eq!(buf.into_vec(), b"encoded");
+//! # }
+//! ```
+//!
+//! Functions which instead fill a caller-provided buffer, after being called once to query the
+//! required size, can be wrapped with `double_call_buffer`:
+//!
+//! ```
+//! extern crate foreign_types;
+//!
+//! use foreign_types::buffer::{double_call_buffer, Fill};
+//!
+//! # mod dev_sys {
+//! #     use std::os::raw::c_int;
+//! #     pub const ERANGE: c_int = 34;
+//! #     pub unsafe fn DEV_name(buf: *mut u8, len: usize) -> usize {
+//! #         let name = b"sda";
+//! #         if len >= name.len() { std::ptr::copy_nonoverlapping(name.as_ptr(), buf, name.len()) }
+//! #         name.len()
+//! #     }
+//! #     pub unsafe fn DEV_path(buf: *mut u8, len: usize) -> c_int {
+//! #         let path = [b'x'; 200];
+//! #         if len <= path.len() { return ERANGE }
+//! #         std::ptr::copy_nonoverlapping(path.as_ptr(), buf, path.len());
+//! #         *buf.add(path.len()) = 0;
+//! #         0
+//! #     }
+//! # }
+//! use std::os::raw::c_int;
+//!
+//! /// Returns the name of the device.
+//! ///
+//! /// `DEV_name` returns the length of the name, and only writes it if the buffer is large enough.
+//! fn name() -> Vec<u8> {
+//!     let name = double_call_buffer(|buf, len| unsafe {
+//!         Fill::<()>::Len(dev_sys::DEV_name(buf, len))
+//!     });
+//!     name.unwrap()
+//! }
+//!
+//! /// Returns the path of the device.
+//! ///
+//! /// `DEV_path` writes a nul-terminated path, or fails with `ERANGE` if the buffer is too small.
+//! fn path() -> Result<Vec<u8>, c_int> {
+//!     double_call_buffer(|buf, len| match unsafe { dev_sys::DEV_path(buf, len) } {
+//!         0 => Fill::Len(unsafe { std::ffi::CStr::from_ptr(buf as *const _) }.to_bytes().len()),
+//!         dev_sys::ERANGE => Fill::TooSmall,
+//!         e => Fill::Err(e),
+//!     })
+//! }
+//!
+//! # fn main() {
+//! assert_eq!(name(), b"sda");
+//! assert_eq!(path().unwrap().len(), 200);
+//! # }
+//! ```
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::slice;
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+/// A function which frees byte buffers allocated by a C library.
+///
+/// # Safety
+///
+/// Implementations must free buffers allocated by the library which `ForeignBytes` values using
+/// this deleter are created from. If the implementing type is `Send` or `Sync`, `delete` must be
+/// safe to call on a buffer from any thread.
+pub unsafe trait Deleter {
+    /// Frees the buffer of `len` bytes starting at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a non-null buffer of `len` bytes which was allocated by the C library and
+    /// has not already been freed.
+    unsafe fn delete(ptr: *mut u8, len: usize);
+}
+
+/// An owned byte buffer allocated by C, freed with `D` when dropped.
+pub struct ForeignBytes<D>
+where
+    D: Deleter,
+{
+    ptr: *mut u8,
+    len: usize,
+    _p: PhantomData<D>,
+}
+
+unsafe impl<D> Send for ForeignBytes<D> where D: Deleter + Send {}
+
+unsafe impl<D> Sync for ForeignBytes<D> where D: Deleter + Sync {}
+
+impl<D> Drop for ForeignBytes<D>
+where
+    D: Deleter,
+{
+    #[inline]
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { D::delete(self.ptr, self.len) }
+        }
+    }
+}
+
+impl<D> ForeignBytes<D>
+where
+    D: Deleter,
+{
+    /// Takes ownership of a buffer of `len` bytes starting at `ptr`.
+    ///
+    /// A null `ptr` is treated as an empty buffer, and is not passed to the deleter.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must either be null or a buffer of `len` initialized bytes which can be freed by `D`,
+    /// and which is not used elsewhere for the lifetime of the returned value.
+    #[inline]
+    pub unsafe fn from_raw_parts(ptr: *mut u8, len: usize) -> ForeignBytes<D> {
+        ForeignBytes {
+            ptr,
+            len: if ptr.is_null() { 0 } else { len },
+            _p: PhantomData,
+        }
+    }
+
+    /// Releases ownership of the buffer, returning its pointer and length.
+    ///
+    /// The buffer must be freed by the caller.
+    #[inline]
+    pub fn into_raw_parts(self) -> (*mut u8, usize) {
+        let parts = (self.ptr, self.len);
+        ::core::mem::forget(self);
+        parts
+    }
+
+    /// Returns a raw pointer to the buffer, which is null if the buffer was created from a null
+    /// pointer.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    /// Copies the contents of the buffer into a `Vec`, freeing the original.
+    ///
+    /// This method requires the `std` feature, which is enabled by default.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn into_vec(self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl<D> Deref for ForeignBytes<D>
+where
+    D: Deleter,
+{
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.ptr, self.len) }
+        }
+    }
+}
+
+impl<D> DerefMut for ForeignBytes<D>
+where
+    D: Deleter,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        if self.ptr.is_null() {
+            &mut []
+        } else {
+            unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+        }
+    }
+}
+
+impl<D> AsRef<[u8]> for ForeignBytes<D>
+where
+    D: Deleter,
+{
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<D> AsMut<[u8]> for ForeignBytes<D>
+where
+    D: Deleter,
+{
+    #[inline]
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl<D> Default for ForeignBytes<D>
+where
+    D: Deleter,
+{
+    #[inline]
+    fn default() -> ForeignBytes<D> {
+        unsafe { ForeignBytes::from_raw_parts(ptr::null_mut(), 0) }
+    }
+}
+
+impl<D> fmt::Debug for ForeignBytes<D>
+where
+    D: Deleter,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+/// The result of one call of a function passed to `double_call_buffer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fill<E> {
+    /// The number of bytes the called function requires, or has written.
+    ///
+    /// If this is larger than the buffer, the buffer is treated as too small, and its contents are
+    /// discarded.
+    Len(usize),
+    /// The buffer was too small, and the called function did not report the required size.
+    TooSmall,
+    /// The called function failed.
+    Err(E),
+}
+
+impl<E> From<Result<usize, E>> for Fill<E> {
+    #[inline]
+    fn from(result: Result<usize, E>) -> Fill<E> {
+        match result {
+            Ok(len) => Fill::Len(len),
+            Err(e) => Fill::Err(e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+const INITIAL_CAPACITY: usize = 64;
+
+/// Calls a "query size, then fill" C function, returning the filled bytes.
+///
+/// `f` is first called with a null pointer and a length of zero, and then with a zeroed buffer of
+/// the queried size, which is truncated to the length that `f` returns. An empty `Vec` is returned
+/// without a second call if the queried size is zero.
+///
+/// If the size increases between calls, `f` is called again with a larger buffer. If `f` returns
+/// `Fill::TooSmall`, as functions failing with `ERANGE` do, the buffer is doubled in size, starting
+/// from 64 bytes if the size could not be queried.
+///
+/// This function requires the `std` feature, which is enabled by default.
+///
+/// # Panics
+///
+/// Panics if the buffer size would overflow a `usize`.
+#[cfg(feature = "std")]
+pub fn double_call_buffer<F, E>(mut f: F) -> Result<Vec<u8>, E>
+where
+    F: FnMut(*mut u8, usize) -> Fill<E>,
+{
+    let mut cap = match f(ptr::null_mut(), 0) {
+        Fill::Len(0) => return Ok(Vec::new()),
+        Fill::Len(len) => len,
+        Fill::TooSmall => INITIAL_CAPACITY,
+        Fill::Err(e) => return Err(e),
+    };
+
+    loop {
+        let mut buf = std::vec![0; cap];
+        match f(buf.as_mut_ptr(), cap) {
+            Fill::Len(len) if len <= cap => {
+                buf.truncate(len);
+                return Ok(buf);
+            }
+            Fill::Len(len) => cap = len,
+            Fill::TooSmall => cap = cap.checked_mul(2).expect("buffer size overflow"),
+            Fill::Err(e) => return Err(e),
+        }
+    }
+}
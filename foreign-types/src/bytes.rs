@@ -0,0 +1,184 @@
+//! Integration with the `bytes` crate.
+//!
+//! `ForeignBuf` exposes a byte buffer owned by a C object as a `bytes::Buf`, and can be converted
+//! into a `Bytes` without copying. In the other direction, `bytes_into_raw` and `release_bytes`
+//! allow a `Bytes` to be handed to C APIs which accept a release callback for their data.
+//!
+//! This module requires the `bytes` feature.
+use core::ffi::c_void;
+use core::slice;
+use std::boxed::Box;
+
+use bytes_crate::{Buf, Bytes};
+
+/// A byte buffer owned by a C object.
+///
+/// The buffer remains valid for as long as its owner, typically a `ForeignType` wrapper, is alive.
+/// The owner is dropped, freeing the buffer, when the `ForeignBuf` is.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate bytes;
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use bytes::Buf;
+/// use foreign_types::bytes::ForeignBuf;
+///
+/// # mod buf_sys {
+/// #     pub struct BUF(pub Vec<u8>);
+/// #     pub unsafe extern "C" fn BUF_free(buf: *mut BUF) { drop(Box::from_raw(buf)) }
+/// #     pub unsafe fn BUF_data(buf: *mut BUF) -> *const u8 { (*buf).0.as_ptr() }
+/// #     pub unsafe fn BUF_len(buf: *mut BUF) -> usize { (*buf).0.len() }
+/// # }
+/// foreign_type! {
+///     type CType = buf_sys::BUF;
+///     fn drop = buf_sys::BUF_free;
+///     unsafe impl Send;
+///     getters {
+///         fn data -> *const u8 = buf_sys::BUF_data;
+///         fn len -> usize = buf_sys::BUF_len;
+///     }
+///     /// A buffer.
+///     pub struct Buffer;
+///     /// A borrowed buffer.
+///     pub struct BufferRef;
+/// }
+///
+/// fn into_buf(buffer: Buffer) -> ForeignBuf<Buffer> {
+///     let (data, len) = (buffer.data(), buffer.len());
+///     unsafe { ForeignBuf::new(buffer, data, len) }
+/// }
+///
+/// # fn main() {
+/// # let buffer: Buffer = unsafe {
+/// #     foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(buf_sys::BUF(b"hello world".to_vec()))))
+/// # };
+/// let mut buf = into_buf(buffer);
+/// buf.advance(6);
+/// assert_eq!(buf.into_bytes(), &b"world"[..]);
+/// # }
+/// ```
+pub struct ForeignBuf<T> {
+    owner: T,
+    data: *const u8,
+    len: usize,
+    pos: usize,
+}
+
+// The buffer is only accessed through `&self` or `&mut self`, and is owned by `owner`.
+unsafe impl<T: Send> Send for ForeignBuf<T> {}
+unsafe impl<T: Sync> Sync for ForeignBuf<T> {}
+
+impl<T> ForeignBuf<T> {
+    /// Creates a new `ForeignBuf` over the `len` bytes at `data`, which are owned by `owner`.
+    ///
+    /// # Safety
+    ///
+    /// `data` must point to `len` initialized bytes which remain valid and unmodified until `owner`
+    /// is dropped.
+    #[inline]
+    pub unsafe fn new(owner: T, data: *const u8, len: usize) -> ForeignBuf<T> {
+        ForeignBuf {
+            owner,
+            data,
+            len,
+            pos: 0,
+        }
+    }
+
+    /// Returns a shared reference to the owner of the buffer.
+    #[inline]
+    pub fn owner(&self) -> &T {
+        &self.owner
+    }
+
+    /// Consumes the `ForeignBuf`, returning the owner of the buffer.
+    #[inline]
+    pub fn into_owner(self) -> T {
+        self.owner
+    }
+}
+
+impl<T> ForeignBuf<T>
+where
+    T: Send + 'static,
+{
+    /// Converts the unread part of the buffer into a `Bytes` without copying.
+    ///
+    /// The owner is dropped when the last `Bytes` referring to the buffer is.
+    #[inline]
+    pub fn into_bytes(self) -> Bytes {
+        Bytes::from_owner(self)
+    }
+}
+
+impl<T> AsRef<[u8]> for ForeignBuf<T> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+
+        unsafe { slice::from_raw_parts(self.data.add(self.pos), self.len - self.pos) }
+    }
+}
+
+impl<T> Buf for ForeignBuf<T> {
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.len - self.pos
+    }
+
+    #[inline]
+    fn chunk(&self) -> &[u8] {
+        self.as_ref()
+    }
+
+    #[inline]
+    fn advance(&mut self, cnt: usize) {
+        assert!(
+            cnt <= self.remaining(),
+            "cannot advance past the end of the buffer"
+        );
+        self.pos += cnt;
+    }
+}
+
+/// Converts a `Bytes` into a raw pointer and length for use by C, along with a userdata pointer
+/// which keeps the data alive.
+///
+/// The userdata pointer must eventually be passed to `release_bytes`, typically by the C library
+/// once it is done with the data.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate bytes;
+/// # extern crate foreign_types;
+/// # use bytes::Bytes;
+/// # use foreign_types::bytes::{bytes_into_raw, release_bytes};
+/// # fn main() {
+/// let (data, len, userdata) = bytes_into_raw(Bytes::from_static(b"hello"));
+/// assert_eq!(unsafe { std::slice::from_raw_parts(data, len) }, b"hello");
+/// unsafe { release_bytes(userdata) };
+/// # }
+/// ```
+pub fn bytes_into_raw(bytes: Bytes) -> (*const u8, usize, *mut c_void) {
+    let data = bytes.as_ptr();
+    let len = bytes.len();
+    let userdata = Box::into_raw(Box::new(bytes)) as *mut c_void;
+    (data, len, userdata)
+}
+
+/// Releases data passed to C with `bytes_into_raw`.
+///
+/// This has the signature of a typical C release callback, so it can be passed directly to C APIs.
+///
+/// # Safety
+///
+/// `userdata` must have been returned by `bytes_into_raw`, and not yet released.
+pub unsafe extern "C" fn release_bytes(userdata: *mut c_void) {
+    drop(Box::from_raw(userdata as *mut Bytes));
+}
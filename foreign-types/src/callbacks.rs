@@ -0,0 +1,133 @@
+//! Storage for Rust closures registered as C callbacks.
+//!
+//! C APIs of the form `X_set_callback(obj, callback, userdata)` call `callback` with `userdata`
+//! until it is replaced or the object is freed. The methods generated by a `callbacks` block in
+//! `foreign_type!` box a Rust closure, pass a pointer to it as the userdata, and store the box here
+//! until it is no longer needed: when the callback is replaced, or after the last owned value
+//! wrapping the object has been freed.
+//!
+//! Owned values are counted per object, so that dropping one of several owners of the same object,
+//! such as clones of a `ForeignRc`, does not free closures the object may still call. Types with a
+//! `fn refcount_get = ...;` entry additionally keep their closures if the C library holds other
+//! references to the object when the last owned value is dropped.
+//!
+//! Callbacks set through a reference to an object which is not owned by a Rust wrapper, or on an
+//! owned value which is then released with `into_ptr`, are only freed when they are replaced or
+//! when the last owned value wrapping the object is dropped.
+//!
+//! This module requires the `std` feature, which is enabled by default.
+use core::ffi::c_void;
+use std::boxed::Box;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::vec::Vec;
+
+type Key = (usize, &'static str, &'static str);
+
+static STORED: Mutex<BTreeMap<Key, __Stored>> = Mutex::new(BTreeMap::new());
+
+// The number of owned values wrapping each object with callbacks.
+static OWNERS: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+fn stored() -> MutexGuard<'static, BTreeMap<Key, __Stored>> {
+    STORED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn owners() -> MutexGuard<'static, BTreeMap<usize, usize>> {
+    OWNERS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns the number of closures currently stored for C callbacks.
+///
+/// This is useful to detect callbacks which are never freed, such as those set on objects which
+/// are not owned by a Rust wrapper.
+pub fn live_callbacks() -> usize {
+    stored().len()
+}
+
+#[doc(hidden)]
+pub struct __Stored {
+    ptr: *mut c_void,
+    drop: unsafe fn(*mut c_void),
+}
+
+// Only closures which are `Send` are stored.
+unsafe impl Send for __Stored {}
+
+impl __Stored {
+    #[inline]
+    pub fn new<F>(f: F) -> __Stored
+    where
+        F: Send + 'static,
+    {
+        unsafe fn drop_box<F>(ptr: *mut c_void) {
+            drop(Box::from_raw(ptr as *mut F));
+        }
+
+        __Stored {
+            ptr: Box::into_raw(Box::new(f)) as *mut c_void,
+            drop: drop_box::<F>,
+        }
+    }
+
+    #[inline]
+    pub fn userdata(&self) -> *mut c_void {
+        self.ptr
+    }
+}
+
+impl Drop for __Stored {
+    fn drop(&mut self) {
+        unsafe { (self.drop)(self.ptr) }
+    }
+}
+
+#[doc(hidden)]
+pub fn __replace(
+    obj: *mut c_void,
+    type_name: &'static str,
+    name: &'static str,
+    callback: __Stored,
+) -> Option<__Stored> {
+    stored().insert((obj as usize, type_name, name), callback)
+}
+
+#[doc(hidden)]
+pub fn __retain(obj: *mut c_void) {
+    *owners().entry(obj as usize).or_insert(0) += 1;
+}
+
+/// Returns `true` if the released value was the last owner of the object.
+#[doc(hidden)]
+pub fn __release(obj: *mut c_void) -> bool {
+    let mut owners = owners();
+    match owners.get_mut(&(obj as usize)) {
+        Some(count) if *count > 1 => {
+            *count -= 1;
+            false
+        }
+        Some(_) => {
+            owners.remove(&(obj as usize));
+            true
+        }
+        None => true,
+    }
+}
+
+#[doc(hidden)]
+pub fn __clear(obj: *mut c_void) {
+    let obj = obj as usize;
+    let mut stored = stored();
+    let keys = stored
+        .range((obj, "", "")..)
+        .take_while(|(key, _)| key.0 == obj)
+        .map(|(key, _)| *key)
+        .collect::<Vec<_>>();
+    let removed = keys
+        .iter()
+        .filter_map(|key| stored.remove(key))
+        .collect::<Vec<_>>();
+    // Closures are dropped without the lock held, since their destructors may free other values.
+    drop(stored);
+    drop(removed);
+}
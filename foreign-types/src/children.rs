@@ -0,0 +1,160 @@
+//! Detection of parents destroyed while their children are still in use.
+//!
+//! Many C objects hand out pointers to children which are only valid for as long as their parent
+//! is alive. The borrow checker prevents most misuse of the borrowed types, but references can
+//! still escape through callbacks or `'static` casts. A `ChildGuard` registers a child with its
+//! parent for as long as the guard is alive.
+//!
+//! When the `debug-children` feature is enabled, dropping or destroying an owned value created by
+//! `foreign_type!` panics if any children are still registered with it. When the feature is
+//! disabled, `ChildGuard` does nothing and has no cost, so registrations can be left in place.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::children::ChildGuard;
+//! use foreign_types::ForeignTypeRef;
+//!
+//! # mod tree_sys {
+//! #     pub struct NODE;
+//! #     pub struct TREE(pub NODE);
+//! #     pub unsafe extern "C" fn TREE_free(tree: *mut TREE) { drop(Box::from_raw(tree)) }
+//! #     pub unsafe fn TREE_root(tree: *mut TREE) -> *mut NODE { &mut (*tree).0 }
+//! # }
+//! foreign_type! {
+//!     type CType = tree_sys::TREE;
+//!     fn drop = tree_sys::TREE_free;
+//!     /// A tree.
+//!     pub struct Tree;
+//!     /// A borrowed tree.
+//!     pub struct TreeRef;
+//! }
+//!
+//! /// A handle to the root of a tree, which must not outlive the tree.
+//! pub struct Root {
+//!     node: *mut tree_sys::NODE,
+//!     _guard: ChildGuard,
+//! }
+//!
+//! impl TreeRef {
+//!     pub fn root(&self) -> Root {
+//!         Root {
+//!             node: unsafe { tree_sys::TREE_root(self.as_ptr()) },
+//!             _guard: ChildGuard::new(self),
+//!         }
+//!     }
+//! }
+//!
+//! # fn main() {
+//! # let tree: Tree = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(tree_sys::TREE(tree_sys::NODE)))) };
+//! let root = tree.root();
+//! // Dropping `tree` here would panic with the `debug-children` feature enabled.
+//! drop(root);
+//! drop(tree);
+//! # }
+//! ```
+#[cfg(feature = "debug-children")]
+use core::ffi::c_void;
+use core::fmt;
+#[cfg(feature = "debug-children")]
+use std::collections::BTreeMap;
+#[cfg(feature = "debug-children")]
+use std::sync::Mutex;
+
+use ForeignTypeRef;
+
+#[cfg(feature = "debug-children")]
+static LIVE: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+#[cfg(feature = "debug-children")]
+fn live() -> std::sync::MutexGuard<'static, BTreeMap<usize, usize>> {
+    LIVE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A registration of a child with its parent, which lasts until the guard is dropped.
+pub struct ChildGuard {
+    #[cfg(feature = "debug-children")]
+    parent: usize,
+}
+
+impl ChildGuard {
+    /// Registers a child with `parent`.
+    #[inline]
+    pub fn new<P>(parent: &P) -> ChildGuard
+    where
+        P: ForeignTypeRef,
+    {
+        #[cfg(feature = "debug-children")]
+        {
+            let parent = parent.as_ptr() as usize;
+            *live().entry(parent).or_insert(0) += 1;
+            ChildGuard { parent }
+        }
+        #[cfg(not(feature = "debug-children"))]
+        {
+            let _ = parent;
+            ChildGuard {}
+        }
+    }
+}
+
+impl Drop for ChildGuard {
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(feature = "debug-children")]
+        {
+            let mut live = live();
+            let remove = match live.get_mut(&self.parent) {
+                Some(count) => {
+                    *count -= 1;
+                    *count == 0
+                }
+                None => false,
+            };
+            if remove {
+                live.remove(&self.parent);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ChildGuard {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ChildGuard").finish()
+    }
+}
+
+/// Returns the number of children registered with `parent`.
+///
+/// This is always zero when the `debug-children` feature is disabled.
+#[inline]
+pub fn live_children<P>(parent: &P) -> usize
+where
+    P: ForeignTypeRef,
+{
+    #[cfg(feature = "debug-children")]
+    {
+        live().get(&(parent.as_ptr() as usize)).cloned().unwrap_or(0)
+    }
+    #[cfg(not(feature = "debug-children"))]
+    {
+        let _ = parent;
+        0
+    }
+}
+
+#[cfg(feature = "debug-children")]
+#[doc(hidden)]
+pub fn __check_parent(type_name: &'static str, ptr: *mut c_void) {
+    if std::thread::panicking() {
+        return;
+    }
+
+    let count = live().get(&(ptr as usize)).cloned().unwrap_or(0);
+    if count != 0 {
+        panic!("{} destroyed while {} of its children are still alive", type_name, count);
+    }
+}
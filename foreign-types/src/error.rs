@@ -0,0 +1,196 @@
+use core::error::Error;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "std")]
+use ForeignType;
+
+/// An error returned when a foreign value could not be duplicated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CloneError(());
+
+impl CloneError {
+    #[doc(hidden)]
+    pub fn __new() -> CloneError {
+        CloneError(())
+    }
+}
+
+impl fmt::Display for CloneError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("failed to duplicate foreign value")
+    }
+}
+
+impl Error for CloneError {}
+
+/// An error returned when a pointer fails the validity check of its C type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationError(());
+
+impl ValidationError {
+    #[doc(hidden)]
+    pub fn __new() -> ValidationError {
+        ValidationError(())
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("foreign value failed validation")
+    }
+}
+
+impl Error for ValidationError {}
+
+/// An error returned when a foreign value could not be parsed from bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError(());
+
+impl ParseError {
+    #[doc(hidden)]
+    pub fn __new() -> ParseError {
+        ParseError(())
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("failed to parse foreign value")
+    }
+}
+
+impl Error for ParseError {}
+
+/// An error returned when a foreign value could not be serialized to bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeError(());
+
+impl EncodeError {
+    #[doc(hidden)]
+    pub fn __new() -> EncodeError {
+        EncodeError(())
+    }
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("failed to serialize foreign value")
+    }
+}
+
+impl Error for EncodeError {}
+
+/// An error returned when a foreign value could not be converted into another type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConvertError(());
+
+impl ConvertError {
+    #[doc(hidden)]
+    pub fn __new() -> ConvertError {
+        ConvertError(())
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("failed to convert foreign value")
+    }
+}
+
+impl Error for ConvertError {}
+
+/// An error returned when a raw C value does not correspond to any variant of a `foreign_enum!`
+/// type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownValue<T>(T);
+
+impl<T> UnknownValue<T> {
+    #[doc(hidden)]
+    pub fn __new(value: T) -> UnknownValue<T> {
+        UnknownValue(value)
+    }
+
+    /// Returns the raw value which was not recognized.
+    pub fn value(&self) -> T
+    where
+        T: Copy,
+    {
+        self.0
+    }
+}
+
+impl<T> fmt::Display for UnknownValue<T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "unknown foreign enum value {}", self.0)
+    }
+}
+
+impl<T> Error for UnknownValue<T> where T: fmt::Debug + fmt::Display {}
+
+/// Constructs an owned value from a pointer returned by a C constructor which reports failure
+/// through `errno`.
+///
+/// If `ptr` is `INVALID`, the current value of `errno` (or `GetLastError` on Windows) is captured
+/// as an `io::Error` before anything else can overwrite it. The function should therefore be called
+/// immediately after the C constructor returns.
+///
+/// This function requires the `std` feature, which is enabled by default.
+///
+/// # Safety
+///
+/// `ptr` must either be `INVALID` or a valid, owned instance of the C type.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use std::io;
+///
+/// # mod file_sys {
+/// #     pub struct FILE;
+/// #     pub unsafe extern "C" fn FILE_free(file: *mut FILE) { drop(Box::from_raw(file)) }
+/// #     pub unsafe fn FILE_open(path: &str) -> *mut FILE {
+/// #         match std::fs::File::open(path) {
+/// #             Ok(_) => Box::into_raw(Box::new(FILE)),
+/// #             Err(_) => 0 as *mut _,
+/// #         }
+/// #     }
+/// # }
+/// foreign_type! {
+///     type CType = file_sys::FILE;
+///     fn drop = file_sys::FILE_free;
+///     /// A file.
+///     pub struct File;
+///     /// A borrowed file.
+///     pub struct FileRef;
+/// }
+///
+/// fn open(path: &str) -> io::Result<File> {
+///     unsafe { foreign_types::from_ptr_errno(file_sys::FILE_open(path)) }
+/// }
+///
+/// # fn main() {
+/// # if cfg!(any(unix, windows)) {
+/// let err = open("/this/path/does/not/exist").err().unwrap();
+/// assert_eq!(err.kind(), io::ErrorKind::NotFound);
+/// # }
+/// # }
+/// ```
+#[cfg(feature = "std")]
+#[inline]
+pub unsafe fn from_ptr_errno<T>(ptr: *mut T::CType) -> io::Result<T>
+where
+    T: ForeignType,
+{
+    match T::from_ptr_opt(ptr) {
+        Some(value) => Ok(value),
+        None => Err(io::Error::last_os_error()),
+    }
+}
@@ -0,0 +1,96 @@
+//! Adapters for C visitor functions.
+//!
+//! C collections often only offer iteration through a function like
+//! `X_foreach(obj, callback, userdata)`, which calls `callback(item, userdata)` for each item. The
+//! `for_each` function lets a Rust closure be used as the callback.
+use core::ffi::{c_int, c_void};
+
+use ForeignTypeRef;
+
+/// The type of the callback passed to C visitor functions.
+///
+/// The callback returns `CONTINUE` to request the next item, and `STOP` to end iteration early.
+pub type Callback<T> = unsafe extern "C" fn(item: *mut T, userdata: *mut c_void) -> c_int;
+
+/// The value returned by the callback to continue iteration.
+pub const CONTINUE: c_int = 0;
+
+/// The value returned by the callback to stop iteration.
+pub const STOP: c_int = 1;
+
+struct State<F, E> {
+    f: F,
+    error: Option<E>,
+}
+
+unsafe extern "C" fn callback<T, E, F>(item: *mut T::CType, userdata: *mut c_void) -> c_int
+where
+    T: ForeignTypeRef,
+    F: FnMut(&T) -> Result<(), E>,
+{
+    let state = &mut *(userdata as *mut State<F, E>);
+    match T::with_ref(item, |item| (state.f)(item)) {
+        Ok(()) => CONTINUE,
+        Err(e) => {
+            state.error = Some(e);
+            STOP
+        }
+    }
+}
+
+/// Calls a C visitor function with a Rust closure as its callback.
+///
+/// `foreach` is passed the callback and userdata pointer, and should pass them on to the C
+/// function. If `f` returns an error, iteration is stopped and the error is returned.
+///
+/// A panic in `f` cannot unwind through the C function, and will abort the process.
+///
+/// # Safety
+///
+/// `foreach` must only invoke the callback with the provided userdata pointer and valid items,
+/// and must not retain either after it returns.
+///
+/// # Examples
+///
+/// ```
+/// # use std::os::raw::c_void;
+/// # use foreign_types::{foreach, Opaque, ForeignTypeRef};
+/// # pub struct ITEM(u32);
+/// # unsafe fn ITEMS_foreach(items: *mut ITEM, n: usize, cb: foreach::Callback<ITEM>, data: *mut c_void) {
+/// #     for i in 0..n { if cb(items.add(i), data) == foreach::STOP { break; } }
+/// # }
+/// # pub struct ItemRef(Opaque);
+/// # impl ForeignTypeRef for ItemRef { type CType = ITEM; }
+/// # impl ItemRef { fn value(&self) -> u32 { unsafe { (*self.as_ptr()).0 } } }
+/// # fn main() {
+/// # let mut items = [ITEM(1), ITEM(2), ITEM(3)];
+/// let mut sum = 0;
+/// let result = unsafe {
+///     foreach::for_each(
+///         |cb, userdata| ITEMS_foreach(items.as_mut_ptr(), items.len(), cb, userdata),
+///         |item: &ItemRef| {
+///             if item.value() > 2 {
+///                 return Err(item.value());
+///             }
+///             sum += item.value();
+///             Ok(())
+///         },
+///     )
+/// };
+/// assert_eq!(result, Err(3));
+/// assert_eq!(sum, 3);
+/// # }
+/// ```
+pub unsafe fn for_each<T, E, F, G>(foreach: G, f: F) -> Result<(), E>
+where
+    T: ForeignTypeRef,
+    F: FnMut(&T) -> Result<(), E>,
+    G: FnOnce(Callback<T::CType>, *mut c_void),
+{
+    let mut state = State { f, error: None };
+    foreach(callback::<T, E, F>, &mut state as *mut State<F, E> as *mut c_void);
+    match state.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
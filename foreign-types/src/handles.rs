@@ -0,0 +1,268 @@
+//! Tables mapping owned values to integer handles.
+//!
+//! Some C APIs can only carry a small integer through a callback registration, rather than a
+//! pointer. A `HandleTable` owns values and hands out nonzero `u32` handles for them, which can be
+//! passed through C and resolved back to the value later.
+//!
+//! This module requires the `std` feature, which is enabled by default.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::handles::HandleTable;
+//! use foreign_types::{ForeignType, ForeignTypeRef};
+//!
+//! # mod conn_sys {
+//! #     pub struct CONN(pub u32);
+//! #     pub unsafe fn CONN_new(port: u32) -> *mut CONN { Box::into_raw(Box::new(CONN(port))) }
+//! #     pub unsafe extern "C" fn CONN_free(conn: *mut CONN) { drop(Box::from_raw(conn)) }
+//! #     pub unsafe fn CONN_port(conn: *mut CONN) -> u32 { (*conn).0 }
+//! # }
+//! foreign_type! {
+//!     type CType = conn_sys::CONN;
+//!     fn drop = conn_sys::CONN_free;
+//!     unsafe impl Send;
+//!     unsafe impl Sync;
+//!     getters {
+//!         fn port -> u32 = conn_sys::CONN_port;
+//!     }
+//!     /// A connection.
+//!     pub struct Conn;
+//!     /// A borrowed connection.
+//!     pub struct ConnRef;
+//! }
+//!
+//! static CONNS: HandleTable<Conn> = HandleTable::new();
+//!
+//! // Called by the C library with the handle it was registered with.
+//! extern "C" fn on_event(handle: u32) {
+//!     if let Some(conn) = CONNS.get(handle) {
+//!         assert_eq!(conn.port(), 443);
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let handle = CONNS.insert(unsafe { Conn::from_ptr(conn_sys::CONN_new(443)) });
+//! on_event(handle);
+//!
+//! let conn = CONNS.remove(handle).unwrap();
+//! assert!(CONNS.get(handle).is_none());
+//!
+//! // A stale handle does not resolve to a value inserted later in the same slot.
+//! let other = CONNS.insert(unsafe { Conn::from_ptr(conn_sys::CONN_new(80)) });
+//! assert_ne!(other, handle);
+//! assert!(CONNS.get(handle).is_none());
+//! # drop(CONNS.remove(other));
+//! # drop(conn);
+//! # }
+//! ```
+use core::fmt;
+use core::ops::Deref;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::vec::Vec;
+
+use {ForeignType, ForeignTypeRef};
+
+// Handles hold the index of their slot plus one in their low bits, and the slot's generation in
+// their high bits. A slot's generation is incremented whenever its value is removed, so that stale
+// handles to it are rejected, and the slot is retired rather than reused once its generation would
+// wrap around.
+const INDEX_BITS: u32 = 24;
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+const MAX_GENERATION: u32 = u32::MAX >> INDEX_BITS;
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+struct Slots<T> {
+    values: Vec<Slot<T>>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl<T> Slots<T> {
+    fn get(&self, handle: u32) -> Option<&T> {
+        let (index, generation) = split(handle)?;
+        match self.values.get(index) {
+            Some(slot) if slot.generation == generation => slot.value.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// A thread-safe table of owned values, identified by `u32` handles.
+///
+/// Handles are never zero, so zero can be used by C code to mean "no handle". Each handle encodes
+/// a generation as well as a position in the table, so a handle still held after its value has been
+/// removed never resolves to a value inserted later.
+///
+/// The table is `Sync` if `T` is both `Send` and `Sync`, allowing it to be stored in a `static`.
+pub struct HandleTable<T>
+where
+    T: ForeignType,
+{
+    slots: RwLock<Slots<T>>,
+}
+
+impl<T> HandleTable<T>
+where
+    T: ForeignType,
+{
+    /// Creates an empty table.
+    #[inline]
+    pub const fn new() -> HandleTable<T> {
+        HandleTable {
+            slots: RwLock::new(Slots {
+                values: Vec::new(),
+                free: Vec::new(),
+                len: 0,
+            }),
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Slots<T>> {
+        self.slots.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Slots<T>> {
+        self.slots.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Stores a value in the table, returning its handle.
+    ///
+    /// As with `remove`, this will deadlock if a `HandleRef` from this table is alive on the same
+    /// thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the table has run out of handles, which happens once 16,777,215 slots are either
+    /// in use or retired. A slot is retired after 256 values have been removed from it.
+    pub fn insert(&self, value: T) -> u32 {
+        let mut slots = self.write();
+        let index = match slots.free.pop() {
+            Some(index) => {
+                slots.values[index].value = Some(value);
+                index
+            }
+            None => {
+                assert!(
+                    slots.values.len() < INDEX_MASK as usize,
+                    "handle table is full"
+                );
+                slots.values.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                slots.values.len() - 1
+            }
+        };
+        slots.len += 1;
+        (slots.values[index].generation << INDEX_BITS) | (index as u32 + 1)
+    }
+
+    /// Returns a reference to the value with the given handle.
+    ///
+    /// The table is locked for reading while the returned reference is alive, so inserting or
+    /// removing values on the same thread before it is dropped will deadlock.
+    pub fn get(&self, handle: u32) -> Option<HandleRef<'_, T>> {
+        let slots = self.read();
+        let ptr = slots.get(handle)?.as_ptr();
+        Some(HandleRef { _slots: slots, ptr })
+    }
+
+    /// Returns `true` if the table holds a value with the given handle.
+    pub fn contains(&self, handle: u32) -> bool {
+        self.read().get(handle).is_some()
+    }
+
+    /// Removes the value with the given handle from the table, returning it.
+    ///
+    /// The table is locked for writing, so this will deadlock if a `HandleRef` from this table is
+    /// alive on the same thread.
+    pub fn remove(&self, handle: u32) -> Option<T> {
+        let mut slots = self.write();
+        let (index, generation) = split(handle)?;
+        let slot = match slots.values.get_mut(index) {
+            Some(slot) if slot.generation == generation => slot,
+            _ => return None,
+        };
+        let value = slot.value.take()?;
+        if slot.generation < MAX_GENERATION {
+            slot.generation += 1;
+            slots.free.push(index);
+        }
+        slots.len -= 1;
+        Some(value)
+    }
+
+    /// Returns the number of values in the table.
+    pub fn len(&self) -> usize {
+        self.read().len
+    }
+
+    /// Returns `true` if the table holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn split(handle: u32) -> Option<(usize, u32)> {
+    let index = ((handle & INDEX_MASK) as usize).checked_sub(1)?;
+    Some((index, handle >> INDEX_BITS))
+}
+
+impl<T> Default for HandleTable<T>
+where
+    T: ForeignType,
+{
+    #[inline]
+    fn default() -> HandleTable<T> {
+        HandleTable::new()
+    }
+}
+
+impl<T> fmt::Debug for HandleTable<T>
+where
+    T: ForeignType,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("HandleTable")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// A reference to a value in a `HandleTable`, which keeps the table locked for reading.
+pub struct HandleRef<'a, T>
+where
+    T: ForeignType + 'a,
+{
+    _slots: RwLockReadGuard<'a, Slots<T>>,
+    ptr: *mut T::CType,
+}
+
+impl<'a, T> Deref for HandleRef<'a, T>
+where
+    T: ForeignType + 'a,
+{
+    type Target = T::Ref;
+
+    #[inline]
+    fn deref(&self) -> &T::Ref {
+        unsafe { T::Ref::from_ptr(self.ptr) }
+    }
+}
+
+impl<'a, T> fmt::Debug for HandleRef<'a, T>
+where
+    T: ForeignType + 'a,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("HandleRef").field("ptr", &self.ptr).finish()
+    }
+}
@@ -0,0 +1,110 @@
+//! Hooks observing the lifecycle of wrapped C objects.
+//!
+//! Types defined with `foreign_type!` call the hook registered with `on_create` whenever an owned
+//! value is created from a pointer, and the hook registered with `on_drop` whenever an owned value
+//! is dropped or converted back into a raw pointer. Hooks receive the name of the owned type and
+//! the pointer it wraps, and can be used to build leak detectors, object registries, or audit logs
+//! across every bindings crate in a program.
+//!
+//! Only one hook of each kind is registered at a time; registering a new hook replaces the
+//! previous one.
+//!
+//! This module requires the `hooks` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use std::os::raw::c_void;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use foreign_types::hooks;
+//!
+//! # mod foo_sys { pub struct FOO; pub unsafe extern "C" fn FOO_free(foo: *mut FOO) { drop(Box::from_raw(foo)) } }
+//! foreign_type! {
+//!     type CType = foo_sys::FOO;
+//!     fn drop = foo_sys::FOO_free;
+//!     /// A Foo.
+//!     pub struct Foo;
+//!     /// A borrowed Foo.
+//!     pub struct FooRef;
+//! }
+//!
+//! static LIVE: AtomicUsize = AtomicUsize::new(0);
+//!
+//! fn created(_: &'static str, _: *mut c_void) {
+//!     LIVE.fetch_add(1, Ordering::SeqCst);
+//! }
+//!
+//! fn dropped(_: &'static str, _: *mut c_void) {
+//!     LIVE.fetch_sub(1, Ordering::SeqCst);
+//! }
+//!
+//! # fn main() {
+//! use foreign_types::ForeignType;
+//!
+//! hooks::on_create(created);
+//! hooks::on_drop(dropped);
+//!
+//! let foo = unsafe { Foo::from_ptr(Box::into_raw(Box::new(foo_sys::FOO))) };
+//! assert_eq!(LIVE.load(Ordering::SeqCst), 1);
+//! drop(foo);
+//! assert_eq!(LIVE.load(Ordering::SeqCst), 0);
+//!
+//! hooks::clear();
+//! # }
+//! ```
+use core::ffi::c_void;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// A lifecycle hook, called with the name of the owned type and the pointer it wraps.
+pub type Hook = fn(type_name: &'static str, ptr: *mut c_void);
+
+static ON_CREATE: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+static ON_DROP: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Registers a hook called whenever an owned value is created from a pointer.
+#[inline]
+pub fn on_create(hook: Hook) {
+    ON_CREATE.store(hook as *mut (), Ordering::Release);
+}
+
+/// Registers a hook called whenever an owned value is dropped or converted into a raw pointer.
+///
+/// The hook runs before the C object is freed, so the pointer is still valid when it is called.
+#[inline]
+pub fn on_drop(hook: Hook) {
+    ON_DROP.store(hook as *mut (), Ordering::Release);
+}
+
+/// Removes all registered hooks.
+#[inline]
+pub fn clear() {
+    ON_CREATE.store(ptr::null_mut(), Ordering::Release);
+    ON_DROP.store(ptr::null_mut(), Ordering::Release);
+}
+
+#[inline]
+fn call(hook: &AtomicPtr<()>, type_name: &'static str, ptr: *mut c_void) {
+    let hook = hook.load(Ordering::Acquire);
+    if !hook.is_null() {
+        // Only `Hook` values are ever stored in the hook slots.
+        let hook = unsafe { mem::transmute::<*mut (), Hook>(hook) };
+        hook(type_name, ptr);
+    }
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __created(type_name: &'static str, ptr: *mut c_void) {
+    call(&ON_CREATE, type_name, ptr);
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __dropped(type_name: &'static str, ptr: *mut c_void) {
+    call(&ON_DROP, type_name, ptr);
+}
@@ -13,7 +13,7 @@
 //! # Examples
 //!
 //! ```
-//! use foreign_types::{ForeignType, ForeignTypeRef, Opaque};
+//! use foreign_types::{ForeignType, ForeignTypeMut, ForeignTypeRef, Opaque};
 //! use std::ops::{Deref, DerefMut};
 //!
 //! mod foo_sys {
@@ -34,6 +34,9 @@
 //!     type CType = foo_sys::FOO;
 //! }
 //!
+//! // `FOO`s may be modified, so a `&mut FooRef` can be handed out.
+//! unsafe impl ForeignTypeMut for FooRef {}
+//!
 //! // The owned type is simply a newtype wrapper around the raw C type.
 //! //
 //! // It dereferences to `FooRef`, so methods that do not require ownership
@@ -175,14 +178,121 @@
 //!
 //! # fn main() {}
 //! ```
+//!
+//! Accessors like these can also be generated by `foreign_type!` with a `getters` block:
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! mod foo_sys {
+//!     pub enum FOO {}
+//!     pub enum BAR {}
+//!
+//!     extern {
+//!         pub fn FOO_free(foo: *mut FOO);
+//!         pub fn BAR_free(bar: *mut BAR);
+//!         pub fn BAR_get_foo(bar: *mut BAR) -> *mut FOO;
+//!     }
+//! }
+//!
+//! foreign_type! {
+//!     type CType = foo_sys::FOO;
+//!     fn drop = foo_sys::FOO_free;
+//!     /// A Foo.
+//!     pub struct Foo;
+//!     /// A borrowed Foo.
+//!     pub struct FooRef;
+//! }
+//!
+//! foreign_type! {
+//!     type CType = foo_sys::BAR;
+//!     fn drop = foo_sys::BAR_free;
+//!     getters {
+//!         fn foo -> &FooRef = foo_sys::BAR_get_foo;
+//!         fn foo_mut -> &mut FooRef = foo_sys::BAR_get_foo;
+//!     }
+//!     /// A Foo.
+//!     pub struct Bar;
+//!     /// A borrowed Bar.
+//!     pub struct BarRef;
+//! }
+//!
+//! # fn main() {}
+//! ```
+//!
+//! # WebAssembly
+//!
+//! The framework makes no assumptions about pointer width or the representation of C objects
+//! beyond what the C ABI of the target provides, and is supported on `wasm32` targets such as
+//! `wasm32-unknown-emscripten`, where C objects live in linear memory and destructors are exported
+//! functions. The `fn drop` and other functions named in `foreign_type!` are called through
+//! whatever calling convention they are declared with, typically `extern "C"`.
 #![no_std]
 #![warn(missing_docs)]
-#![doc(html_root_url="https://docs.rs/foreign-types/0.3")]
+#![doc(html_root_url="https://docs.rs/foreign-types/0.4")]
 extern crate foreign_types_shared;
+#[cfg(feature = "std")]
+extern crate std;
+#[cfg(feature = "bytes")]
+extern crate bytes as bytes_crate;
 
 #[doc(inline)]
 pub use foreign_types_shared::*;
 
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "arbitrary")]
+    pub extern crate arbitrary;
+    #[cfg(feature = "stable_deref_trait")]
+    pub extern crate stable_deref_trait;
+
+    /// Implemented for the pointer types a `fn drop` function may take.
+    #[diagnostic::on_unimplemented(
+        message = "`fn drop` functions must take a `*mut {C}` or `*const {C}`, not `{Self}`"
+    )]
+    pub trait DropArg<C> {}
+
+    impl<C> DropArg<C> for *mut C {}
+
+    impl<C> DropArg<C> for *const C {}
+}
+
+pub use error::{CloneError, ConvertError, EncodeError, ParseError, UnknownValue, ValidationError};
+#[cfg(feature = "std")]
+pub use error::from_ptr_errno;
+
+mod error;
+pub mod array;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod callbacks;
+#[cfg(feature = "debug-borrows")]
+pub mod borrows;
+pub mod buffer;
+#[cfg(feature = "bytes")]
+pub mod bytes;
+pub mod children;
+pub mod foreach;
+#[cfg(feature = "std")]
+pub mod handles;
+#[cfg(feature = "hooks")]
+pub mod hooks;
+pub mod maybe_owned;
+pub mod rc;
+#[cfg(feature = "shutdown")]
+pub mod shutdown;
+pub mod slice;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod strings;
+#[cfg(feature = "std")]
+pub mod tag;
+#[cfg(feature = "std")]
+pub mod thread_local;
+
 /// A macro to easily define wrappers for foreign types.
 ///
 /// # Examples
@@ -191,7 +301,7 @@ pub use foreign_types_shared::*;
 /// #[macro_use]
 /// extern crate foreign_types;
 ///
-/// # mod openssl_sys { pub type SSL = (); pub unsafe fn SSL_free(_: *mut SSL) {} pub unsafe fn SSL_dup(x: *mut SSL) -> *mut SSL {x} }
+/// # mod openssl_sys { pub type SSL = (); pub unsafe extern "C" fn SSL_free(_: *mut SSL) {} pub unsafe fn SSL_dup(x: *mut SSL) -> *mut SSL {x} }
 /// foreign_type! {
 ///     type CType = openssl_sys::SSL;
 ///     fn drop = openssl_sys::SSL_free;
@@ -204,103 +314,5931 @@ pub use foreign_types_shared::*;
 ///
 /// # fn main() {}
 /// ```
-#[macro_export]
-macro_rules! foreign_type {
-    (
-        $(#[$impl_attr:meta])*
-        type CType = $ctype:ty;
-        fn drop = $drop:expr;
-        $(fn clone = $clone:expr;)*
-        $(#[$owned_attr:meta])*
-        pub struct $owned:ident;
-        $(#[$borrowed_attr:meta])*
-        pub struct $borrowed:ident;
-    ) => {
-        $(#[$owned_attr])*
-        pub struct $owned(*mut $ctype);
-
-        $(#[$impl_attr])*
-        impl $crate::ForeignType for $owned {
-            type CType = $ctype;
-            type Ref = $borrowed;
-
-            #[inline]
-            unsafe fn from_ptr(ptr: *mut $ctype) -> $owned {
-                $owned(ptr)
-            }
-
-            #[inline]
-            fn as_ptr(&self) -> *mut $ctype {
-                self.0
-            }
-        }
-
-        impl Drop for $owned {
-            #[inline]
-            fn drop(&mut self) {
-                unsafe { $drop(self.0) }
-            }
-        }
-
-        $(
-            impl Clone for $owned {
-                #[inline]
-                fn clone(&self) -> $owned {
-                    unsafe {
-                        let handle: *mut $ctype = $clone(self.0);
-                        $crate::ForeignType::from_ptr(handle)
-                    }
-                }
-            }
-
-            impl ::std::borrow::ToOwned for $borrowed {
-                type Owned = $owned;
-                #[inline]
-                fn to_owned(&self) -> $owned {
-                    unsafe {
-                        let handle: *mut $ctype = $clone($crate::ForeignTypeRef::as_ptr(self));
-                        $crate::ForeignType::from_ptr(handle)
-                    }
-                }
-            }
-        )*
-
-        impl ::std::ops::Deref for $owned {
-            type Target = $borrowed;
-
-            #[inline]
-            fn deref(&self) -> &$borrowed {
-                unsafe { $crate::ForeignTypeRef::from_ptr(self.0) }
-            }
-        }
-
-        impl ::std::ops::DerefMut for $owned {
-            #[inline]
-            fn deref_mut(&mut self) -> &mut $borrowed {
-                unsafe { $crate::ForeignTypeRef::from_ptr_mut(self.0) }
-            }
-        }
-
-        impl ::std::borrow::Borrow<$borrowed> for $owned {
-            #[inline]
-            fn borrow(&self) -> &$borrowed {
-                &**self
-            }
-        }
-
-        impl ::std::convert::AsRef<$borrowed> for $owned {
-            #[inline]
-            fn as_ref(&self) -> &$borrowed {
-                &**self
-            }
-        }
-
-        $(#[$borrowed_attr])*
-        pub struct $borrowed($crate::Opaque);
-
-        $(#[$impl_attr])*
-        impl $crate::ForeignTypeRef for $borrowed {
-            type CType = $ctype;
-        }
-    }
+///
+/// If duplication can fail, `fn try_clone` can be specified instead of `fn clone`. Rather than
+/// implementing `Clone`, it generates a `try_clone` method on the borrowed type which returns a
+/// `CloneError` if the function returns null.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod openssl_sys { pub type SSL = (); pub unsafe extern "C" fn SSL_free(_: *mut SSL) {} pub unsafe fn SSL_dup(_: *mut SSL) -> *mut SSL { 0 as *mut _ } }
+/// foreign_type! {
+///     type CType = openssl_sys::SSL;
+///     fn drop = openssl_sys::SSL_free;
+///     fn try_clone = openssl_sys::SSL_dup;
+///     /// Documentation for the owned type.
+///     pub struct Ssl;
+///     /// Documentation for the borrowed type.
+///     pub struct SslRef;
+/// }
+///
+/// # fn main() {
+/// # let ssl: Ssl = unsafe { foreign_types::ForeignType::from_ptr(&mut () as *mut ()) };
+/// assert!(ssl.try_clone().is_err());
+/// # std::mem::forget(ssl);
+/// # }
+/// ```
+///
+/// # Error capture
+///
+/// Libraries such as OpenSSL report the cause of a failure through a separate error queue rather
+/// than the return value. `fn error_source -> E = ...;` names a function returning the current
+/// error as an `E`, which is captured as soon as a generated operation fails so that the details
+/// are not lost. It generates a `try_from_ptr` constructor on the owned type, which returns the
+/// captured error for an invalid pointer, and `try_clone` returns it in place of a `CloneError`.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod ssl_sys {
+/// #     use std::os::raw::c_ulong;
+/// #     pub enum SSL {}
+/// #     pub unsafe extern "C" fn SSL_free(_: *mut SSL) {}
+/// #     pub unsafe fn SSL_new() -> *mut SSL { 0 as *mut _ }
+/// #     pub unsafe fn SSL_dup(_: *mut SSL) -> *mut SSL { 0 as *mut _ }
+/// #     pub unsafe fn ERR_get_error() -> c_ulong { 42 }
+/// # }
+/// /// An error reported by the SSL library.
+/// #[derive(Debug)]
+/// pub struct ErrorStack(std::os::raw::c_ulong);
+///
+/// impl ErrorStack {
+///     fn get() -> ErrorStack {
+///         ErrorStack(unsafe { ssl_sys::ERR_get_error() })
+///     }
+/// }
+///
+/// foreign_type! {
+///     type CType = ssl_sys::SSL;
+///     fn drop = ssl_sys::SSL_free;
+///     fn try_clone = ssl_sys::SSL_dup;
+///     fn error_source -> ErrorStack = ErrorStack::get;
+///     /// An SSL session.
+///     pub struct Ssl;
+///     /// A borrowed SSL session.
+///     pub struct SslRef;
+/// }
+///
+/// impl Ssl {
+///     fn new() -> Result<Ssl, ErrorStack> {
+///         unsafe { Ssl::try_from_ptr(ssl_sys::SSL_new()) }
+///     }
+/// }
+///
+/// # fn main() {
+/// assert_eq!(Ssl::new().err().unwrap().0, 42);
+/// # }
+/// ```
+///
+/// # Validation
+///
+/// Pointers received from untrusted sources, such as plugins or other processes, can be checked
+/// with a C validity function such as `X_is_valid(ptr)` before they are accepted. Specifying
+/// `fn validate = ...;` generates a `try_from_ptr` constructor on the owned type which passes the
+/// pointer to the function, and returns a `ValidationError` without taking ownership of the pointer
+/// if it returns zero or `false`. When `fn error_source` is also specified, the error type it names
+/// must implement `From<ValidationError>`.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # use std::os::raw::c_int;
+/// # mod plugin_sys {
+/// #     use std::os::raw::c_int;
+/// #     pub struct PLUGIN { pub magic: u32 }
+/// #     pub unsafe extern "C" fn PLUGIN_free(plugin: *mut PLUGIN) { drop(Box::from_raw(plugin)) }
+/// #     pub unsafe fn PLUGIN_is_valid(plugin: *mut PLUGIN) -> c_int { ((*plugin).magic == 0x504c5547) as c_int }
+/// # }
+/// foreign_type! {
+///     type CType = plugin_sys::PLUGIN;
+///     fn drop = plugin_sys::PLUGIN_free;
+///     fn validate = plugin_sys::PLUGIN_is_valid;
+///     /// A plugin.
+///     pub struct Plugin;
+///     /// A borrowed plugin.
+///     pub struct PluginRef;
+/// }
+///
+/// # fn main() {
+/// let mut bogus = plugin_sys::PLUGIN { magic: 0 };
+/// assert!(unsafe { Plugin::try_from_ptr(&mut bogus) }.is_err());
+///
+/// let ptr = Box::into_raw(Box::new(plugin_sys::PLUGIN { magic: 0x504c5547 }));
+/// assert!(unsafe { Plugin::try_from_ptr(ptr) }.is_ok());
+/// # }
+/// ```
+///
+/// # Thread safety
+///
+/// The generated types are neither `Send` nor `Sync` by default, since many C objects are not
+/// thread-safe. If the C library guarantees that a type can be moved to or shared between threads,
+/// `unsafe impl Send;` and `unsafe impl Sync;` implement the corresponding traits for both the
+/// owned and borrowed types.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod openssl_sys { pub type SSL = (); pub unsafe extern "C" fn SSL_free(_: *mut SSL) {} }
+/// foreign_type! {
+///     type CType = openssl_sys::SSL;
+///     fn drop = openssl_sys::SSL_free;
+///     unsafe impl Send;
+///     unsafe impl Sync;
+///     /// Documentation for the owned type.
+///     pub struct Ssl;
+///     /// Documentation for the borrowed type.
+///     pub struct SslRef;
+/// }
+///
+/// fn assert_thread_safe<T: Send + Sync>() {}
+///
+/// # fn main() {
+/// assert_thread_safe::<Ssl>();
+/// assert_thread_safe::<SslRef>();
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod openssl_sys { pub type SSL = (); pub unsafe extern "C" fn SSL_free(_: *mut SSL) {} }
+/// foreign_type! {
+///     type CType = openssl_sys::SSL;
+///     fn drop = openssl_sys::SSL_free;
+///     /// Documentation for the owned type.
+///     pub struct Ssl;
+///     /// Documentation for the borrowed type.
+///     pub struct SslRef;
+/// }
+///
+/// fn assert_send<T: Send>() {}
+///
+/// # fn main() {
+/// assert_send::<SslRef>();
+/// # }
+/// ```
+///
+/// # Must use
+///
+/// The owned type is annotated with `#[must_use]`, since dropping a freshly created C object
+/// immediately is almost always a bug. For types where discarding a value is legitimate, this can
+/// be turned off with `const MUST_USE: bool = false;`:
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod openssl_sys { pub type SSL = (); pub unsafe extern "C" fn SSL_free(_: *mut SSL) {} }
+/// foreign_type! {
+///     type CType = openssl_sys::SSL;
+///     fn drop = openssl_sys::SSL_free;
+///     const MUST_USE: bool = false;
+///     /// Documentation for the owned type.
+///     pub struct Ssl;
+///     /// Documentation for the borrowed type.
+///     pub struct SslRef;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// # Const pointers
+///
+/// C libraries sometimes declare their free and duplication functions with a `const` pointer
+/// parameter, such as `void FOO_free(const FOO *foo)`. These can be used directly, since the
+/// generated code passes the wrapped `*mut` pointer through the usual `*mut` to `*const` coercion.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod foo_sys {
+/// #     pub enum FOO {}
+/// #     pub unsafe extern "C" fn FOO_free(_: *const FOO) {}
+/// #     pub unsafe fn FOO_dup(_: *const FOO) -> *mut FOO { 0 as *mut _ }
+/// # }
+/// foreign_type! {
+///     type CType = foo_sys::FOO;
+///     fn drop = foo_sys::FOO_free;
+///     fn clone = foo_sys::FOO_dup;
+///     /// A Foo.
+///     pub struct Foo;
+///     /// A borrowed Foo.
+///     pub struct FooRef;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// # Destructor signatures
+///
+/// The `fn drop` functions are only called with the wrapped pointer, so a function with the wrong
+/// signature could otherwise compile. A generic function such as `std::mem::drop` would be
+/// accepted without freeing anything, for example. The macro therefore checks at compile time that
+/// each is an `extern "C"` function taking a single `*mut` or `*const` pointer to the C type, as
+/// generated by `bindgen`, and reports a mismatch at the macro call site. The return value, if any,
+/// is ignored. Functions with another ABI are declared with it, as in `extern "Rust" fn drop = ...;`
+/// for functions written in Rust.
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod foo_sys { pub enum FOO {} }
+/// foreign_type! {
+///     type CType = foo_sys::FOO;
+///     fn drop = std::mem::drop;
+///     /// A Foo.
+///     pub struct Foo;
+///     /// A borrowed Foo.
+///     pub struct FooRef;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod foo_sys { pub enum FOO {} }
+/// unsafe fn free_foo(foo: *mut foo_sys::FOO) {
+///     // ...
+/// #   let _ = foo;
+/// }
+///
+/// foreign_type! {
+///     type CType = foo_sys::FOO;
+///     extern "Rust" fn drop = free_foo;
+///     /// A Foo.
+///     pub struct Foo;
+///     /// A borrowed Foo.
+///     pub struct FooRef;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// # Sentinel values
+///
+/// Some APIs use a non-null sentinel value, such as Windows' `INVALID_HANDLE_VALUE`, for a handle
+/// which does not refer to an object. `const INVALID: *mut Self::CType = ...;` overrides the
+/// `INVALID` constant of both generated types, which is null by default. It is used by the
+/// `from_ptr_opt` constructors, takes the place of null in pointer types and list cursors, and is
+/// checked by the generated code wherever a C function can fail to return an object.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use foreign_types::ForeignType;
+///
+/// # mod win_sys {
+/// #     pub type HANDLE = *mut ::std::os::raw::c_void;
+/// #     pub const INVALID_HANDLE_VALUE: HANDLE = -1isize as HANDLE;
+/// #     pub unsafe extern "C" fn CloseHandle(_: HANDLE) {}
+/// # }
+/// foreign_type! {
+///     type CType = ::std::os::raw::c_void;
+///     fn drop = win_sys::CloseHandle;
+///     const INVALID: *mut Self::CType = win_sys::INVALID_HANDLE_VALUE;
+///     ptr {
+///         /// A raw kernel object handle.
+///         pub struct HandlePtr;
+///     }
+///     /// A kernel object handle.
+///     pub struct Handle;
+///     /// A borrowed kernel object handle.
+///     pub struct HandleRef;
+/// }
+///
+/// # fn main() {
+/// assert!(unsafe { Handle::from_ptr_opt(win_sys::INVALID_HANDLE_VALUE) }.is_none());
+///
+/// let ptr = HandlePtr::from_raw(win_sys::INVALID_HANDLE_VALUE);
+/// assert!(ptr.is_null());
+/// assert_eq!(HandlePtr::null(), ptr);
+/// assert!(unsafe { ptr.into_owned() }.is_none());
+/// # }
+/// ```
+///
+/// # Reuse
+///
+/// `fn reset = ...;` names a function which reinitializes an object, and generates a `reset`
+/// method on the borrowed type so that hot paths can reuse an allocation rather than creating a new
+/// object. If the function can fail, `fn reset -> Result<(), E> = ...;` instead names a function
+/// returning a `Result<(), E>`, which is returned from `reset`.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod hash_sys {
+/// #     pub struct HASH { pub len: usize }
+/// #     pub unsafe extern "C" fn HASH_free(hash: *mut HASH) { drop(Box::from_raw(hash)) }
+/// #     pub unsafe fn HASH_reset(hash: *mut HASH) { (*hash).len = 0 }
+/// #     pub unsafe fn HASH_len(hash: *mut HASH) -> usize { (*hash).len }
+/// # }
+/// foreign_type! {
+///     type CType = hash_sys::HASH;
+///     fn drop = hash_sys::HASH_free;
+///     fn reset = hash_sys::HASH_reset;
+///     getters {
+///         fn len -> usize = hash_sys::HASH_len;
+///     }
+///     /// A hash context.
+///     pub struct Hasher;
+///     /// A borrowed hash context.
+///     pub struct HasherRef;
+/// }
+///
+/// # fn main() {
+/// # let mut hasher: Hasher = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(hash_sys::HASH { len: 5 }))) };
+/// hasher.reset();
+/// assert_eq!(hasher.len(), 0);
+/// # }
+/// ```
+///
+/// # Multi-step destruction
+///
+/// Some C APIs require a cleanup function to be called before an object is freed. `fn drop` can
+/// list several functions, which are called in order when the owned value is dropped.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod ctx_sys {
+/// #     pub struct CTX { pub active: bool }
+/// #     pub unsafe extern "C" fn CTX_cleanup(ctx: *mut CTX) { (*ctx).active = false }
+/// #     pub unsafe extern "C" fn CTX_free(ctx: *mut CTX) { assert!(!(*ctx).active); drop(Box::from_raw(ctx)) }
+/// # }
+/// foreign_type! {
+///     type CType = ctx_sys::CTX;
+///     fn drop = ctx_sys::CTX_cleanup, ctx_sys::CTX_free;
+///     /// A context.
+///     pub struct Ctx;
+///     /// A borrowed context.
+///     pub struct CtxRef;
+/// }
+///
+/// # fn main() {
+/// # let ctx: Ctx = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(ctx_sys::CTX { active: true }))) };
+/// drop(ctx);
+/// # }
+/// ```
+///
+/// # Fallible destruction
+///
+/// `Drop` has no way to report errors from destructors which can fail, such as those that flush
+/// buffered data. Specifying `fn destroy -> Result<(), E> = ...;` generates a `destroy` method on
+/// the owned type which consumes it and passes its pointer to the given function, which must
+/// return a `Result<(), E>`. The function is called exactly once and `Drop` does not run
+/// afterwards, whether or not it succeeds.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # use std::os::raw::c_int;
+/// # mod db_sys {
+/// #     use std::os::raw::c_int;
+/// #     pub enum DB {}
+/// #     pub unsafe extern "C" fn DB_close(_: *mut DB) -> c_int { 5 }
+/// # }
+/// unsafe fn close(db: *mut db_sys::DB) -> Result<(), c_int> {
+///     match db_sys::DB_close(db) {
+///         0 => Ok(()),
+///         e => Err(e),
+///     }
+/// }
+///
+/// foreign_type! {
+///     type CType = db_sys::DB;
+///     fn drop = db_sys::DB_close;
+///     fn destroy -> Result<(), c_int> = close;
+///     /// A database connection.
+///     pub struct Db;
+///     /// A borrowed database connection.
+///     pub struct DbRef;
+/// }
+///
+/// # fn main() {
+/// # let db: Db = unsafe { foreign_types::ForeignType::from_ptr(0 as *mut _) };
+/// assert_eq!(db.destroy(), Err(5));
+/// # }
+/// ```
+///
+/// Otherwise `destroy` releases the value as `Drop` would: closures registered through a
+/// `callbacks` block are freed once the function returns.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use foreign_types::callbacks::live_callbacks;
+/// use std::os::raw::c_int;
+///
+/// # mod db_sys {
+/// #     use std::os::raw::{c_int, c_void};
+/// #     pub struct DB;
+/// #     pub unsafe extern "C" fn DB_close(db: *mut DB) -> c_int { drop(Box::from_raw(db)); 0 }
+/// #     pub unsafe fn DB_set_trace(_: *mut DB, _: unsafe extern "C" fn(*mut c_void), _: *mut c_void) {}
+/// # }
+/// unsafe fn close(db: *mut db_sys::DB) -> Result<(), c_int> {
+///     match db_sys::DB_close(db) {
+///         0 => Ok(()),
+///         e => Err(e),
+///     }
+/// }
+///
+/// foreign_type! {
+///     type CType = db_sys::DB;
+///     fn drop = db_sys::DB_close;
+///     fn destroy -> Result<(), c_int> = close;
+///     callbacks {
+///         /// Sets the function called for each statement.
+///         pub fn set_trace(FnMut()) = db_sys::DB_set_trace;
+///     }
+///     /// A database connection.
+///     pub struct Db;
+///     /// A borrowed database connection.
+///     pub struct DbRef;
+/// }
+///
+/// # fn main() {
+/// # let mut db: Db = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(db_sys::DB))) };
+/// db.set_trace(|| {});
+/// assert_eq!(live_callbacks(), 1);
+/// assert_eq!(db.destroy(), Ok(()));
+/// assert_eq!(live_callbacks(), 0);
+/// # }
+/// ```
+///
+/// # File descriptors
+///
+/// For C objects wrapping a file descriptor, `fn as_raw_fd = ...;` names a function returning the
+/// object's descriptor, or a negative value if it has none, such as `-1` for an unconnected
+/// socket. It generates `AsRawFd` implementations for both types, and an `as_fd` method on the
+/// borrowed type which returns `None` for a negative descriptor. `AsFd` is not implemented, since
+/// it cannot report a missing descriptor. The descriptor must stay open while the object is
+/// borrowed.
+///
+/// `fn into_raw_fd = ...;` names a function which detaches the descriptor from the object so that
+/// it is no longer closed when the object is freed. It generates `IntoRawFd` and
+/// `From<Foo> for OwnedFd` implementations which detach the descriptor and then free the object.
+/// The conversion into `OwnedFd` panics if the detached descriptor is negative.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # #[cfg(unix)]
+/// # mod sock_sys {
+/// #     use std::os::raw::c_int;
+/// #     pub enum SOCK {}
+/// #     pub unsafe extern "C" fn SOCK_free(_: *mut SOCK) {}
+/// #     pub unsafe fn SOCK_get_fd(_: *mut SOCK) -> c_int { -1 }
+/// #     pub unsafe fn SOCK_detach_fd(_: *mut SOCK) -> c_int { -1 }
+/// # }
+/// # #[cfg(unix)]
+/// # mod imp {
+/// # use sock_sys;
+/// foreign_type! {
+///     type CType = sock_sys::SOCK;
+///     fn drop = sock_sys::SOCK_free;
+///     fn as_raw_fd = sock_sys::SOCK_get_fd;
+///     fn into_raw_fd = sock_sys::SOCK_detach_fd;
+///     /// A socket.
+///     pub struct Socket;
+///     /// A borrowed socket.
+///     pub struct SocketRef;
+/// }
+/// # }
+///
+/// # fn main() {
+/// # #[cfg(unix)] {
+/// # use foreign_types::ForeignType;
+/// # let socket = unsafe { imp::Socket::from_ptr(std::ptr::NonNull::dangling().as_ptr()) };
+/// // The socket is not connected, so it has no descriptor.
+/// assert!(socket.as_fd().is_none());
+/// # }
+/// # }
+/// ```
+///
+/// # Serialization
+///
+/// `fn from_bytes = ...;` names a function which parses a value from a byte buffer, taking a
+/// `*const u8` pointer and a `usize` length, and returning null on failure. It generates a
+/// `from_bytes` constructor on the owned type, which returns a `ParseError` if parsing fails.
+///
+/// `fn to_bytes = ...;` names a function which serializes a value into a caller-provided buffer,
+/// taking the value's pointer, a `*mut u8` pointer to the buffer and its `usize` length. It must
+/// return the length of the serialized value as an integer, or a negative number on failure, and
+/// must not write to the buffer if it is null or too small. It generates a `to_bytes` method on the
+/// borrowed type, which queries the length, serializes the value into a `Vec`, and returns an
+/// `EncodeError` if either call fails. Functions in the style of OpenSSL's `i2d_X`, which advance
+/// an output pointer instead, can be adapted with a small wrapper.
+///
+/// When `fn error_source` is also specified, both methods return the error it captures instead.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod key_sys {
+/// #     use std::os::raw::c_int;
+/// #     pub struct KEY(pub Vec<u8>);
+/// #     pub unsafe extern "C" fn KEY_free(key: *mut KEY) { drop(Box::from_raw(key)) }
+/// #     pub unsafe fn KEY_parse(buf: *const u8, len: usize) -> *mut KEY {
+/// #         let bytes = std::slice::from_raw_parts(buf, len);
+/// #         if bytes.starts_with(b"KEY") { Box::into_raw(Box::new(KEY(bytes.to_vec()))) } else { 0 as *mut _ }
+/// #     }
+/// #     pub unsafe fn KEY_encode(key: *mut KEY, buf: *mut u8, len: usize) -> c_int {
+/// #         let bytes = &(*key).0;
+/// #         if !buf.is_null() && len >= bytes.len() { std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len()) }
+/// #         bytes.len() as c_int
+/// #     }
+/// # }
+/// foreign_type! {
+///     type CType = key_sys::KEY;
+///     fn drop = key_sys::KEY_free;
+///     fn from_bytes = key_sys::KEY_parse;
+///     fn to_bytes = key_sys::KEY_encode;
+///     /// A key.
+///     pub struct Key;
+///     /// A borrowed key.
+///     pub struct KeyRef;
+/// }
+///
+/// # fn main() {
+/// let key = Key::from_bytes(b"KEY-1234").unwrap();
+/// assert_eq!(key.to_bytes().unwrap(), b"KEY-1234");
+/// assert!(Key::from_bytes(b"nonsense").is_err());
+/// # }
+/// ```
+///
+/// # Fuzzing
+///
+/// When the `arbitrary` feature of this crate is enabled, `fn from_bytes = ...;` is also used to
+/// implement `arbitrary::Arbitrary` for the owned type, so that fuzz targets can exercise the C
+/// parser through the safe wrapper. Input which fails to parse is rejected with
+/// `arbitrary::Error::IncorrectFormat`.
+///
+/// ```ignore
+/// foreign_type! {
+///     type CType = x509_sys::X509;
+///     fn drop = x509_sys::X509_free;
+///     fn from_bytes = x509_sys::X509_parse;
+///     /// A certificate.
+///     pub struct X509;
+///     /// A borrowed certificate.
+///     pub struct X509Ref;
+/// }
+///
+/// libfuzzer_sys::fuzz_target!(|cert: X509| {
+///     let _ = cert.subject_name();
+/// });
+/// ```
+///
+/// The generated implementation passes the fuzzer's bytes to the parser:
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+/// # #[cfg(feature = "arbitrary")]
+/// extern crate arbitrary;
+///
+/// # #[cfg(feature = "arbitrary")]
+/// use arbitrary::{Arbitrary, Unstructured};
+///
+/// # mod key_sys {
+/// #     pub struct KEY(pub Vec<u8>);
+/// #     pub unsafe extern "C" fn KEY_free(key: *mut KEY) { drop(Box::from_raw(key)) }
+/// #     pub unsafe fn KEY_parse(buf: *const u8, len: usize) -> *mut KEY {
+/// #         let bytes = std::slice::from_raw_parts(buf, len);
+/// #         if bytes.starts_with(b"KEY") { Box::into_raw(Box::new(KEY(bytes.to_vec()))) } else { 0 as *mut _ }
+/// #     }
+/// #     pub unsafe fn KEY_len(key: *mut KEY) -> usize { (*key).0.len() }
+/// # }
+/// foreign_type! {
+///     type CType = key_sys::KEY;
+///     fn drop = key_sys::KEY_free;
+///     fn from_bytes = key_sys::KEY_parse;
+///     getters {
+///         fn len -> usize = key_sys::KEY_len;
+///     }
+///     /// A key.
+///     pub struct Key;
+///     /// A borrowed key.
+///     pub struct KeyRef;
+/// }
+///
+/// # #[cfg(feature = "arbitrary")]
+/// # fn main() {
+/// let key = Key::arbitrary_take_rest(Unstructured::new(b"KEY-1234")).unwrap();
+/// assert_eq!(key.len(), 8);
+///
+/// let err = Key::arbitrary_take_rest(Unstructured::new(b"nonsense")).err().unwrap();
+/// assert_eq!(err, arbitrary::Error::IncorrectFormat);
+/// # }
+/// # #[cfg(not(feature = "arbitrary"))]
+/// # fn main() {}
+/// ```
+///
+/// # Stable dereferencing
+///
+/// The owned type dereferences to the C object behind its pointer, which does not move when the
+/// owned value does. When the `stable_deref_trait` feature of this crate is enabled, the owned
+/// type implements `stable_deref_trait::StableDeref`, allowing it to be used with `owning_ref` and
+/// similar self-referential building blocks.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+/// # #[cfg(feature = "stable_deref_trait")]
+/// extern crate stable_deref_trait;
+///
+/// # mod foo_sys { pub enum FOO {} pub unsafe extern "C" fn FOO_free(_: *mut FOO) {} }
+/// foreign_type! {
+///     type CType = foo_sys::FOO;
+///     fn drop = foo_sys::FOO_free;
+///     /// A Foo.
+///     pub struct Foo;
+///     /// A borrowed Foo.
+///     pub struct FooRef;
+/// }
+///
+/// # #[cfg(feature = "stable_deref_trait")]
+/// const _: fn() = || {
+///     fn assert<T: stable_deref_trait::StableDeref>() {}
+///     assert::<Foo>();
+/// };
+///
+/// # fn main() {}
+/// ```
+///
+/// # Batch destruction
+///
+/// `fn free_all = ...;` names a function which frees an array of values with a single call,
+/// taking a pointer to the array and its length. It is used to implement `batch::BatchFree`, so
+/// that values can be collected into a `batch::BatchOwned` which frees them all together. This
+/// requires the `std` feature.
+///
+/// # Reference counting
+///
+/// `fn up_ref = ...;` names a function which increments the C object's reference count, which the
+/// `drop` function is expected to decrement. It is used to implement `rc::RefCounted`, so that
+/// values can be shared within a thread through an `rc::ForeignRc`.
+///
+/// `fn refcount_get = ...;` additionally names a function which returns the current reference
+/// count, which is used to implement `rc::RefCountGet`. This enables `ForeignRc::strong_count`, as
+/// well as `try_unwrap`, `get_mut` and, for types with a `fn clone` entry, `make_mut`, which
+/// recover exclusive access to objects which are no longer shared.
+///
+/// # Memory accounting
+///
+/// `fn sizeof = ...;` names a function which returns the number of bytes used by a value. When the
+/// `stats` feature of this crate is enabled, owned values report their size to the `stats` module
+/// when they are created, and release the same size when they are dropped, so the memory held
+/// through the wrapper can be inspected with `foreign_types::stats::bytes_live::<Foo>()`. The
+/// entry is ignored when the feature is disabled.
+///
+/// # Lifecycle hooks
+///
+/// When the `hooks` feature of this crate is enabled, every owned value reports its creation and
+/// release to the functions registered with `foreign_types::hooks`, along with its type name and
+/// pointer. No entry is needed in the macro invocation.
+///
+/// # Teardown ordering
+///
+/// When the `shutdown` feature of this crate is enabled, every owned value is registered with the
+/// `shutdown` module while it is alive, so that an application can check that all values have been
+/// dropped before deinitializing the C library. No entry is needed in the macro invocation.
+///
+/// # Immutable types
+///
+/// Some C objects are logically constant once created, such as interned values or shared
+/// configuration. Specifying `const MUTABLE: bool = false;` omits the owned type's `DerefMut`
+/// implementation and the borrowed type's `ForeignTypeMut` implementation, so safe code cannot
+/// obtain a mutable reference to the borrowed type, either directly or through wrappers such as
+/// `MaybeOwned` and `ForeignRc`. It also adds an `as_const_ptr` method to the borrowed type
+/// returning a `*const` pointer. Entries which modify the value, such as `setters`, `fn reset` and
+/// getters returning `&mut`, are rejected. The borrowed type still implements `ForeignTypeRef`,
+/// but its unsafe `from_ptr_mut` constructor must not be used.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod conf_sys { pub enum CONF {} pub unsafe extern "C" fn CONF_free(_: *mut CONF) {} }
+/// foreign_type! {
+///     type CType = conf_sys::CONF;
+///     fn drop = conf_sys::CONF_free;
+///     const MUTABLE: bool = false;
+///     /// A shared configuration.
+///     pub struct Conf;
+///     /// A borrowed shared configuration.
+///     pub struct ConfRef;
+/// }
+///
+/// fn conf_ptr(conf: &Conf) -> *const conf_sys::CONF {
+///     conf.as_const_ptr()
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod conf_sys { pub enum CONF {} pub unsafe extern "C" fn CONF_free(_: *mut CONF) {} }
+/// foreign_type! {
+///     type CType = conf_sys::CONF;
+///     fn drop = conf_sys::CONF_free;
+///     const MUTABLE: bool = false;
+///     /// A shared configuration.
+///     pub struct Conf;
+///     /// A borrowed shared configuration.
+///     pub struct ConfRef;
+/// }
+///
+/// fn conf_mut(conf: &mut Conf) -> &mut ConfRef {
+///     &mut *conf
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use foreign_types::maybe_owned::MaybeOwned;
+///
+/// # mod conf_sys { pub enum CONF {} pub unsafe extern "C" fn CONF_free(_: *mut CONF) {} }
+/// foreign_type! {
+///     type CType = conf_sys::CONF;
+///     fn drop = conf_sys::CONF_free;
+///     const MUTABLE: bool = false;
+///     /// A shared configuration.
+///     pub struct Conf;
+///     /// A borrowed shared configuration.
+///     pub struct ConfRef;
+/// }
+///
+/// fn conf_mut(conf: &mut MaybeOwned<Conf>) -> &mut ConfRef {
+///     &mut *conf
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod conf_sys {
+/// #     pub enum CONF {}
+/// #     pub unsafe extern "C" fn CONF_free(_: *mut CONF) {}
+/// #     pub unsafe fn CONF_set_flags(_: *mut CONF, _: u32) {}
+/// # }
+/// foreign_type! {
+///     type CType = conf_sys::CONF;
+///     fn drop = conf_sys::CONF_free;
+///     const MUTABLE: bool = false;
+///     setters {
+///         /// Sets the configuration's flags.
+///         pub fn set_flags(u32) = conf_sys::CONF_set_flags;
+///     }
+///     /// A shared configuration.
+///     pub struct Conf;
+///     /// A borrowed shared configuration.
+///     pub struct ConfRef;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// # Unowned types
+///
+/// Some C libraries hand out objects that they own themselves, such as global singletons or
+/// interned values, which must never be freed. Specifying `fn drop = none;` generates the usual
+/// pair of types without a `Drop` implementation. In this mode `from_ptr` does not take ownership
+/// of anything: the owned type is just a handle to an object whose lifetime is managed by the
+/// library.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod foo_sys { pub enum FOO {} }
+/// foreign_type! {
+///     type CType = foo_sys::FOO;
+///     fn drop = none;
+///     /// A handle to the library's global `FOO`.
+///     pub struct Foo;
+///     /// A borrowed Foo.
+///     pub struct FooRef;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// Attributes on the `fn drop` line are applied to the generated `Drop` implementation, so it can
+/// be conditionally compiled with `#[cfg(...)]`. When the condition is not met, values are not
+/// freed at all.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod foo_sys { pub enum FOO {} pub unsafe extern "C" fn FOO_free(_: *mut FOO) {} }
+/// foreign_type! {
+///     type CType = foo_sys::FOO;
+///     #[cfg(not(feature = "leak-foo"))]
+///     fn drop = foo_sys::FOO_free;
+///     /// A Foo.
+///     pub struct Foo;
+///     /// A borrowed Foo.
+///     pub struct FooRef;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// # Compatible wrappers
+///
+/// `unsafe impl ForeignTypeCompat;` implements `ForeignTypeCompat` for both generated types,
+/// allowing them to be converted to and from other compatible wrappers over the same C type. See
+/// the trait's documentation for the requirements this places on the wrapper.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use foreign_types::{ForeignType, ForeignTypeRef};
+///
+/// # mod x509_sys { pub enum X509 {} pub unsafe extern "C" fn X509_free(_: *mut X509) {} }
+/// mod a {
+/// #   use x509_sys;
+///     foreign_type! {
+///         type CType = x509_sys::X509;
+///         fn drop = x509_sys::X509_free;
+///         unsafe impl ForeignTypeCompat;
+///         /// One crate's certificate.
+///         pub struct X509;
+///         /// One crate's borrowed certificate.
+///         pub struct X509Ref;
+///     }
+/// }
+///
+/// mod b {
+/// #   use x509_sys;
+///     foreign_type! {
+///         type CType = x509_sys::X509;
+///         fn drop = x509_sys::X509_free;
+///         unsafe impl ForeignTypeCompat;
+///         /// Another crate's certificate.
+///         pub struct Certificate;
+///         /// Another crate's borrowed certificate.
+///         pub struct CertificateRef;
+///     }
+/// }
+///
+/// fn convert(cert: a::X509) -> b::Certificate {
+///     let _: &b::CertificateRef = cert.as_other_ref();
+///     cert.into_other()
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// # Untyped C APIs
+///
+/// Some libraries represent all of their objects as `void *`, and distinguish them only by the
+/// functions which accept them. Several wrappers can share `type CType = c_void;`, and the
+/// generated types remain distinct: methods which accept or return wrappers, such as getters and
+/// setters, are checked against the wrapper types rather than the raw pointer type. Type safety is
+/// only lost at the raw pointer level, such as in the results of `as_ptr`.
+///
+/// `unsafe impl ForeignTypeCompat;` must not be used with a `c_void` C type, since it would allow
+/// conversions between wrappers of unrelated objects.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use std::os::raw::c_void;
+///
+/// # mod ui_sys {
+/// #     use std::os::raw::c_void;
+/// #     pub unsafe extern "C" fn ui_free(_: *mut c_void) {}
+/// #     pub unsafe fn window_set_app(_: *mut c_void, _: *mut c_void) {}
+/// # }
+/// foreign_type! {
+///     type CType = c_void;
+///     fn drop = ui_sys::ui_free;
+///     /// An application.
+///     pub struct App;
+///     /// A borrowed application.
+///     pub struct AppRef;
+/// }
+///
+/// foreign_type! {
+///     type CType = c_void;
+///     fn drop = ui_sys::ui_free;
+///     setters {
+///         /// Sets the application which owns the window.
+///         pub fn set_app(&AppRef) = ui_sys::window_set_app;
+///     }
+///     /// A window.
+///     pub struct Window;
+///     /// A borrowed window.
+///     pub struct WindowRef;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// Passing a window where an application is expected fails to compile:
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use std::os::raw::c_void;
+///
+/// # mod ui_sys {
+/// #     use std::os::raw::c_void;
+/// #     pub unsafe extern "C" fn ui_free(_: *mut c_void) {}
+/// #     pub unsafe fn window_set_app(_: *mut c_void, _: *mut c_void) {}
+/// # }
+/// # foreign_type! {
+/// #     type CType = c_void;
+/// #     fn drop = ui_sys::ui_free;
+/// #     pub struct App;
+/// #     pub struct AppRef;
+/// # }
+/// # foreign_type! {
+/// #     type CType = c_void;
+/// #     fn drop = ui_sys::ui_free;
+/// #     setters {
+/// #         pub fn set_app(&AppRef) = ui_sys::window_set_app;
+/// #     }
+/// #     pub struct Window;
+/// #     pub struct WindowRef;
+/// # }
+/// fn reparent(window: &mut WindowRef, other: &WindowRef) {
+///     window.set_app(other);
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// # Getters
+///
+/// A `getters` block generates methods on the borrowed type which pass its pointer to a C getter
+/// function. The declared return type determines how the function's result is translated:
+///
+/// * `-> &FooRef` and `-> &mut FooRef` borrow the returned pointer from `self`.
+/// * `-> Option<&FooRef>` and `-> Option<&mut FooRef>` do the same, returning `None` for a null
+///   pointer.
+/// * `-> owned Foo` takes ownership of the returned pointer.
+/// * `-> Option<owned Foo>` does the same, returning `None` for a null pointer.
+/// * Any other type is returned as-is, which is appropriate for scalars.
+///
+/// The forms without `Option` require the function to return a valid, non-null pointer. If it can
+/// return null, such as when a value is absent or an allocation fails, use the `Option` form, as
+/// the others would otherwise construct a reference or owned value from a null pointer.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # use std::os::raw::c_int;
+/// # mod foo_sys {
+/// #     use std::os::raw::c_int;
+/// #     pub enum FOO {}
+/// #     pub enum BAR {}
+/// #     pub unsafe extern "C" fn FOO_free(_: *mut FOO) {}
+/// #     pub unsafe extern "C" fn BAR_free(_: *mut BAR) {}
+/// #     pub unsafe fn BAR_get_foo(_: *mut BAR) -> *mut FOO { 0 as *mut _ }
+/// #     pub unsafe fn BAR_get_peer(_: *mut BAR) -> *mut FOO { 0 as *mut _ }
+/// #     pub unsafe fn BAR_dup_foo(_: *mut BAR) -> *mut FOO { 0 as *mut _ }
+/// #     pub unsafe fn BAR_dup_peer(_: *mut BAR) -> *mut FOO { 0 as *mut _ }
+/// #     pub unsafe fn BAR_get_flags(_: *const BAR) -> c_int { 0 }
+/// # }
+/// foreign_type! {
+///     type CType = foo_sys::FOO;
+///     fn drop = foo_sys::FOO_free;
+///     /// A Foo.
+///     pub struct Foo;
+///     /// A borrowed Foo.
+///     pub struct FooRef;
+/// }
+///
+/// foreign_type! {
+///     type CType = foo_sys::BAR;
+///     fn drop = foo_sys::BAR_free;
+///     getters {
+///         /// Returns the bar's foo.
+///         pub fn foo -> &FooRef = foo_sys::BAR_get_foo;
+///         /// Returns the bar's foo mutably.
+///         pub fn foo_mut -> &mut FooRef = foo_sys::BAR_get_foo;
+///         /// Returns the bar's peer, if it has one.
+///         pub fn peer -> Option<&FooRef> = foo_sys::BAR_get_peer;
+///         /// Returns a copy of the bar's foo.
+///         pub fn to_foo -> owned Foo = foo_sys::BAR_dup_foo;
+///         /// Returns a copy of the bar's peer, if it has one.
+///         pub fn to_peer -> Option<owned Foo> = foo_sys::BAR_dup_peer;
+///         /// Returns the bar's flags.
+///         pub fn flags -> c_int = foo_sys::BAR_get_flags;
+///     }
+///     /// A Bar.
+///     pub struct Bar;
+///     /// A borrowed Bar.
+///     pub struct BarRef;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// # Setters
+///
+/// A `setters` block similarly generates methods on the borrowed type which pass its pointer and
+/// an argument to a C setter function. The declared parameter determines how the argument is
+/// passed:
+///
+/// * `(&FooRef)` lends the argument to the setter, which is appropriate if the C function only
+///   uses it for the duration of the call or makes its own copy.
+/// * `(owned Foo)` transfers ownership of the argument to the C function with `into_ptr`.
+/// * `(cstr)` accepts any [`TryIntoCStr`](strings/trait.TryIntoCStr.html) value, such as a `&str`,
+///   and lends it to the setter as a `*const c_char`. The generated method returns a `Result`,
+///   which is an error if the value contains a nul byte. This form requires the `std` feature.
+/// * Any other type is passed as-is, which is appropriate for scalars.
+///
+/// The generated method returns `()` unless a return type is given, in which case the setter's
+/// return value, such as a status code, is returned to the caller.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # use std::os::raw::c_int;
+/// # mod foo_sys {
+/// #     use std::os::raw::c_int;
+/// #     pub enum FOO {}
+/// #     pub enum BAR {}
+/// #     pub unsafe extern "C" fn FOO_free(_: *mut FOO) {}
+/// #     pub unsafe extern "C" fn BAR_free(_: *mut BAR) {}
+/// #     pub unsafe fn BAR_set0_foo(_: *mut BAR, _: *mut FOO) {}
+/// #     pub unsafe fn BAR_set1_foo(_: *mut BAR, _: *mut FOO) -> c_int { 1 }
+/// #     pub unsafe fn BAR_set_flags(_: *mut BAR, _: c_int) {}
+/// #     pub unsafe fn BAR_set_name(_: *mut BAR, _: *const std::os::raw::c_char) {}
+/// # }
+/// foreign_type! {
+///     type CType = foo_sys::FOO;
+///     fn drop = foo_sys::FOO_free;
+///     /// A Foo.
+///     pub struct Foo;
+///     /// A borrowed Foo.
+///     pub struct FooRef;
+/// }
+///
+/// foreign_type! {
+///     type CType = foo_sys::BAR;
+///     fn drop = foo_sys::BAR_free;
+///     setters {
+///         /// Sets the bar's foo, taking ownership of it.
+///         pub fn set_foo(owned Foo) = foo_sys::BAR_set0_foo;
+///         /// Sets the bar's foo to a copy of `value`.
+///         pub fn set_foo_copy(&FooRef) -> c_int = foo_sys::BAR_set1_foo;
+///         /// Sets the bar's flags.
+///         pub fn set_flags(c_int) = foo_sys::BAR_set_flags;
+///         /// Sets the bar's name.
+///         pub fn set_name(cstr) = foo_sys::BAR_set_name;
+///     }
+///     /// A Bar.
+///     pub struct Bar;
+///     /// A borrowed Bar.
+///     pub struct BarRef;
+/// }
+///
+/// # fn main() {
+/// fn rename(bar: &mut BarRef) -> Result<(), std::ffi::NulError> {
+///     bar.set_name("bar")
+/// }
+/// # let _ = rename;
+/// # }
+/// ```
+///
+/// # Fields
+///
+/// Some C types have a public, stable layout but must still be freed by the library. When the C
+/// type is a `#[repr(C)]` struct, a `fields` block generates safe methods on the borrowed type
+/// which access its fields directly:
+///
+/// * `-> &T = field` and `-> &mut T = field` borrow the field from `self`.
+/// * `-> T = field` copies the field out, and requires `T: Copy`.
+/// * `(T) = field` generates a setter which overwrites the field.
+///
+/// The field names are checked against the struct definition when the methods are compiled.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # use std::os::raw::{c_char, c_int};
+/// mod rect_sys {
+///     use std::os::raw::{c_char, c_int};
+///
+///     #[repr(C)]
+///     pub struct RECT {
+///         pub width: c_int,
+///         pub height: c_int,
+///         pub label: [c_char; 8],
+///     }
+/// #   pub unsafe extern "C" fn RECT_free(rect: *mut RECT) { drop(Box::from_raw(rect)) }
+/// }
+///
+/// foreign_type! {
+///     type CType = rect_sys::RECT;
+///     fn drop = rect_sys::RECT_free;
+///     fields {
+///         /// Returns the rectangle's width.
+///         pub fn width -> c_int = width;
+///         /// Sets the rectangle's width.
+///         pub fn set_width(c_int) = width;
+///         /// Returns the rectangle's label.
+///         pub fn label -> &[c_char; 8] = label;
+///     }
+///     /// A rectangle.
+///     pub struct Rect;
+///     /// A borrowed rectangle.
+///     pub struct RectRef;
+/// }
+///
+/// # fn main() {
+/// # let mut rect: Rect = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(rect_sys::RECT { width: 1, height: 2, label: [0; 8] }))) };
+/// rect.set_width(3);
+/// assert_eq!(rect.width(), 3);
+/// assert_eq!(rect.label()[0], 0);
+/// # }
+/// ```
+///
+/// # Callbacks
+///
+/// A `callbacks` block generates methods on the borrowed type which register a Rust closure with
+/// a C function of the form `X_set_callback(obj, callback, userdata)`. The entry declares the
+/// callback's named parameters and optional return type; the C callback must take the same
+/// parameters followed by the userdata pointer, as an `unsafe extern "C" fn`.
+///
+/// The closure is boxed and kept alive until it is replaced by another call to the method, or
+/// until the last owned value wrapping the object has been freed. It must be `Send` and `'static`. A panic in the closure
+/// cannot unwind through the C library, and will abort the process. This requires the `std`
+/// feature.
+///
+/// The C library may call the closure from several threads at once, for example when the wrapper
+/// is `Sync`. An `FnMut` closure is therefore called with a mutex held, and must not cause the C
+/// library to call it again from within itself, which would deadlock. Entries declared with `Fn`
+/// instead require a closure which is also `Sync`, and call it without locking.
+///
+/// Closures set on a value which is then converted into a raw pointer with `into_ptr` are not
+/// freed, since the C library may still call them. They are freed if the pointer is wrapped by an
+/// owned value again and that value is dropped.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use std::os::raw::{c_int, c_void};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// # mod log_sys {
+/// #     use std::os::raw::{c_int, c_void};
+/// #     pub type LogCallback = unsafe extern "C" fn(level: c_int, userdata: *mut c_void) -> c_int;
+/// #     pub struct CTX { pub cb: Option<(LogCallback, *mut c_void)> }
+/// #     pub unsafe extern "C" fn CTX_free(ctx: *mut CTX) { drop(Box::from_raw(ctx)) }
+/// #     pub unsafe fn CTX_set_log_callback(ctx: *mut CTX, cb: LogCallback, userdata: *mut c_void) {
+/// #         (*ctx).cb = Some((cb, userdata));
+/// #     }
+/// #     pub unsafe fn CTX_log(ctx: *mut CTX, level: c_int) -> c_int {
+/// #         match (*ctx).cb { Some((cb, userdata)) => cb(level, userdata), None => 0 }
+/// #     }
+/// # }
+/// foreign_type! {
+///     type CType = log_sys::CTX;
+///     fn drop = log_sys::CTX_free;
+///     callbacks {
+///         /// Sets the function called for each log message.
+///         pub fn set_log_callback(FnMut(level: c_int) -> c_int) = log_sys::CTX_set_log_callback;
+///     }
+///     /// A context.
+///     pub struct Ctx;
+///     /// A borrowed context.
+///     pub struct CtxRef;
+/// }
+///
+/// # fn main() {
+/// # let mut ctx: Ctx = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(log_sys::CTX { cb: None }))) };
+/// let count = Arc::new(AtomicUsize::new(0));
+/// let counter = count.clone();
+/// ctx.set_log_callback(move |level| {
+///     counter.fetch_add(1, Ordering::SeqCst);
+///     level
+/// });
+///
+/// # let ptr = foreign_types::ForeignTypeRef::as_ptr(&*ctx);
+/// assert_eq!(unsafe { log_sys::CTX_log(ptr, 3) }, 3);
+/// assert_eq!(count.load(Ordering::SeqCst), 1);
+///
+/// // The closure is freed along with the context.
+/// drop(ctx);
+/// assert_eq!(Arc::strong_count(&count), 1);
+/// # }
+/// ```
+///
+/// Objects may have several owners, such as clones of a `ForeignRc` or values returned by a
+/// `fn clone` entry which only increments a reference count. Their closures are freed along with
+/// the last owned value. A type with both `callbacks` and `fn up_ref` must also have a
+/// `fn refcount_get` entry, so that closures are kept while the C library holds other references.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use foreign_types::callbacks::live_callbacks;
+/// use foreign_types::rc::ForeignRc;
+/// use std::os::raw::{c_int, c_void};
+///
+/// # mod log_sys {
+/// #     use std::os::raw::{c_int, c_void};
+/// #     pub type LogCallback = unsafe extern "C" fn(level: c_int, userdata: *mut c_void) -> c_int;
+/// #     pub struct CTX { pub refs: u32, pub cb: Option<(LogCallback, *mut c_void)> }
+/// #     pub unsafe fn CTX_new() -> *mut CTX { Box::into_raw(Box::new(CTX { refs: 1, cb: None })) }
+/// #     pub unsafe fn CTX_up_ref(ctx: *mut CTX) { (*ctx).refs += 1 }
+/// #     pub unsafe fn CTX_refs(ctx: *mut CTX) -> u32 { (*ctx).refs }
+/// #     pub unsafe extern "C" fn CTX_free(ctx: *mut CTX) {
+/// #         (*ctx).refs -= 1;
+/// #         if (*ctx).refs == 0 { drop(Box::from_raw(ctx)) }
+/// #     }
+/// #     pub unsafe fn CTX_set_log_callback(ctx: *mut CTX, cb: LogCallback, userdata: *mut c_void) {
+/// #         (*ctx).cb = Some((cb, userdata));
+/// #     }
+/// #     pub unsafe fn CTX_log(ctx: *mut CTX, level: c_int) -> c_int {
+/// #         match (*ctx).cb { Some((cb, userdata)) => cb(level, userdata), None => 0 }
+/// #     }
+/// # }
+/// foreign_type! {
+///     type CType = log_sys::CTX;
+///     fn drop = log_sys::CTX_free;
+///     fn up_ref = log_sys::CTX_up_ref;
+///     fn refcount_get = log_sys::CTX_refs;
+///     callbacks {
+///         /// Sets the function called for each log message.
+///         pub fn set_log_callback(Fn(level: c_int) -> c_int) = log_sys::CTX_set_log_callback;
+///     }
+///     /// A context.
+///     pub struct Ctx;
+///     /// A borrowed context.
+///     pub struct CtxRef;
+/// }
+///
+/// # fn main() {
+/// use foreign_types::ForeignTypeRef;
+///
+/// let mut ctx: ForeignRc<Ctx> = unsafe { ForeignRc::from_ptr(log_sys::CTX_new()) };
+/// ForeignRc::get_mut(&mut ctx).unwrap().set_log_callback(|level| level + 1);
+/// assert_eq!(live_callbacks(), 1);
+///
+/// // Dropping one clone keeps the closure, which the object can still call.
+/// let other = ctx.clone();
+/// drop(ctx);
+/// assert_eq!(live_callbacks(), 1);
+/// assert_eq!(unsafe { log_sys::CTX_log(other.as_ptr(), 1) }, 2);
+///
+/// drop(other);
+/// assert_eq!(live_callbacks(), 0);
+/// # }
+/// ```
+///
+/// # Visitors
+///
+/// A `foreach` block generates methods on the borrowed type which iterate over items with a C
+/// visitor function of the form `X_foreach(obj, callback, userdata)`, using
+/// `foreach::for_each`. The generated method takes a closure returning `Result<(), E>`; an error
+/// stops iteration and is returned from the method.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # use std::os::raw::c_void;
+/// # mod list_sys {
+/// #     use std::os::raw::c_void;
+/// #     use foreign_types::foreach::Callback;
+/// #     pub enum ITEM {}
+/// #     pub enum LIST {}
+/// #     pub unsafe extern "C" fn ITEM_free(_: *mut ITEM) {}
+/// #     pub unsafe extern "C" fn LIST_free(_: *mut LIST) {}
+/// #     pub unsafe fn LIST_foreach(_: *mut LIST, _: Callback<ITEM>, _: *mut c_void) {}
+/// # }
+/// foreign_type! {
+///     type CType = list_sys::ITEM;
+///     fn drop = list_sys::ITEM_free;
+///     /// An item.
+///     pub struct Item;
+///     /// A borrowed item.
+///     pub struct ItemRef;
+/// }
+///
+/// foreign_type! {
+///     type CType = list_sys::LIST;
+///     fn drop = list_sys::LIST_free;
+///     foreach {
+///         /// Calls `f` on each item in the list.
+///         pub fn for_each(&ItemRef) = list_sys::LIST_foreach;
+///     }
+///     /// A list.
+///     pub struct List;
+///     /// A borrowed list.
+///     pub struct ListRef;
+/// }
+///
+/// fn count(list: &ListRef) -> usize {
+///     let mut count = 0;
+///     list.for_each(|_| {
+///         count += 1;
+///         Ok::<(), ()>(())
+///     }).unwrap();
+///     count
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// # Cursors
+///
+/// A `cursor` block generates methods on the borrowed type for C types which act as cursors,
+/// advanced by a function of the form `X_next(cursor)` that returns null once the end is reached.
+///
+/// Items declared as `owned T` are owned by the caller, and the method mutably borrows the cursor
+/// and returns an iterator over the remaining items.
+///
+/// Items declared as `&T` are borrowed from the cursor, which may reuse their storage for the next
+/// item, as `readdir` does. The method instead advances the cursor by one item and returns it, or
+/// `None` at the end. The item borrows the cursor mutably, so it cannot be kept once the cursor is
+/// advanced again; copy the data out, or use an `owned T` entry, to keep it for longer.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod dir_sys {
+/// #     pub struct ENTRY(pub u32);
+/// #     pub struct DIR { pub entries: Vec<ENTRY>, pub pos: usize }
+/// #     pub unsafe extern "C" fn ENTRY_free(entry: *mut ENTRY) { drop(Box::from_raw(entry)) }
+/// #     pub unsafe fn ENTRY_id(entry: *mut ENTRY) -> u32 { (*entry).0 }
+/// #     pub unsafe extern "C" fn DIR_free(dir: *mut DIR) { drop(Box::from_raw(dir)) }
+/// #     pub unsafe fn DIR_next(dir: *mut DIR) -> *mut ENTRY {
+/// #         let dir = &mut *dir;
+/// #         match dir.entries.get_mut(dir.pos) {
+/// #             Some(entry) => { dir.pos += 1; entry }
+/// #             None => 0 as *mut _,
+/// #         }
+/// #     }
+/// #     pub unsafe fn DIR_next_copy(dir: *mut DIR) -> *mut ENTRY {
+/// #         let entry = DIR_next(dir);
+/// #         if entry.is_null() { entry } else { Box::into_raw(Box::new(ENTRY((*entry).0))) }
+/// #     }
+/// # }
+/// foreign_type! {
+///     type CType = dir_sys::ENTRY;
+///     fn drop = dir_sys::ENTRY_free;
+///     getters {
+///         fn id -> u32 = dir_sys::ENTRY_id;
+///     }
+///     /// A directory entry.
+///     pub struct Entry;
+///     /// A borrowed directory entry.
+///     pub struct EntryRef;
+/// }
+///
+/// foreign_type! {
+///     type CType = dir_sys::DIR;
+///     fn drop = dir_sys::DIR_free;
+///     cursor {
+///         /// Advances to the next entry, returning it.
+///         pub fn next_entry -> &EntryRef = dir_sys::DIR_next;
+///         /// Returns an iterator over copies of the remaining entries.
+///         pub fn entry_copies -> owned Entry = dir_sys::DIR_next_copy;
+///     }
+///     /// A directory.
+///     pub struct Dir;
+///     /// A borrowed directory.
+///     pub struct DirRef;
+/// }
+///
+/// # fn main() {
+/// # let entries = (0..4).map(dir_sys::ENTRY).collect();
+/// # let mut dir: Dir = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(dir_sys::DIR { entries, pos: 0 }))) };
+/// let mut first = vec![];
+/// while let Some(entry) = dir.next_entry() {
+///     first.push(entry.id());
+///     if first.len() == 2 {
+///         break;
+///     }
+/// }
+/// assert_eq!(first, [0, 1]);
+/// let rest = dir.entry_copies().collect::<Vec<Entry>>();
+/// assert_eq!(rest.len(), 2);
+/// assert_eq!(rest[1].id(), 3);
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// # #[macro_use]
+/// # extern crate foreign_types;
+/// # mod dir_sys {
+/// #     pub enum ENTRY {}
+/// #     pub enum DIR {}
+/// #     pub unsafe extern "C" fn ENTRY_free(_: *mut ENTRY) {}
+/// #     pub unsafe extern "C" fn DIR_free(_: *mut DIR) {}
+/// #     pub unsafe fn DIR_next(_: *mut DIR) -> *mut ENTRY { 0 as *mut _ }
+/// # }
+/// # foreign_type! {
+/// #     type CType = dir_sys::ENTRY;
+/// #     fn drop = dir_sys::ENTRY_free;
+/// #     pub struct Entry;
+/// #     pub struct EntryRef;
+/// # }
+/// # foreign_type! {
+/// #     type CType = dir_sys::DIR;
+/// #     fn drop = dir_sys::DIR_free;
+/// #     cursor {
+/// #         pub fn next_entry -> &EntryRef = dir_sys::DIR_next;
+/// #     }
+/// #     pub struct Dir;
+/// #     pub struct DirRef;
+/// # }
+/// # fn main() {}
+/// // Borrowed items cannot be kept across calls.
+/// fn first_two(dir: &mut DirRef) -> (Option<&EntryRef>, Option<&EntryRef>) {
+///     let first = dir.next_entry();
+///     (first, dir.next_entry())
+/// }
+/// ```
+///
+/// # Linked lists
+///
+/// A `list_cursor` block generates a method on the borrowed type which mutably borrows a C linked
+/// list and returns a cursor over its items, declared by the block as `fn name -> Cursor<Item>`.
+/// The cursor can move in both directions, remove the item at the cursor as an owned value, and
+/// insert owned items into the list. The list cannot be otherwise accessed while the cursor is
+/// alive, and references to items are only valid until the cursor is next used.
+///
+/// The block names four functions, each taking a pointer to the list first:
+///
+/// * `fn next` and `fn prev` take an item and return the item after or before it, or null at the
+///   end of the list. Given null, they return the first and last items respectively, in the style
+///   of `nvlist_next_nvpair`. For item types with an `INVALID` value, it is used in place of null.
+/// * `fn remove` takes an item and unlinks it from the list, passing its ownership to the caller.
+/// * `fn insert` takes an item, which may be null to mean the end of the list, and an owned item
+///   which it inserts before it.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod list_sys {
+/// #     pub struct ITEM(pub u32);
+/// #     pub struct LIST(pub Vec<*mut ITEM>);
+/// #     pub unsafe fn ITEM_new(id: u32) -> *mut ITEM { Box::into_raw(Box::new(ITEM(id))) }
+/// #     pub unsafe extern "C" fn ITEM_free(item: *mut ITEM) { drop(Box::from_raw(item)) }
+/// #     pub unsafe fn ITEM_id(item: *mut ITEM) -> u32 { (*item).0 }
+/// #     pub unsafe extern "C" fn LIST_free(list: *mut LIST) {
+/// #         let items = &(*list).0;
+/// #         for &item in items { ITEM_free(item) }
+/// #         drop(Box::from_raw(list))
+/// #     }
+/// #     fn pos(items: &[*mut ITEM], item: *mut ITEM) -> usize { items.iter().position(|&i| i == item).unwrap() }
+/// #     pub unsafe fn LIST_next(list: *mut LIST, item: *mut ITEM) -> *mut ITEM {
+/// #         let items = &(*list).0;
+/// #         let i = if item.is_null() { 0 } else { pos(items, item) + 1 };
+/// #         items.get(i).cloned().unwrap_or(0 as *mut _)
+/// #     }
+/// #     pub unsafe fn LIST_prev(list: *mut LIST, item: *mut ITEM) -> *mut ITEM {
+/// #         let items = &(*list).0;
+/// #         let i = if item.is_null() { items.len() } else { pos(items, item) };
+/// #         if i == 0 { 0 as *mut _ } else { items[i - 1] }
+/// #     }
+/// #     pub unsafe fn LIST_remove(list: *mut LIST, item: *mut ITEM) {
+/// #         let items = &mut (*list).0;
+/// #         let i = pos(items, item);
+/// #         items.remove(i);
+/// #     }
+/// #     pub unsafe fn LIST_insert(list: *mut LIST, before: *mut ITEM, item: *mut ITEM) {
+/// #         let items = &mut (*list).0;
+/// #         let i = if before.is_null() { items.len() } else { pos(items, before) };
+/// #         items.insert(i, item);
+/// #     }
+/// # }
+/// foreign_type! {
+///     type CType = list_sys::ITEM;
+///     fn drop = list_sys::ITEM_free;
+///     getters {
+///         fn id -> u32 = list_sys::ITEM_id;
+///     }
+///     /// An item.
+///     pub struct Item;
+///     /// A borrowed item.
+///     pub struct ItemRef;
+/// }
+///
+/// foreign_type! {
+///     type CType = list_sys::LIST;
+///     fn drop = list_sys::LIST_free;
+///     list_cursor {
+///         /// Returns a cursor at the first item of the list.
+///         pub fn cursor_mut -> ItemCursor<Item>;
+///         fn next = list_sys::LIST_next;
+///         fn prev = list_sys::LIST_prev;
+///         fn remove = list_sys::LIST_remove;
+///         fn insert = list_sys::LIST_insert;
+///     }
+///     /// A list of items.
+///     pub struct List;
+///     /// A borrowed list of items.
+///     pub struct ListRef;
+/// }
+///
+/// fn remove_odd(list: &mut ListRef) -> Vec<Item> {
+///     let mut removed = vec![];
+///     let mut cursor = list.cursor_mut();
+///     while let Some(item) = cursor.current() {
+///         if item.id() % 2 == 1 {
+///             removed.extend(cursor.remove_current());
+///         } else {
+///             cursor.move_next();
+///         }
+///     }
+///     removed
+/// }
+///
+/// # fn main() {
+/// use foreign_types::ForeignType;
+///
+/// # let items = unsafe { (0..4).map(|i| list_sys::ITEM_new(i)).collect() };
+/// # let mut list = unsafe { List::from_ptr(Box::into_raw(Box::new(list_sys::LIST(items)))) };
+/// let removed = remove_odd(&mut list);
+/// assert_eq!(removed.iter().map(|item| item.id()).collect::<Vec<_>>(), [1, 3]);
+///
+/// let mut cursor = list.cursor_mut();
+/// cursor.insert_before(unsafe { Item::from_ptr(list_sys::ITEM_new(5)) });
+/// cursor.move_prev();
+/// assert_eq!(cursor.current().unwrap().id(), 5);
+/// cursor.move_prev();
+/// assert!(cursor.current().is_none());
+/// cursor.move_prev();
+/// assert_eq!(cursor.current().unwrap().id(), 2);
+/// # }
+/// ```
+///
+/// # Collections
+///
+/// A `collect` block implements `Extend` for C containers with a function of the form
+/// `X_push(container, item)`, so that they can be filled from iterators. Each `fn push` entry
+/// declares how items are passed, in the same way as a setter:
+///
+/// * `(owned Foo)` implements `Extend<Foo>`, transferring ownership of each item with `into_ptr`.
+/// * `(&FooRef)` implements `Extend<&FooRef>`, lending each item to a function which copies it.
+/// * Any other type implements `Extend` for that type, passing items as-is.
+///
+/// `Extend` is implemented for both the owned and the borrowed type. If the block starts with a
+/// `fn new = ...;` entry naming a constructor for an empty container, the owned type also
+/// implements `FromIterator`, so that containers can be built with `collect`. The constructor
+/// panics if it returns null. The return values of the push functions are ignored, so fallible
+/// functions should be wrapped to panic on failure.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod list_sys {
+/// #     pub struct ITEM(pub u32);
+/// #     pub struct LIST(pub Vec<Box<ITEM>>);
+/// #     pub unsafe fn ITEM_new(id: u32) -> *mut ITEM { Box::into_raw(Box::new(ITEM(id))) }
+/// #     pub unsafe extern "C" fn ITEM_free(item: *mut ITEM) { drop(Box::from_raw(item)) }
+/// #     pub unsafe fn LIST_new() -> *mut LIST { Box::into_raw(Box::new(LIST(vec![]))) }
+/// #     pub unsafe extern "C" fn LIST_free(list: *mut LIST) { drop(Box::from_raw(list)) }
+/// #     pub unsafe fn LIST_push(list: *mut LIST, item: *mut ITEM) { (*list).0.push(Box::from_raw(item)) }
+/// #     pub unsafe fn LIST_push_copy(list: *mut LIST, item: *mut ITEM) { LIST_push(list, ITEM_new((*item).0)) }
+/// #     pub unsafe fn LIST_push_id(list: *mut LIST, id: u32) { LIST_push(list, ITEM_new(id)) }
+/// #     pub unsafe fn LIST_len(list: *mut LIST) -> usize { (*list).0.len() }
+/// # }
+/// foreign_type! {
+///     type CType = list_sys::ITEM;
+///     fn drop = list_sys::ITEM_free;
+///     /// An item.
+///     pub struct Item;
+///     /// A borrowed item.
+///     pub struct ItemRef;
+/// }
+///
+/// foreign_type! {
+///     type CType = list_sys::LIST;
+///     fn drop = list_sys::LIST_free;
+///     getters {
+///         fn len -> usize = list_sys::LIST_len;
+///     }
+///     collect {
+///         fn new = list_sys::LIST_new;
+///         fn push(owned Item) = list_sys::LIST_push;
+///         fn push(&ItemRef) = list_sys::LIST_push_copy;
+///         fn push(u32) = list_sys::LIST_push_id;
+///     }
+///     /// A list of items.
+///     pub struct List;
+///     /// A borrowed list of items.
+///     pub struct ListRef;
+/// }
+///
+/// # fn main() {
+/// use foreign_types::ForeignType;
+///
+/// let items = (0..3).map(|i| unsafe { Item::from_ptr(list_sys::ITEM_new(i)) });
+/// let mut list = items.collect::<List>();
+/// assert_eq!(list.len(), 3);
+///
+/// let item = unsafe { Item::from_ptr(list_sys::ITEM_new(3)) };
+/// list.extend(Some(&*item));
+/// list.extend(4..6);
+/// assert_eq!(list.len(), 6);
+/// # }
+/// ```
+///
+/// # Conversions
+///
+/// A `converts` block implements `From` and `TryFrom` between wrapper types for C functions which
+/// convert one kind of object into another, such as `EVP_PKEY_get1_RSA`. Each entry names the
+/// source type in the same way as a setter parameter, and the owned type it converts into:
+///
+/// * `fn from(owned Foo) -> Bar` implements `From<Foo> for Bar`, passing ownership of the source
+///   to the function with `into_ptr`.
+/// * `fn from(&FooRef) -> Bar` implements `From<&FooRef> for Bar`, lending the source to the
+///   function, which must return a new reference or copy.
+/// * `fn try_from(owned Foo) -> Bar` and `fn try_from(&FooRef) -> Bar` implement `TryFrom` in the
+///   same way.
+///
+/// The function returns null on failure. `From` implementations panic in that case, while
+/// `TryFrom` implementations return a `ConvertError`, or the error captured by `fn error_source`
+/// if it is also specified. A consumed source is not returned on failure, since its ownership has
+/// already been passed to the function.
+///
+/// The block may appear in the definition of either type, or of neither.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use std::convert::TryFrom;
+///
+/// # mod key_sys {
+/// #     pub struct KEY(pub u32);
+/// #     pub struct RSA(pub u32);
+/// #     pub unsafe fn KEY_new(bits: u32) -> *mut KEY { Box::into_raw(Box::new(KEY(bits))) }
+/// #     pub unsafe extern "C" fn KEY_free(key: *mut KEY) { drop(Box::from_raw(key)) }
+/// #     pub unsafe fn RSA_new(bits: u32) -> *mut RSA { Box::into_raw(Box::new(RSA(bits))) }
+/// #     pub unsafe extern "C" fn RSA_free(rsa: *mut RSA) { drop(Box::from_raw(rsa)) }
+/// #     pub unsafe fn RSA_bits(rsa: *mut RSA) -> u32 { (*rsa).0 }
+/// #     pub unsafe fn KEY_get1_RSA(key: *mut KEY) -> *mut RSA {
+/// #         if (*key).0 == 0 { 0 as *mut _ } else { RSA_new((*key).0) }
+/// #     }
+/// #     pub unsafe fn KEY_from_RSA(rsa: *mut RSA) -> *mut KEY {
+/// #         let key = KEY_new((*rsa).0);
+/// #         RSA_free(rsa);
+/// #         key
+/// #     }
+/// # }
+/// foreign_type! {
+///     type CType = key_sys::RSA;
+///     fn drop = key_sys::RSA_free;
+///     getters {
+///         fn bits -> u32 = key_sys::RSA_bits;
+///     }
+///     /// An RSA key.
+///     pub struct Rsa;
+///     /// A borrowed RSA key.
+///     pub struct RsaRef;
+/// }
+///
+/// foreign_type! {
+///     type CType = key_sys::KEY;
+///     fn drop = key_sys::KEY_free;
+///     converts {
+///         fn from(owned Rsa) -> Key = key_sys::KEY_from_RSA;
+///         fn try_from(&KeyRef) -> Rsa = key_sys::KEY_get1_RSA;
+///     }
+///     /// A key.
+///     pub struct Key;
+///     /// A borrowed key.
+///     pub struct KeyRef;
+/// }
+///
+/// # fn main() {
+/// use foreign_types::ForeignType;
+///
+/// let rsa = unsafe { Rsa::from_ptr(key_sys::RSA_new(2048)) };
+/// let key = Key::from(rsa);
+/// assert_eq!(Rsa::try_from(&*key).unwrap().bits(), 2048);
+///
+/// let empty = unsafe { Key::from_ptr(key_sys::KEY_new(0)) };
+/// assert!(Rsa::try_from(&*empty).is_err());
+/// # }
+/// ```
+///
+/// # Guards
+///
+/// A `guards` block generates methods for C functions which must be called in pairs, such as
+/// `X_lock` and `X_unlock` or `X_map` and `X_unmap`. Each entry declares a guard type, along with
+/// any attributes for it, followed by a method which calls the first function and returns the
+/// guard. The guard calls the second function with the object's pointer when it is dropped. Every
+/// method takes `&mut self`, so the object remains mutably borrowed while the guard is alive, and
+/// the first function is never called again before the second. The method's return type
+/// determines what the guard dereferences to:
+///
+/// * `-> Guard` dereferences to the borrowed type. The result of the first function is ignored.
+/// * `-> Guard<&FooRef>` dereferences to the `FooRef` returned by the first function.
+/// * `-> Guard<&mut FooRef>` does the same and also implements `DerefMut`.
+///
+/// The methods for the last two forms return `None` if the first function returns null, in which
+/// case the second function is not called. When `fn error_source` is also specified, they return
+/// a `Result` with the captured error instead. With the first form, the first function must
+/// succeed. For these two forms, the second function may instead be written as
+/// `X_unmap(owner, resource)`, in which case it is passed the pointer returned by the first
+/// function after the object's pointer. Guards are not available on immutable types.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod db_sys {
+/// #     pub struct TXN;
+/// #     pub struct DB { pub txn: TXN, pub depth: u32 }
+/// #     pub unsafe extern "C" fn DB_free(db: *mut DB) { drop(Box::from_raw(db)) }
+/// #     pub unsafe fn DB_begin(db: *mut DB) -> *mut TXN { (*db).depth += 1; &mut (*db).txn }
+/// #     pub unsafe fn DB_commit(db: *mut DB) { (*db).depth -= 1 }
+/// #     pub unsafe fn DB_depth(db: *mut DB) -> u32 { (*db).depth }
+/// #     pub unsafe extern "C" fn TXN_free(_: *mut TXN) {}
+/// # }
+/// foreign_type! {
+///     type CType = db_sys::TXN;
+///     fn drop = db_sys::TXN_free;
+///     /// A transaction.
+///     pub struct Transaction;
+///     /// A borrowed transaction.
+///     pub struct TransactionRef;
+/// }
+///
+/// foreign_type! {
+///     type CType = db_sys::DB;
+///     fn drop = db_sys::DB_free;
+///     getters {
+///         fn depth -> u32 = db_sys::DB_depth;
+///     }
+///     guards {
+///         /// A transaction, which is committed when it is dropped.
+///         pub struct TransactionGuard;
+///         /// Begins a transaction.
+///         pub fn begin -> TransactionGuard<&mut TransactionRef> = db_sys::DB_begin, db_sys::DB_commit;
+///     }
+///     /// A database.
+///     pub struct Db;
+///     /// A borrowed database.
+///     pub struct DbRef;
+/// }
+///
+/// # fn main() {
+/// # let mut db: Db = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(db_sys::DB { txn: db_sys::TXN, depth: 0 }))) };
+/// {
+///     let mut txn = db.begin().unwrap();
+///     let _txn: &mut TransactionRef = &mut txn;
+///     // The transaction is committed here.
+/// }
+/// assert_eq!(db.depth(), 0);
+/// # }
+/// ```
+///
+/// A release function written with `(owner, resource)` receives the mapped pointer as well:
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod buf_sys {
+/// #     pub struct MAP { pub len: usize }
+/// #     pub struct BUF { pub maps: Vec<*mut MAP> }
+/// #     pub unsafe extern "C" fn BUF_free(buf: *mut BUF) { drop(Box::from_raw(buf)) }
+/// #     pub unsafe fn BUF_map(buf: *mut BUF) -> *mut MAP {
+/// #         let map = Box::into_raw(Box::new(MAP { len: 16 }));
+/// #         (*buf).maps.push(map);
+/// #         map
+/// #     }
+/// #     pub unsafe fn BUF_unmap(buf: *mut BUF, map: *mut MAP) {
+/// #         (*buf).maps.retain(|&m| m != map);
+/// #         drop(Box::from_raw(map));
+/// #     }
+/// #     pub unsafe fn BUF_num_maps(buf: *mut BUF) -> usize { (*buf).maps.len() }
+/// #     pub unsafe fn MAP_len(map: *mut MAP) -> usize { (*map).len }
+/// #     pub unsafe extern "C" fn MAP_free(_: *mut MAP) {}
+/// # }
+/// foreign_type! {
+///     type CType = buf_sys::MAP;
+///     fn drop = buf_sys::MAP_free;
+///     getters {
+///         fn len -> usize = buf_sys::MAP_len;
+///     }
+///     /// A mapping of a buffer.
+///     pub struct Mapping;
+///     /// A borrowed mapping.
+///     pub struct MappingRef;
+/// }
+///
+/// foreign_type! {
+///     type CType = buf_sys::BUF;
+///     fn drop = buf_sys::BUF_free;
+///     getters {
+///         fn num_maps -> usize = buf_sys::BUF_num_maps;
+///     }
+///     guards {
+///         /// A mapping, which is unmapped when it is dropped.
+///         pub struct MapGuard;
+///         /// Maps the buffer.
+///         pub fn map -> MapGuard<&MappingRef> = buf_sys::BUF_map, buf_sys::BUF_unmap(owner, resource);
+///     }
+///     /// A buffer.
+///     pub struct Buf;
+///     /// A borrowed buffer.
+///     pub struct BufRef;
+/// }
+///
+/// # fn main() {
+/// # let mut buf: Buf = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(buf_sys::BUF { maps: vec![] }))) };
+/// {
+///     let map = buf.map().unwrap();
+///     assert_eq!(map.len(), 16);
+/// }
+/// assert_eq!(buf.num_maps(), 0);
+/// # }
+/// ```
+///
+/// Since the object is mutably borrowed, a second guard cannot be acquired while the first is
+/// alive:
+///
+/// ```compile_fail,E0499
+/// # #[macro_use]
+/// # extern crate foreign_types;
+/// # mod buf_sys {
+/// #     pub struct MAP;
+/// #     pub struct BUF;
+/// #     pub unsafe extern "C" fn BUF_free(_: *mut BUF) {}
+/// #     pub unsafe fn BUF_map(_: *mut BUF) -> *mut MAP { unimplemented!() }
+/// #     pub unsafe fn BUF_unmap(_: *mut BUF, _: *mut MAP) {}
+/// #     pub unsafe extern "C" fn MAP_free(_: *mut MAP) {}
+/// # }
+/// # foreign_type! {
+/// #     type CType = buf_sys::MAP;
+/// #     fn drop = buf_sys::MAP_free;
+/// #     pub struct Mapping;
+/// #     pub struct MappingRef;
+/// # }
+/// # foreign_type! {
+/// #     type CType = buf_sys::BUF;
+/// #     fn drop = buf_sys::BUF_free;
+/// #     guards {
+/// #         pub struct MapGuard;
+/// #         pub fn map -> MapGuard<&MappingRef> = buf_sys::BUF_map, buf_sys::BUF_unmap(owner, resource);
+/// #     }
+/// #     pub struct Buf;
+/// #     pub struct BufRef;
+/// # }
+/// fn both(buf: &mut BufRef) {
+///     let first = buf.map();
+///     let second = buf.map();
+///     drop((first, second));
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Builders
+///
+/// C libraries often configure objects through a series of setter calls after creating them. A
+/// `builder` block generates a builder type wrapping a newly created owned value. It consists of
+/// the builder's declaration, the C constructor as `fn new`, an optional finalization function
+/// as `fn build`, and a list of setters in the same form as a `setters` block. Each setter consumes
+/// and returns the builder so calls can be chained. Setters in a builder cannot return values.
+///
+/// Without `fn build`, the builder's `build` method simply returns the owned value. With it,
+/// `build` passes the value's pointer to the function, which must return a `Result<(), E>`, and
+/// only returns the value if it succeeds.
+///
+/// The builder's `new` method and its `Default` implementation panic if the C constructor returns
+/// null. `try_new` returns `None` instead.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # use std::os::raw::c_int;
+/// # mod foo_sys {
+/// #     use std::os::raw::c_int;
+/// #     pub struct FOO { pub verbose: c_int }
+/// #     pub unsafe fn FOO_new() -> *mut FOO { Box::into_raw(Box::new(FOO { verbose: 0 })) }
+/// #     pub unsafe extern "C" fn FOO_free(foo: *mut FOO) { drop(Box::from_raw(foo)) }
+/// #     pub unsafe fn FOO_set_verbose(foo: *mut FOO, verbose: c_int) { (*foo).verbose = verbose }
+/// #     pub unsafe fn FOO_get_verbose(foo: *mut FOO) -> c_int { (*foo).verbose }
+/// #     pub unsafe fn FOO_finalize(foo: *mut FOO) -> c_int { if (*foo).verbose < 0 { 0 } else { 1 } }
+/// # }
+/// unsafe fn finalize(foo: *mut foo_sys::FOO) -> Result<(), c_int> {
+///     match foo_sys::FOO_finalize(foo) {
+///         1 => Ok(()),
+///         e => Err(e),
+///     }
+/// }
+///
+/// foreign_type! {
+///     type CType = foo_sys::FOO;
+///     fn drop = foo_sys::FOO_free;
+///     getters {
+///         /// Returns the verbosity.
+///         pub fn verbose -> c_int = foo_sys::FOO_get_verbose;
+///     }
+///     builder {
+///         /// A builder for `Foo`s.
+///         pub struct FooBuilder;
+///         fn new = foo_sys::FOO_new;
+///         fn build -> Result<Foo, c_int> = finalize;
+///         /// Sets the verbosity.
+///         pub fn verbose(c_int) = foo_sys::FOO_set_verbose;
+///     }
+///     /// A Foo.
+///     pub struct Foo;
+///     /// A borrowed Foo.
+///     pub struct FooRef;
+/// }
+///
+/// # fn main() {
+/// let foo = FooBuilder::new().verbose(2).build().unwrap();
+/// assert_eq!(foo.verbose(), 2);
+/// assert!(FooBuilder::new().verbose(-1).build().is_err());
+///
+/// let foo = FooBuilder::try_new().expect("out of memory").build().unwrap();
+/// assert_eq!(foo.verbose(), 0);
+/// # }
+/// ```
+///
+/// # Pointer types
+///
+/// Structs which are passed to C, such as configuration or callback tables, often contain pointers
+/// to wrapped objects. A `ptr` block declares a `#[repr(transparent)]` wrapper around
+/// `*mut CType`, which has the same layout as the raw pointer and can be used as a field of a
+/// `#[repr(C)]` struct in its place.
+///
+/// Like a raw pointer, the pointer type is `Copy`, may be null, and does not own the object it
+/// points to. For types with an `INVALID` value, that value is treated as null instead. Converting an owned value into it with `From` releases ownership, which must be
+/// reclaimed with the unsafe `into_owned` method or taken over by the C library. Converting a
+/// reference into it only borrows the object, and the pointer must not be used after the borrow
+/// ends.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod cfg_sys {
+/// #     pub struct LOGGER(pub u32);
+/// #     pub unsafe fn LOGGER_new(level: u32) -> *mut LOGGER { Box::into_raw(Box::new(LOGGER(level))) }
+/// #     pub unsafe extern "C" fn LOGGER_free(logger: *mut LOGGER) { drop(Box::from_raw(logger)) }
+/// #     pub unsafe fn LOGGER_level(logger: *mut LOGGER) -> u32 { (*logger).0 }
+/// # }
+/// foreign_type! {
+///     type CType = cfg_sys::LOGGER;
+///     fn drop = cfg_sys::LOGGER_free;
+///     getters {
+///         fn level -> u32 = cfg_sys::LOGGER_level;
+///     }
+///     ptr {
+///         /// A pointer to a logger.
+///         pub struct LoggerPtr;
+///     }
+///     /// A logger.
+///     pub struct Logger;
+///     /// A borrowed logger.
+///     pub struct LoggerRef;
+/// }
+///
+/// /// The configuration passed to `CFG_init`.
+/// #[repr(C)]
+/// pub struct Config {
+///     pub logger: LoggerPtr,
+///     pub flags: u32,
+/// }
+///
+/// # fn main() {
+/// use foreign_types::ForeignType;
+///
+/// let logger = unsafe { Logger::from_ptr(cfg_sys::LOGGER_new(3)) };
+/// let config = Config { logger: LoggerPtr::from(&*logger), flags: 0 };
+/// assert_eq!(unsafe { config.logger.as_ref() }.unwrap().level(), 3);
+/// assert!(Config { logger: LoggerPtr::null(), flags: 0 }.logger.is_null());
+/// # let _ = config.flags;
+/// # }
+/// ```
+///
+/// # Facets
+///
+/// Some C objects expose independent capabilities, such as the read and write halves of a
+/// connection. A `facets` block declares additional borrowed types for the same C type, each of
+/// which can be given its own methods. The borrowed type implements `AsRef` and `AsMut` for each
+/// facet, and gains a `split` method which mutably borrows all facets at once. Declaring facets
+/// asserts that the C library allows them to be used independently of each other.
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # use std::os::raw::c_int;
+/// # mod conn_sys {
+/// #     use std::os::raw::c_int;
+/// #     pub struct CONN { pub read: c_int, pub written: c_int }
+/// #     pub unsafe extern "C" fn CONN_free(conn: *mut CONN) { drop(Box::from_raw(conn)) }
+/// #     pub unsafe fn CONN_read(conn: *mut CONN) -> c_int { (*conn).read += 1; (*conn).read }
+/// #     pub unsafe fn CONN_write(conn: *mut CONN) -> c_int { (*conn).written += 1; (*conn).written }
+/// # }
+/// use foreign_types::ForeignTypeRef;
+///
+/// foreign_type! {
+///     type CType = conn_sys::CONN;
+///     fn drop = conn_sys::CONN_free;
+///     facets {
+///         /// The reading half of a connection.
+///         pub struct ReadHalf;
+///         /// The writing half of a connection.
+///         pub struct WriteHalf;
+///     }
+///     /// A connection.
+///     pub struct Conn;
+///     /// A borrowed connection.
+///     pub struct ConnRef;
+/// }
+///
+/// impl ReadHalf {
+///     fn read(&mut self) -> c_int {
+///         unsafe { conn_sys::CONN_read(self.as_ptr()) }
+///     }
+/// }
+///
+/// impl WriteHalf {
+///     fn write(&mut self) -> c_int {
+///         unsafe { conn_sys::CONN_write(self.as_ptr()) }
+///     }
+/// }
+///
+/// # fn main() {
+/// # let mut conn: Conn = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(conn_sys::CONN { read: 0, written: 0 }))) };
+/// let (read, write) = conn.split();
+/// assert_eq!(read.read(), 1);
+/// assert_eq!(write.write(), 1);
+/// assert_eq!(read.read(), 2);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! foreign_type {
+    (
+        $(#[$impl_attr:meta])*
+        type CType = $ctype:ty;
+        $($t:tt)*
+    ) => {
+        $crate::__foreign_type_impl! {
+            @parse [$(#[$impl_attr])*] $ctype; []
+            $($t)*
+        }
+    };
+}
+
+/// A macro to define wrappers for foreign handles which are passed by value.
+///
+/// This is the counterpart of `foreign_type!` for C APIs which identify objects with a small
+/// `Copy` value, such as `typedef struct { void *p; uint32_t gen; } handle_t`, rather than a
+/// pointer. The owned type stores the handle and passes it to the `drop` function when dropped.
+/// The borrowed type is a `#[repr(transparent)]` wrapper around the handle, which the owned type
+/// dereferences to, and is where methods should be defined.
+///
+/// As with `foreign_type!`, the `drop` function is checked at compile time to be an `extern "C"`
+/// function taking the handle by value. Functions with another ABI are declared with it, as in
+/// `extern "Rust" fn drop = ...;`.
+///
+/// ```compile_fail,E0308
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// foreign_handle! {
+///     type CType = u32;
+///     fn drop = std::mem::drop;
+///     /// A handle.
+///     pub struct Handle;
+///     /// A borrowed handle.
+///     pub struct HandleRef;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// An optional `fn clone = ...;` entry names a function which returns a new handle to a copy of
+/// the object, and is used to implement `Clone` and `ToOwned`.
+///
+/// An optional `const INVALID: Self::CType = ...;` entry before `fn drop` sets the handle's
+/// sentinel value, such as `-1` for a file descriptor, which `ForeignHandle::from_handle_opt`
+/// maps to `None`.
+///
+/// Like `foreign_type!`, the generated types are neither `Send` nor `Sync` by default, even if the
+/// handle type is, since a handle which is a plain integer or struct says nothing about the
+/// thread-safety of the object it identifies. `unsafe impl Send;` and `unsafe impl Sync;` entries
+/// after `fn clone` implement the corresponding traits for both types.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use foreign_types::{ForeignHandle, ForeignHandleRef};
+///
+/// mod gpu_sys {
+///     #[derive(Clone, Copy)]
+///     #[repr(C)]
+///     pub struct buffer_t {
+///         pub id: u32,
+///         pub generation: u32,
+///     }
+/// #   pub unsafe fn buffer_create(id: u32) -> buffer_t { buffer_t { id, generation: 1 } }
+/// #   pub unsafe extern "C" fn buffer_destroy(_: buffer_t) {}
+/// #   pub unsafe fn buffer_copy(buf: buffer_t) -> buffer_t { buffer_t { id: buf.id + 1, generation: 1 } }
+/// #   pub unsafe fn buffer_id(buf: buffer_t) -> u32 { buf.id }
+/// }
+///
+/// foreign_handle! {
+///     type CType = gpu_sys::buffer_t;
+///     fn drop = gpu_sys::buffer_destroy;
+///     fn clone = gpu_sys::buffer_copy;
+///     unsafe impl Send;
+///     /// A GPU buffer.
+///     pub struct Buffer;
+///     /// A borrowed GPU buffer.
+///     pub struct BufferRef;
+/// }
+///
+/// impl BufferRef {
+///     pub fn id(&self) -> u32 {
+///         unsafe { gpu_sys::buffer_id(self.as_handle()) }
+///     }
+/// }
+///
+/// # fn main() {
+/// let buf = unsafe { Buffer::from_handle(gpu_sys::buffer_create(7)) };
+/// assert_eq!(buf.id(), 7);
+/// assert_eq!(buf.clone().id(), 8);
+///
+/// # if cfg!(not(target_arch = "wasm32")) {
+/// let id = std::thread::spawn(move || buf.id()).join().unwrap();
+/// assert_eq!(id, 7);
+/// # }
+/// # }
+/// ```
+///
+/// A handle with a sentinel value:
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use foreign_types::ForeignHandle;
+/// use std::os::raw::c_int;
+///
+/// # mod fd_sys {
+/// #     use std::os::raw::c_int;
+/// #     pub unsafe fn fd_open(ok: bool) -> c_int { if ok { 3 } else { -1 } }
+/// #     pub unsafe extern "C" fn fd_close(_: c_int) {}
+/// # }
+/// foreign_handle! {
+///     type CType = c_int;
+///     const INVALID: Self::CType = -1;
+///     fn drop = fd_sys::fd_close;
+///     /// A file descriptor.
+///     pub struct Fd;
+///     /// A borrowed file descriptor.
+///     pub struct FdRef;
+/// }
+///
+/// # fn main() {
+/// assert!(unsafe { Fd::from_handle_opt(fd_sys::fd_open(true)) }.is_some());
+/// assert!(unsafe { Fd::from_handle_opt(fd_sys::fd_open(false)) }.is_none());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! foreign_handle {
+    (
+        $(#[$impl_attr:meta])*
+        type CType = $ctype:ty;
+        $(const INVALID: Self::CType = $invalid:expr;)*
+        $(extern $abi:tt)* fn drop = $drop:expr;
+        $(fn clone = $clone:expr;)*
+        $(unsafe impl $marker:ident;)*
+        $(#[$owned_attr:meta])*
+        pub struct $owned:ident;
+        $(#[$borrowed_attr:meta])*
+        pub struct $borrowed:ident;
+    ) => {
+        $crate::foreign_handle! { @drop_check [$($abi)*] $ctype, $drop }
+
+        $(#[$owned_attr])*
+        #[must_use]
+        pub struct $owned($ctype, ::std::marker::PhantomData<$crate::Opaque>);
+
+        $(#[$impl_attr])*
+        impl $crate::ForeignHandle for $owned {
+            type CType = $ctype;
+            type Ref = $borrowed;
+
+            $(const INVALID: Option<$ctype> = Some($invalid);)*
+
+            #[inline]
+            unsafe fn from_handle(handle: $ctype) -> $owned {
+                $owned(handle, ::std::marker::PhantomData)
+            }
+
+            #[inline]
+            fn as_handle(&self) -> $ctype {
+                self.0
+            }
+        }
+
+        impl Drop for $owned {
+            #[inline]
+            fn drop(&mut self) {
+                unsafe { $drop(self.0) };
+            }
+        }
+
+        $(
+            impl Clone for $owned {
+                #[inline]
+                fn clone(&self) -> $owned {
+                    unsafe { $owned($clone(self.0), ::std::marker::PhantomData) }
+                }
+            }
+
+            impl ::std::borrow::ToOwned for $borrowed {
+                type Owned = $owned;
+
+                #[inline]
+                fn to_owned(&self) -> $owned {
+                    unsafe { $owned($clone(self.0), ::std::marker::PhantomData) }
+                }
+            }
+        )*
+
+        impl ::std::ops::Deref for $owned {
+            type Target = $borrowed;
+
+            #[inline]
+            fn deref(&self) -> &$borrowed {
+                unsafe { $crate::ForeignHandleRef::from_handle(&self.0) }
+            }
+        }
+
+        impl ::std::ops::DerefMut for $owned {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut $borrowed {
+                unsafe { $crate::ForeignHandleRef::from_handle_mut(&mut self.0) }
+            }
+        }
+
+        impl ::std::borrow::Borrow<$borrowed> for $owned {
+            #[inline]
+            fn borrow(&self) -> &$borrowed {
+                &**self
+            }
+        }
+
+        impl ::std::convert::AsRef<$borrowed> for $owned {
+            #[inline]
+            fn as_ref(&self) -> &$borrowed {
+                &**self
+            }
+        }
+
+        $(#[$borrowed_attr])*
+        #[repr(transparent)]
+        pub struct $borrowed($ctype, ::std::marker::PhantomData<$crate::Opaque>);
+
+        $(#[$impl_attr])*
+        unsafe impl $crate::ForeignHandleRef for $borrowed {
+            type CType = $ctype;
+        }
+
+        $(
+            $crate::foreign_handle! { @marker $marker $owned, $borrowed }
+        )*
+    };
+    (@marker Send $owned:ident, $borrowed:ident) => {
+        unsafe impl Send for $owned {}
+        unsafe impl Send for $borrowed {}
+    };
+    (@marker Sync $owned:ident, $borrowed:ident) => {
+        unsafe impl Sync for $owned {}
+        unsafe impl Sync for $borrowed {}
+    };
+    (@drop_check [] $ctype:ty, $drop:expr) => {
+        $crate::foreign_handle! { @drop_check ["C"] $ctype, $drop }
+    };
+    (@drop_check [$abi:tt] $ctype:ty, $drop:expr) => {
+        const _: () = {
+            fn check<R>(_: unsafe extern $abi fn($ctype) -> R) {}
+
+            #[allow(dead_code)]
+            fn check_all() {
+                check($drop);
+            }
+        };
+    };
+}
+
+/// A macro to define tokens which cancel a registration when dropped.
+///
+/// Many C APIs return an ID from a function such as `X_subscribe`, which must later be passed to
+/// `X_unsubscribe(obj, id)`. The generated token type borrows the parent object, so that it cannot
+/// outlive it, and passes the parent's pointer and the ID to the `unsubscribe` function when it is
+/// dropped.
+///
+/// `cancel` drops the token explicitly, and `forget` consumes it without unsubscribing, returning
+/// the ID.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use std::os::raw::c_int;
+///
+/// # mod bus_sys {
+/// #     use std::os::raw::c_int;
+/// #     pub struct BUS { pub subscribers: Vec<c_int> }
+/// #     pub unsafe extern "C" fn BUS_free(bus: *mut BUS) { drop(Box::from_raw(bus)) }
+/// #     pub unsafe fn BUS_subscribe(bus: *mut BUS) -> c_int {
+/// #         let id = (*bus).subscribers.len() as c_int;
+/// #         (*bus).subscribers.push(id);
+/// #         id
+/// #     }
+/// #     pub unsafe fn BUS_unsubscribe(bus: *mut BUS, id: c_int) { (*bus).subscribers.retain(|&s| s != id) }
+/// #     pub unsafe fn BUS_subscribers(bus: *mut BUS) -> usize { (*bus).subscribers.len() }
+/// # }
+/// foreign_type! {
+///     type CType = bus_sys::BUS;
+///     fn drop = bus_sys::BUS_free;
+///     getters {
+///         fn subscribers -> usize = bus_sys::BUS_subscribers;
+///     }
+///     /// A message bus.
+///     pub struct Bus;
+///     /// A borrowed message bus.
+///     pub struct BusRef;
+/// }
+///
+/// foreign_token! {
+///     type Parent = BusRef;
+///     type Id = c_int;
+///     fn unsubscribe = bus_sys::BUS_unsubscribe;
+///     /// A subscription to a message bus, which is cancelled when dropped.
+///     pub struct Subscription;
+/// }
+///
+/// impl BusRef {
+///     pub fn subscribe(&self) -> Subscription<'_> {
+///         unsafe {
+///             let id = bus_sys::BUS_subscribe(foreign_types::ForeignTypeRef::as_ptr(self));
+///             Subscription::new(self, id)
+///         }
+///     }
+/// }
+///
+/// # fn main() {
+/// # let bus: Bus = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(bus_sys::BUS { subscribers: vec![] }))) };
+/// let subscription = bus.subscribe();
+/// assert_eq!(bus.subscribers(), 1);
+/// drop(subscription);
+/// assert_eq!(bus.subscribers(), 0);
+///
+/// let id = bus.subscribe().forget();
+/// assert_eq!(bus.subscribers(), 1);
+/// # let _ = id;
+/// # }
+/// ```
+#[macro_export]
+macro_rules! foreign_token {
+    (
+        type Parent = $parent:ty;
+        type Id = $id:ty;
+        fn unsubscribe = $unsubscribe:expr;
+        $(#[$attr:meta])*
+        $vis:vis struct $token:ident;
+    ) => {
+        $(#[$attr])*
+        #[must_use]
+        $vis struct $token<'a> {
+            parent: &'a $parent,
+            id: $id,
+        }
+
+        impl<'a> $token<'a> {
+            /// Takes ownership of the registration with the given ID.
+            ///
+            /// # Safety
+            ///
+            /// `id` must identify a registration with `parent` which is owned by the caller.
+            #[inline]
+            pub unsafe fn new(parent: &'a $parent, id: $id) -> $token<'a> {
+                $token { parent, id }
+            }
+
+            /// Returns the ID of the registration.
+            #[inline]
+            pub fn id(&self) -> $id {
+                self.id
+            }
+
+            /// Cancels the registration.
+            #[inline]
+            pub fn cancel(self) {}
+
+            /// Consumes the token without cancelling the registration, returning its ID.
+            #[inline]
+            pub fn forget(self) -> $id {
+                let id = self.id;
+                ::std::mem::forget(self);
+                id
+            }
+        }
+
+        impl<'a> Drop for $token<'a> {
+            #[inline]
+            fn drop(&mut self) {
+                unsafe { $unsubscribe($crate::ForeignTypeRef::as_ptr(self.parent), self.id) };
+            }
+        }
+
+        impl<'a> ::std::fmt::Debug for $token<'a> {
+            fn fmt(&self, fmt: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                fmt.debug_struct(stringify!($token))
+                    .field("parent", &$crate::ForeignTypeRef::as_ptr(self.parent))
+                    .finish()
+            }
+        }
+    };
+}
+
+/// A macro to define wrappers for C objects with a run-time number of trailing elements.
+///
+/// Some C objects are a header followed by a number of elements which is only known at run time,
+/// such as a struct ending in a flexible array member. The borrowed type generated by this macro is
+/// a dynamically sized type, so a reference to it carries the number of elements alongside the
+/// pointer to the object, and it dereferences to a slice of the elements without querying the C
+/// library again.
+///
+/// `fn len = ...;` names a function returning the number of elements of an object, and
+/// `fn data = ...;` a function returning a pointer to the first element. Both take a pointer to
+/// the object. As with `foreign_type!`, the `drop` function is checked at compile time to be an
+/// `extern "C"` function taking a pointer to the object, unless it is declared with another ABI, as
+/// in `extern "Rust" fn drop = ...;`.
+///
+/// ```compile_fail,E0308
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod list_sys {
+/// #     pub struct LIST;
+/// #     pub unsafe fn LIST_len(_: *mut LIST) -> usize { 0 }
+/// #     pub unsafe fn LIST_items(_: *mut LIST) -> *mut u32 { 0 as *mut u32 }
+/// # }
+/// foreign_dst! {
+///     type CType = list_sys::LIST;
+///     type Elem = u32;
+///     fn drop = std::mem::drop;
+///     fn len = list_sys::LIST_len;
+///     fn data = list_sys::LIST_items;
+///     /// A list of integers.
+///     pub struct List;
+///     /// A borrowed list of integers.
+///     pub struct ListRef;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// Since the borrowed type is unsized, it cannot implement `ForeignTypeRef`, and neither type
+/// implements `ForeignType`. Both provide inherent methods with the same names instead:
+///
+/// * The owned type has `from_ptr`, `as_ptr` and `into_ptr`, and dereferences to the borrowed type.
+///   The number of elements is queried each time it is dereferenced.
+/// * The borrowed type has `from_ptr` and `from_ptr_mut`, which query the number of elements,
+///   `from_raw_parts` and `from_raw_parts_mut`, which take it as an argument, and `as_ptr`.
+///
+/// Neither type is `Send` or `Sync`, but both can be marked as such with `unsafe impl` blocks.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod list_sys {
+/// #     pub struct LIST { pub items: Vec<u32> }
+/// #     pub unsafe fn LIST_new(n: u32) -> *mut LIST { Box::into_raw(Box::new(LIST { items: (0..n).collect() })) }
+/// #     pub unsafe extern "C" fn LIST_free(list: *mut LIST) { drop(Box::from_raw(list)) }
+/// #     pub unsafe fn LIST_len(list: *mut LIST) -> usize { (*list).items.len() }
+/// #     pub unsafe fn LIST_items(list: *mut LIST) -> *mut u32 { (*list).items.as_mut_ptr() }
+/// # }
+/// foreign_dst! {
+///     type CType = list_sys::LIST;
+///     type Elem = u32;
+///     fn drop = list_sys::LIST_free;
+///     fn len = list_sys::LIST_len;
+///     fn data = list_sys::LIST_items;
+///     /// A list of integers.
+///     pub struct List;
+///     /// A borrowed list of integers.
+///     pub struct ListRef;
+/// }
+///
+/// fn sum(list: &ListRef) -> u32 {
+///     list.iter().sum()
+/// }
+///
+/// # fn main() {
+/// let mut list = unsafe { List::from_ptr(list_sys::LIST_new(4)) };
+/// assert_eq!(list.len(), 4);
+/// assert_eq!(sum(&list), 6);
+///
+/// list[0] = 10;
+/// assert_eq!(sum(&list), 16);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! foreign_dst {
+    (
+        type CType = $ctype:ty;
+        type Elem = $elem:ty;
+        $(extern $abi:tt)* fn drop = $drop:expr;
+        fn len = $len:expr;
+        fn data = $data:expr;
+        $(#[$owned_attr:meta])*
+        pub struct $owned:ident;
+        $(#[$borrowed_attr:meta])*
+        pub struct $borrowed:ident;
+    ) => {
+        $crate::foreign_dst! { @drop_check [$($abi)*] $ctype, $drop }
+
+        $(#[$owned_attr])*
+        #[must_use]
+        pub struct $owned(*mut $ctype);
+
+        impl $owned {
+            /// Takes ownership of the object at `ptr`.
+            ///
+            /// # Safety
+            ///
+            /// `ptr` must be a valid, owned instance of the C type.
+            #[inline]
+            pub unsafe fn from_ptr(ptr: *mut $ctype) -> $owned {
+                $owned(ptr)
+            }
+
+            /// Returns a raw pointer to the object.
+            #[inline]
+            pub fn as_ptr(&self) -> *mut $ctype {
+                self.0
+            }
+
+            /// Consumes the wrapper, returning the pointer to the object, which must be freed by the
+            /// caller.
+            #[inline]
+            pub fn into_ptr(self) -> *mut $ctype {
+                let ptr = self.0;
+                ::std::mem::forget(self);
+                ptr
+            }
+        }
+
+        impl Drop for $owned {
+            #[inline]
+            fn drop(&mut self) {
+                unsafe { $drop(self.0) };
+            }
+        }
+
+        impl ::std::ops::Deref for $owned {
+            type Target = $borrowed;
+
+            #[inline]
+            fn deref(&self) -> &$borrowed {
+                unsafe { $borrowed::from_ptr(self.0) }
+            }
+        }
+
+        impl ::std::ops::DerefMut for $owned {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut $borrowed {
+                unsafe { $borrowed::from_ptr_mut(self.0) }
+            }
+        }
+
+        impl ::std::borrow::Borrow<$borrowed> for $owned {
+            #[inline]
+            fn borrow(&self) -> &$borrowed {
+                &**self
+            }
+        }
+
+        impl ::std::convert::AsRef<$borrowed> for $owned {
+            #[inline]
+            fn as_ref(&self) -> &$borrowed {
+                &**self
+            }
+        }
+
+        $(#[$borrowed_attr])*
+        #[repr(transparent)]
+        pub struct $borrowed([$crate::Opaque]);
+
+        impl $borrowed {
+            /// Constructs a shared reference to the object at `ptr`, querying its number of elements.
+            ///
+            /// # Safety
+            ///
+            /// `ptr` must be a valid instance of the C type, which is not mutated for the lifetime
+            /// `'a`.
+            #[inline]
+            pub unsafe fn from_ptr<'a>(ptr: *mut $ctype) -> &'a $borrowed {
+                $borrowed::from_raw_parts(ptr, $len(ptr))
+            }
+
+            /// Constructs a mutable reference to the object at `ptr`, querying its number of elements.
+            ///
+            /// # Safety
+            ///
+            /// `ptr` must be a valid instance of the C type, which is not otherwise accessed for the
+            /// lifetime `'a`.
+            #[inline]
+            pub unsafe fn from_ptr_mut<'a>(ptr: *mut $ctype) -> &'a mut $borrowed {
+                $borrowed::from_raw_parts_mut(ptr, $len(ptr))
+            }
+
+            /// Constructs a shared reference to the object at `ptr`, which has `len` elements.
+            ///
+            /// # Safety
+            ///
+            /// In addition to the requirements of `from_ptr`, the object must have at least `len`
+            /// elements.
+            #[inline]
+            pub unsafe fn from_raw_parts<'a>(ptr: *mut $ctype, len: usize) -> &'a $borrowed {
+                &*(::std::ptr::slice_from_raw_parts(ptr as *const $crate::Opaque, len) as *const $borrowed)
+            }
+
+            /// Constructs a mutable reference to the object at `ptr`, which has `len` elements.
+            ///
+            /// # Safety
+            ///
+            /// In addition to the requirements of `from_ptr_mut`, the object must have at least
+            /// `len` elements.
+            #[inline]
+            pub unsafe fn from_raw_parts_mut<'a>(ptr: *mut $ctype, len: usize) -> &'a mut $borrowed {
+                &mut *(::std::ptr::slice_from_raw_parts_mut(ptr as *mut $crate::Opaque, len) as *mut $borrowed)
+            }
+
+            /// Returns a raw pointer to the object.
+            #[inline]
+            pub fn as_ptr(&self) -> *mut $ctype {
+                self.0.as_ptr() as *mut $ctype
+            }
+        }
+
+        impl ::std::ops::Deref for $borrowed {
+            type Target = [$elem];
+
+            #[inline]
+            fn deref(&self) -> &[$elem] {
+                match self.0.len() {
+                    0 => &[],
+                    len => unsafe { ::std::slice::from_raw_parts($data(self.as_ptr()), len) },
+                }
+            }
+        }
+
+        impl ::std::ops::DerefMut for $borrowed {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut [$elem] {
+                match self.0.len() {
+                    0 => &mut [],
+                    len => unsafe { ::std::slice::from_raw_parts_mut($data(self.as_ptr()), len) },
+                }
+            }
+        }
+
+        impl ::std::convert::AsRef<[$elem]> for $borrowed {
+            #[inline]
+            fn as_ref(&self) -> &[$elem] {
+                self
+            }
+        }
+
+        impl ::std::convert::AsMut<[$elem]> for $borrowed {
+            #[inline]
+            fn as_mut(&mut self) -> &mut [$elem] {
+                self
+            }
+        }
+    };
+    (@drop_check [] $ctype:ty, $drop:expr) => {
+        $crate::foreign_dst! { @drop_check ["C"] $ctype, $drop }
+    };
+    (@drop_check [$abi:tt] $ctype:ty, $drop:expr) => {
+        const _: () = {
+            fn check<P, R>(_: unsafe extern $abi fn(P) -> R)
+            where
+                P: $crate::__private::DropArg<$ctype>,
+            {
+            }
+
+            #[allow(dead_code)]
+            fn check_all() {
+                check($drop);
+            }
+        };
+    };
+}
+
+/// A macro to define Rust enums for C integer constants.
+///
+/// The generated enum has one variant for each constant, and an `into_raw` method returning the
+/// constant for a variant. Raw values are converted into the enum with `TryFrom`, which fails with
+/// an `UnknownValue` error for values that do not match any constant.
+///
+/// A final `_ => Variant` entry adds a variant holding any unrecognized raw value. The enum then
+/// implements `From` rather than `TryFrom` for the raw type, which is useful for C APIs that may add
+/// new constants in later versions.
+///
+/// The enum derives `Debug`, `Clone`, `Copy`, `PartialEq`, `Eq`, and `Hash`. With a `_` entry,
+/// `PartialEq`, `Eq`, and `Hash` are instead implemented on the raw value, so that an unknown
+/// variant holding the value of a named variant is equal to it.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use std::convert::TryFrom;
+/// use std::os::raw::c_int;
+///
+/// # mod ssl_sys {
+/// #     use std::os::raw::c_int;
+/// #     pub const SSL_ERROR_NONE: c_int = 0;
+/// #     pub const SSL_ERROR_SSL: c_int = 1;
+/// #     pub const SSL_ERROR_WANT_READ: c_int = 2;
+/// # }
+/// foreign_enum! {
+///     type CType = c_int;
+///     /// The result of an SSL operation.
+///     pub enum ErrorCode {
+///         /// The operation succeeded.
+///         None = ssl_sys::SSL_ERROR_NONE,
+///         /// The operation failed.
+///         Ssl = ssl_sys::SSL_ERROR_SSL,
+///     }
+/// }
+///
+/// foreign_enum! {
+///     type CType = c_int;
+///     /// The result of an SSL operation, including codes added by newer library versions.
+///     pub enum ExtendedErrorCode {
+///         /// The operation succeeded.
+///         None = ssl_sys::SSL_ERROR_NONE,
+///         /// The operation failed.
+///         Ssl = ssl_sys::SSL_ERROR_SSL,
+///         _ => Unknown,
+///     }
+/// }
+///
+/// # fn main() {
+/// assert_eq!(ErrorCode::try_from(1), Ok(ErrorCode::Ssl));
+/// assert_eq!(ErrorCode::try_from(2).unwrap_err().value(), 2);
+/// assert_eq!(ErrorCode::None.into_raw(), ssl_sys::SSL_ERROR_NONE);
+///
+/// assert_eq!(ExtendedErrorCode::from(2), ExtendedErrorCode::Unknown(2));
+/// assert_eq!(ExtendedErrorCode::Unknown(2).into_raw(), ssl_sys::SSL_ERROR_WANT_READ);
+/// assert_eq!(ExtendedErrorCode::Unknown(1), ExtendedErrorCode::Ssl);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! foreign_enum {
+    (
+        type CType = $ctype:ty;
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$variant_attr:meta])* $variant:ident = $value:expr,)+
+            _ => $unknown:ident $(,)*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy)]
+        $vis enum $name {
+            $($(#[$variant_attr])* $variant,)+
+            /// A value not recognized by these bindings.
+            $unknown($ctype),
+        }
+
+        // Values are compared by their raw values, so that an unknown variant holding the value of
+        // a named variant is equal to it.
+        impl ::std::cmp::PartialEq for $name {
+            #[inline]
+            fn eq(&self, other: &$name) -> bool {
+                self.into_raw() == other.into_raw()
+            }
+        }
+
+        impl ::std::cmp::Eq for $name {}
+
+        impl ::std::hash::Hash for $name {
+            #[inline]
+            fn hash<H>(&self, state: &mut H)
+            where
+                H: ::std::hash::Hasher,
+            {
+                ::std::hash::Hash::hash(&self.into_raw(), state)
+            }
+        }
+
+        impl $name {
+            /// Returns the raw C value of this variant.
+            #[inline]
+            pub fn into_raw(self) -> $ctype {
+                match self {
+                    $($name::$variant => $value,)+
+                    $name::$unknown(raw) => raw,
+                }
+            }
+        }
+
+        impl ::std::convert::From<$ctype> for $name {
+            #[inline]
+            fn from(raw: $ctype) -> $name {
+                $(
+                    if raw == $value {
+                        return $name::$variant;
+                    }
+                )+
+                $name::$unknown(raw)
+            }
+        }
+
+        $crate::foreign_enum! { @into_raw $ctype, $name }
+    };
+    (
+        type CType = $ctype:ty;
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$variant_attr:meta])* $variant:ident = $value:expr),+ $(,)*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        $vis enum $name {
+            $($(#[$variant_attr])* $variant,)+
+        }
+
+        impl $name {
+            /// Returns the raw C value of this variant.
+            #[inline]
+            pub fn into_raw(self) -> $ctype {
+                match self {
+                    $($name::$variant => $value,)+
+                }
+            }
+        }
+
+        impl ::std::convert::TryFrom<$ctype> for $name {
+            type Error = $crate::UnknownValue<$ctype>;
+
+            #[inline]
+            fn try_from(raw: $ctype) -> Result<$name, $crate::UnknownValue<$ctype>> {
+                $(
+                    if raw == $value {
+                        return Ok($name::$variant);
+                    }
+                )+
+                Err($crate::UnknownValue::__new(raw))
+            }
+        }
+
+        $crate::foreign_enum! { @into_raw $ctype, $name }
+    };
+    (@into_raw $ctype:ty, $name:ident) => {
+        impl ::std::convert::From<$name> for $ctype {
+            #[inline]
+            fn from(value: $name) -> $ctype {
+                value.into_raw()
+            }
+        }
+    };
+}
+
+/// A macro to define Rust types for C bitmask constants.
+///
+/// The generated type is a transparent wrapper around the raw C integer type, with an associated
+/// constant for each flag. It supports the usual set operations through the bitwise operators and
+/// methods such as `contains` and `insert`, and `iter` returns the named flags which are set.
+///
+/// Raw values are converted with `from_raw`, which fails if any bits do not correspond to a named
+/// flag, or with `from_raw_truncate`, which discards them. `into_raw` returns the raw value.
+///
+/// The type derives `Clone`, `Copy`, `PartialEq`, `Eq`, `Hash`, and `Default`, and implements
+/// `Debug` by listing the names of the flags which are set.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use std::os::raw::c_uint;
+///
+/// # mod file_sys {
+/// #     use std::os::raw::c_uint;
+/// #     pub const FILE_READ: c_uint = 0x1;
+/// #     pub const FILE_WRITE: c_uint = 0x2;
+/// #     pub const FILE_APPEND: c_uint = 0x4;
+/// # }
+/// foreign_flags! {
+///     type CType = c_uint;
+///     /// Flags controlling how a file is opened.
+///     pub struct OpenFlags {
+///         /// Open the file for reading.
+///         const READ = file_sys::FILE_READ;
+///         /// Open the file for writing.
+///         const WRITE = file_sys::FILE_WRITE;
+///         /// Append to the end of the file.
+///         const APPEND = file_sys::FILE_APPEND;
+///     }
+/// }
+///
+/// # fn main() {
+/// let mut flags = OpenFlags::READ | OpenFlags::WRITE;
+/// assert!(flags.contains(OpenFlags::READ));
+/// assert!(!flags.contains(OpenFlags::APPEND));
+/// assert_eq!(flags.into_raw(), 0x3);
+///
+/// flags.remove(OpenFlags::READ);
+/// assert_eq!(flags.iter().collect::<Vec<_>>(), [OpenFlags::WRITE]);
+/// assert_eq!(format!("{:?}", flags), "OpenFlags(WRITE)");
+///
+/// assert_eq!(OpenFlags::from_raw(0x5), Some(OpenFlags::READ | OpenFlags::APPEND));
+/// assert_eq!(OpenFlags::from_raw(0x8), None);
+/// assert_eq!(OpenFlags::from_raw_truncate(0x9), OpenFlags::READ);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! foreign_flags {
+    (
+        type CType = $ctype:ty;
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$flag_attr:meta])* const $flag:ident = $value:expr;)+
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+        #[repr(transparent)]
+        $vis struct $name($ctype);
+
+        impl $name {
+            $(
+                $(#[$flag_attr])*
+                pub const $flag: $name = $name($value);
+            )+
+
+            const __FLAGS: &'static [(&'static str, $name)] = &[$((stringify!($flag), $name::$flag)),+];
+
+            /// Returns a value with no flags set.
+            #[inline]
+            pub const fn empty() -> $name {
+                $name(0)
+            }
+
+            /// Returns a value with every named flag set.
+            #[inline]
+            pub const fn all() -> $name {
+                $name(0 $(| $value)+)
+            }
+
+            /// Converts a raw value, returning `None` if it contains bits which do not correspond
+            /// to a named flag.
+            #[inline]
+            pub fn from_raw(raw: $ctype) -> Option<$name> {
+                if raw & !$name::all().0 == 0 {
+                    Some($name(raw))
+                } else {
+                    None
+                }
+            }
+
+            /// Converts a raw value, discarding any bits which do not correspond to a named flag.
+            #[inline]
+            pub const fn from_raw_truncate(raw: $ctype) -> $name {
+                $name(raw & $name::all().0)
+            }
+
+            /// Returns the raw value.
+            #[inline]
+            pub const fn into_raw(self) -> $ctype {
+                self.0
+            }
+
+            /// Returns `true` if no flags are set.
+            #[inline]
+            pub const fn is_empty(self) -> bool {
+                self.0 == 0
+            }
+
+            /// Returns `true` if every flag in `other` is set.
+            #[inline]
+            pub const fn contains(self, other: $name) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Returns `true` if any flag in `other` is set.
+            #[inline]
+            pub const fn intersects(self, other: $name) -> bool {
+                self.0 & other.0 != 0
+            }
+
+            /// Sets the flags in `other`.
+            #[inline]
+            pub fn insert(&mut self, other: $name) {
+                self.0 |= other.0;
+            }
+
+            /// Clears the flags in `other`.
+            #[inline]
+            pub fn remove(&mut self, other: $name) {
+                self.0 &= !other.0;
+            }
+
+            /// Toggles the flags in `other`.
+            #[inline]
+            pub fn toggle(&mut self, other: $name) {
+                self.0 ^= other.0;
+            }
+
+            /// Sets or clears the flags in `other` depending on `value`.
+            #[inline]
+            pub fn set(&mut self, other: $name, value: bool) {
+                if value {
+                    self.insert(other);
+                } else {
+                    self.remove(other);
+                }
+            }
+
+            /// Returns an iterator over the named flags which are set.
+            #[inline]
+            pub fn iter(self) -> impl Iterator<Item = $name> {
+                $name::__FLAGS
+                    .iter()
+                    .map(|&(_, flag)| flag)
+                    .filter(move |&flag| flag.0 != 0 && self.contains(flag))
+            }
+        }
+
+        impl ::std::fmt::Debug for $name {
+            fn fmt(&self, fmt: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                fmt.write_str(stringify!($name))?;
+                fmt.write_str("(")?;
+                let mut first = true;
+                let mut rest = self.0;
+                for &(name, flag) in $name::__FLAGS {
+                    if flag.0 != 0 && self.contains(flag) {
+                        if !first {
+                            fmt.write_str(" | ")?;
+                        }
+                        first = false;
+                        fmt.write_str(name)?;
+                        rest &= !flag.0;
+                    }
+                }
+                if rest != 0 {
+                    if !first {
+                        fmt.write_str(" | ")?;
+                    }
+                    first = false;
+                    write!(fmt, "{:#x}", rest)?;
+                }
+                if first {
+                    fmt.write_str("empty")?;
+                }
+                fmt.write_str(")")
+            }
+        }
+
+        impl ::std::ops::BitOr for $name {
+            type Output = $name;
+
+            #[inline]
+            fn bitor(self, other: $name) -> $name {
+                $name(self.0 | other.0)
+            }
+        }
+
+        impl ::std::ops::BitOrAssign for $name {
+            #[inline]
+            fn bitor_assign(&mut self, other: $name) {
+                self.0 |= other.0;
+            }
+        }
+
+        impl ::std::ops::BitAnd for $name {
+            type Output = $name;
+
+            #[inline]
+            fn bitand(self, other: $name) -> $name {
+                $name(self.0 & other.0)
+            }
+        }
+
+        impl ::std::ops::BitAndAssign for $name {
+            #[inline]
+            fn bitand_assign(&mut self, other: $name) {
+                self.0 &= other.0;
+            }
+        }
+
+        impl ::std::ops::BitXor for $name {
+            type Output = $name;
+
+            #[inline]
+            fn bitxor(self, other: $name) -> $name {
+                $name(self.0 ^ other.0)
+            }
+        }
+
+        impl ::std::ops::BitXorAssign for $name {
+            #[inline]
+            fn bitxor_assign(&mut self, other: $name) {
+                self.0 ^= other.0;
+            }
+        }
+
+        impl ::std::ops::Sub for $name {
+            type Output = $name;
+
+            #[inline]
+            fn sub(self, other: $name) -> $name {
+                $name(self.0 & !other.0)
+            }
+        }
+
+        impl ::std::ops::SubAssign for $name {
+            #[inline]
+            fn sub_assign(&mut self, other: $name) {
+                self.0 &= !other.0;
+            }
+        }
+
+        impl ::std::ops::Not for $name {
+            type Output = $name;
+
+            #[inline]
+            fn not(self) -> $name {
+                $name(!self.0 & $name::all().0)
+            }
+        }
+
+        impl ::std::iter::FromIterator<$name> for $name {
+            fn from_iter<I>(iter: I) -> $name
+            where
+                I: ::std::iter::IntoIterator<Item = $name>,
+            {
+                iter.into_iter().fold($name::empty(), |a, b| a | b)
+            }
+        }
+
+        impl ::std::convert::From<$name> for $ctype {
+            #[inline]
+            fn from(flags: $name) -> $ctype {
+                flags.0
+            }
+        }
+    };
+}
+
+/// Asserts at compile time that types implement `Send`.
+///
+/// Thread safety of foreign types is declared with `unsafe impl Send;` and `unsafe impl Sync;`
+/// entries rather than inferred, so it is easy for a refactor to change it by accident. The
+/// `assert_foreign_send!`, `assert_foreign_sync!`, `assert_foreign_not_send!`, and
+/// `assert_foreign_not_sync!` macros lock in those decisions, failing to compile if a type does not
+/// have the expected implementation. They have no runtime cost.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod openssl_sys { pub type SSL = (); pub unsafe extern "C" fn SSL_free(_: *mut SSL) {} }
+/// foreign_type! {
+///     type CType = openssl_sys::SSL;
+///     fn drop = openssl_sys::SSL_free;
+///     unsafe impl Send;
+///     /// Documentation for the owned type.
+///     pub struct Ssl;
+///     /// Documentation for the borrowed type.
+///     pub struct SslRef;
+/// }
+///
+/// assert_foreign_send!(Ssl, SslRef);
+/// assert_foreign_not_sync!(Ssl, SslRef);
+///
+/// # fn main() {}
+/// ```
+///
+/// A type which is not `Send` fails to compile:
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// # mod openssl_sys { pub type SSL = (); pub unsafe extern "C" fn SSL_free(_: *mut SSL) {} }
+/// foreign_type! {
+///     type CType = openssl_sys::SSL;
+///     fn drop = openssl_sys::SSL_free;
+///     /// Documentation for the owned type.
+///     pub struct Ssl;
+///     /// Documentation for the borrowed type.
+///     pub struct SslRef;
+/// }
+///
+/// assert_foreign_send!(Ssl);
+///
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! assert_foreign_send {
+    ($($t:ty),+ $(,)*) => {
+        $(
+            const _: fn() = || {
+                fn assert<T: ?Sized + ::std::marker::Send>() {}
+                assert::<$t>();
+            };
+        )+
+    };
+}
+
+/// Asserts at compile time that types implement `Sync`.
+///
+/// See `assert_foreign_send!` for details.
+#[macro_export]
+macro_rules! assert_foreign_sync {
+    ($($t:ty),+ $(,)*) => {
+        $(
+            const _: fn() = || {
+                fn assert<T: ?Sized + ::std::marker::Sync>() {}
+                assert::<$t>();
+            };
+        )+
+    };
+}
+
+/// Asserts at compile time that types do not implement `Send`.
+///
+/// See `assert_foreign_send!` for details.
+#[macro_export]
+macro_rules! assert_foreign_not_send {
+    ($($t:ty),+ $(,)*) => {
+        $(
+            $crate::__foreign_type_assert_not! { $t, ::std::marker::Send }
+        )+
+    };
+}
+
+/// Asserts at compile time that types do not implement `Sync`.
+///
+/// See `assert_foreign_send!` for details.
+#[macro_export]
+macro_rules! assert_foreign_not_sync {
+    ($($t:ty),+ $(,)*) => {
+        $(
+            $crate::__foreign_type_assert_not! { $t, ::std::marker::Sync }
+        )+
+    };
+}
+
+// If `$t` implements `$trait`, both impls below apply, and the implementation to use is ambiguous.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __foreign_type_assert_not {
+    ($t:ty, $($trait:tt)+) => {
+        const _: fn() = || {
+            trait AmbiguousIfImpl<A> {
+                fn some_item() {}
+            }
+            impl<T: ?Sized> AmbiguousIfImpl<()> for T {}
+            struct Invalid;
+            impl<T: ?Sized + $($trait)+> AmbiguousIfImpl<Invalid> for T {}
+            let _ = <$t as AmbiguousIfImpl<_>>::some_item;
+        };
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __foreign_type_impl {
+    // The options following `type CType` are collected into a list of tagged entries, which the
+    // generation rules below then search for the entries they care about.
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn drop = none; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (drop_none)]
+            $($t)*
+        }
+    };
+    (
+        @parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*]
+        $(#[$drop_attr:meta])*
+        fn drop = $($drop:expr),+;
+        $($t:tt)*
+    ) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (drop [$(#[$drop_attr])*] [$($drop),+] ["C"])]
+            $($t)*
+        }
+    };
+    (
+        @parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*]
+        $(#[$drop_attr:meta])*
+        extern $abi:tt fn drop = $($drop:expr),+;
+        $($t:tt)*
+    ) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (drop [$(#[$drop_attr])*] [$($drop),+] [$abi])]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn clone = $clone:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (clone $clone)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn try_clone = $clone:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (try_clone $clone)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn reset = $reset:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (reset $reset)]
+            $($t)*
+        }
+    };
+    (
+        @parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*]
+        fn reset -> Result<(), $e:ty> = $reset:expr;
+        $($t:tt)*
+    ) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (try_reset $e, $reset)]
+            $($t)*
+        }
+    };
+    (
+        @parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*]
+        fn destroy -> Result<(), $e:ty> = $destroy:expr;
+        $($t:tt)*
+    ) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (destroy $e, $destroy)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn as_raw_fd = $fd:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (as_raw_fd $fd)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn into_raw_fd = $fd:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (into_raw_fd $fd)]
+            $($t)*
+        }
+    };
+    (
+        @parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*]
+        fn error_source -> $e:ty = $capture:expr;
+        $($t:tt)*
+    ) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (error_source $e, $capture)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn validate = $validate:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (validate $validate)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn free_all = $free_all:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (free_all $free_all)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn refcount_get = $get:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (refcount_get $get)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn up_ref = $up_ref:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (up_ref $up_ref)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn sizeof = $sizeof:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (sizeof $sizeof)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn to_bytes = $to_bytes:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (to_bytes $to_bytes)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fn from_bytes = $from_bytes:expr; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (from_bytes $from_bytes)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] const INVALID: $t:ty = $e:expr; $($t2:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (invalid $t, $e)]
+            $($t2)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] const MUTABLE: bool = true; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)*]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] const MUTABLE: bool = false; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (immutable)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] const MUST_USE: bool = true; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)*]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] const MUST_USE: bool = false; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (no_must_use)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] unsafe impl Send; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (send)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] unsafe impl Sync; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (sync)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] unsafe impl ForeignTypeCompat; $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (compat)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] getters $getters:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (getters $getters)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] fields $fields:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (fields $fields)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] setters $setters:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (setters $setters)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] callbacks $callbacks:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (callbacks $callbacks)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] foreach $foreach:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (foreach $foreach)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] guards $guards:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (guards $guards)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] cursor $cursor:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (cursor $cursor)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] ptr $ptr:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (ptr $ptr)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] collect $collect:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (collect $collect)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] list_cursor $cursor:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (list_cursor $cursor)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] converts $converts:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (converts $converts)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] facets $facets:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (facets $facets)]
+            $($t)*
+        }
+    };
+    (@parse $impl_attrs:tt $ctype:ty; [$($opts:tt)*] builder $builder:tt $($t:tt)*) => {
+        $crate::__foreign_type_impl! {
+            @parse $impl_attrs $ctype; [$($opts)* (builder $builder)]
+            $($t)*
+        }
+    };
+    (
+        @parse [$($impl_attr:tt)*] $ctype:ty; $opts:tt
+        $(#[$owned_attr:meta])*
+        pub struct $owned:ident;
+        $(#[$borrowed_attr:meta])*
+        pub struct $borrowed:ident;
+    ) => {
+        $crate::__foreign_type_impl! { @owned $opts [$(#[$owned_attr])*] $owned $ctype }
+
+        $($impl_attr)*
+        impl $crate::ForeignType for $owned {
+            type CType = $ctype;
+            type Ref = $borrowed;
+
+            $crate::__foreign_type_impl! { @invalid $opts }
+
+            #[inline]
+            unsafe fn from_ptr(ptr: *mut $ctype) -> $owned {
+                $crate::__foreign_type_impl! { @on_create $opts $owned, ptr }
+                $owned(ptr)
+            }
+
+            #[inline]
+            fn as_ptr(&self) -> *mut $ctype {
+                self.0
+            }
+
+            #[inline]
+            fn into_ptr(self) -> *mut $ctype {
+                let ptr = self.0;
+                $crate::__foreign_type_impl! { @on_release $opts $owned, ptr }
+                $crate::__foreign_type_impl! { @callbacks_release $opts ptr }
+                ::std::mem::forget(self);
+                ptr
+            }
+        }
+
+        $crate::__foreign_type_impl! { @stats $opts $owned }
+        $crate::__foreign_type_shutdown! { @impl $owned }
+        $crate::__foreign_type_impl! { @drop $opts $opts $owned }
+        $crate::__foreign_type_impl! { @destroy $opts $opts $owned }
+        $crate::__foreign_type_impl! { @free_all $opts $ctype, $owned }
+        $crate::__foreign_type_impl! { @up_ref $opts $ctype, $owned }
+        $crate::__foreign_type_impl! { @refcount_get $opts $ctype, $owned }
+        $crate::__foreign_type_impl! { @reset $opts $opts $borrowed }
+        $crate::__foreign_type_impl! { @clone $opts $ctype, $owned, $borrowed }
+        $crate::__foreign_type_impl! { @try_clone $opts $opts $ctype, $owned, $borrowed }
+        $crate::__foreign_type_impl! { @error_source $opts $opts $ctype, $owned }
+        $crate::__foreign_type_impl! { @send $opts $owned, $borrowed }
+        $crate::__foreign_type_impl! { @sync $opts $owned, $borrowed }
+        $crate::__foreign_type_impl! { @compat $opts $owned, $borrowed }
+        $crate::__foreign_type_stable_deref! { $owned }
+        $crate::__foreign_type_impl! { @as_raw_fd $opts $owned, $borrowed }
+        $crate::__foreign_type_impl! { @into_raw_fd $opts $owned }
+        $crate::__foreign_type_impl! { @from_bytes $opts $opts $owned }
+        $crate::__foreign_type_impl! { @to_bytes $opts $opts $borrowed }
+        $crate::__foreign_type_impl! { @getters $opts $opts $borrowed }
+        $crate::__foreign_type_impl! { @setters $opts $opts $borrowed }
+        $crate::__foreign_type_impl! { @fields $opts $opts $borrowed }
+        $crate::__foreign_type_impl! { @foreach $opts $borrowed }
+        $crate::__foreign_type_impl! { @callbacks $opts $borrowed }
+        $crate::__foreign_type_impl! { @cursor $opts $borrowed }
+        $crate::__foreign_type_impl! { @list_cursor $opts $opts $borrowed }
+        $crate::__foreign_type_impl! { @collect $opts $opts $owned, $borrowed }
+        $crate::__foreign_type_impl! { @converts $opts $opts }
+        $crate::__foreign_type_impl! { @guards $opts $opts $borrowed }
+        $crate::__foreign_type_impl! { @builder $opts $owned }
+        $crate::__foreign_type_impl! { @ptr $opts $opts $ctype, $owned, $borrowed }
+        $crate::__foreign_type_impl! { @facets $opts $opts [$($impl_attr)*] $ctype, $borrowed }
+
+        impl ::std::ops::Deref for $owned {
+            type Target = $borrowed;
+
+            #[inline]
+            fn deref(&self) -> &$borrowed {
+                unsafe { $crate::ForeignTypeRef::from_ptr(self.0) }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @mutability $opts $ctype, $owned, $borrowed }
+
+        impl ::std::borrow::Borrow<$borrowed> for $owned {
+            #[inline]
+            fn borrow(&self) -> &$borrowed {
+                &**self
+            }
+        }
+
+        impl ::std::convert::AsRef<$borrowed> for $owned {
+            #[inline]
+            fn as_ref(&self) -> &$borrowed {
+                &**self
+            }
+        }
+
+        $($impl_attr)*
+        impl $crate::AsForeignPtr<$ctype> for $owned {
+            #[inline]
+            fn as_foreign_ptr(&self) -> *mut $ctype {
+                self.0
+            }
+        }
+
+        $(#[$borrowed_attr])*
+        pub struct $borrowed($crate::Opaque);
+
+        $($impl_attr)*
+        impl $crate::ForeignTypeRef for $borrowed {
+            type CType = $ctype;
+
+            $crate::__foreign_type_impl! { @invalid $opts }
+        }
+
+        $($impl_attr)*
+        impl $crate::AsForeignPtr<$ctype> for $borrowed {
+            #[inline]
+            fn as_foreign_ptr(&self) -> *mut $ctype {
+                $crate::ForeignTypeRef::as_ptr(self)
+            }
+        }
+    };
+
+    (@owned [(no_must_use) $($opts:tt)*] [$($attr:tt)*] $owned:ident $ctype:ty) => {
+        $($attr)*
+        pub struct $owned(*mut $ctype);
+    };
+    (@owned [$opt:tt $($opts:tt)*] $attrs:tt $owned:ident $ctype:ty) => {
+        $crate::__foreign_type_impl! { @owned [$($opts)*] $attrs $owned $ctype }
+    };
+    (@owned [] [$($attr:tt)*] $owned:ident $ctype:ty) => {
+        $($attr)*
+        #[must_use]
+        pub struct $owned(*mut $ctype);
+    };
+
+    (@invalid [(invalid $t:ty, $e:expr) $($opts:tt)*]) => {
+        const INVALID: $t = $e;
+    };
+    (@invalid [$opt:tt $($opts:tt)*]) => {
+        $crate::__foreign_type_impl! { @invalid [$($opts)*] }
+    };
+    (@invalid []) => {};
+
+    (@mutability [(immutable) $($opts:tt)*] $ctype:ty, $owned:ident, $borrowed:ident) => {
+        impl $borrowed {
+            /// Returns a raw pointer to the wrapped value, which must not be used for mutation.
+            #[inline]
+            pub fn as_const_ptr(&self) -> *const $ctype {
+                $crate::ForeignTypeRef::as_ptr(self)
+            }
+        }
+    };
+    (@mutability [$opt:tt $($opts:tt)*] $ctype:ty, $owned:ident, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @mutability [$($opts)*] $ctype, $owned, $borrowed }
+    };
+    (@mutability [] $ctype:ty, $owned:ident, $borrowed:ident) => {
+        impl ::std::ops::DerefMut for $owned {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut $borrowed {
+                unsafe { $crate::ForeignTypeRef::from_ptr_mut(self.0) }
+            }
+        }
+
+        unsafe impl $crate::ForeignTypeMut for $borrowed {}
+    };
+
+    (@if_mut [(immutable) $($opts:tt)*] $body:tt) => {};
+    (@if_mut [$opt:tt $($opts:tt)*] $body:tt) => {
+        $crate::__foreign_type_impl! { @if_mut [$($opts)*] $body }
+    };
+    (@if_mut [] { $($body:tt)* }) => {
+        $($body)*
+    };
+
+    (@require_mut [(immutable) $($opts:tt)*] $what:literal) => {
+        compile_error!(concat!(
+            "types declared with `const MUTABLE: bool = false;` cannot have ",
+            $what
+        ));
+    };
+    (@require_mut [$opt:tt $($opts:tt)*] $what:literal) => {
+        $crate::__foreign_type_impl! { @require_mut [$($opts)*] $what }
+    };
+    (@require_mut [] $what:literal) => {};
+
+    (@drop [(drop_none) $($opts:tt)*] $all:tt $owned:ident) => {};
+    (@drop [(drop [$($attr:tt)*] [$($drop:expr),+] $abi:tt) $($opts:tt)*] $all:tt $owned:ident) => {
+        $crate::__foreign_type_impl! { @drop_check $abi $owned, $($drop),+ }
+
+        $($attr)*
+        impl Drop for $owned {
+            #[inline]
+            fn drop(&mut self) {
+                $crate::__foreign_type_borrow! { exclusive $owned, self.0 }
+                $crate::__foreign_type_children! { $owned, self.0 }
+                $crate::__foreign_type_shutdown! { @drop $owned, self.0, {
+                    $crate::__foreign_type_impl! { @callbacks_drop $all $all self.0, {
+                        $crate::__foreign_type_impl! { @on_release $all $owned, self.0 }
+                        unsafe {
+                            $($drop(self.0);)+
+                        }
+                    } }
+                } }
+            }
+        }
+    };
+    (@drop [$opt:tt $($opts:tt)*] $all:tt $owned:ident) => {
+        $crate::__foreign_type_impl! { @drop [$($opts)*] $all $owned }
+    };
+    (@drop_check [$abi:tt] $owned:ident, $($drop:expr),+) => {
+        const _: () = {
+            fn check<P, R>(_: unsafe extern $abi fn(P) -> R)
+            where
+                P: $crate::__private::DropArg<<$owned as $crate::ForeignType>::CType>,
+            {
+            }
+
+            #[allow(dead_code)]
+            fn check_all() {
+                $(check($drop);)+
+            }
+        };
+    };
+    (@drop [] $all:tt $owned:ident) => {
+        compile_error!("foreign_type! requires a `fn drop = ...;` entry");
+    };
+
+    (@free_all [(free_all $free_all:expr) $($opts:tt)*] $ctype:ty, $owned:ident) => {
+        impl $crate::batch::BatchFree for $owned {
+            #[inline]
+            unsafe fn free_all(ptrs: *mut *mut $ctype, len: usize) {
+                $free_all(ptrs, len as _);
+            }
+        }
+    };
+    (@free_all [$opt:tt $($opts:tt)*] $ctype:ty, $owned:ident) => {
+        $crate::__foreign_type_impl! { @free_all [$($opts)*] $ctype, $owned }
+    };
+    (@free_all [] $ctype:ty, $owned:ident) => {};
+
+    (@up_ref [(up_ref $up_ref:expr) $($opts:tt)*] $ctype:ty, $owned:ident) => {
+        impl $crate::rc::RefCounted for $owned {
+            #[inline]
+            unsafe fn up_ref(ptr: *mut $ctype) {
+                $up_ref(ptr);
+            }
+        }
+    };
+    (@up_ref [$opt:tt $($opts:tt)*] $ctype:ty, $owned:ident) => {
+        $crate::__foreign_type_impl! { @up_ref [$($opts)*] $ctype, $owned }
+    };
+    (@up_ref [] $ctype:ty, $owned:ident) => {};
+
+    (@refcount_get [(refcount_get $get:expr) $($opts:tt)*] $ctype:ty, $owned:ident) => {
+        impl $crate::rc::RefCountGet for $owned {
+            #[inline]
+            unsafe fn refcount_get(ptr: *mut $ctype) -> usize {
+                $get(ptr) as usize
+            }
+        }
+    };
+    (@refcount_get [$opt:tt $($opts:tt)*] $ctype:ty, $owned:ident) => {
+        $crate::__foreign_type_impl! { @refcount_get [$($opts)*] $ctype, $owned }
+    };
+    (@refcount_get [] $ctype:ty, $owned:ident) => {};
+
+    (@stats [(sizeof $sizeof:expr) $($opts:tt)*] $owned:ident) => {
+        $crate::__foreign_type_stats! { @impl $owned }
+    };
+    (@stats [$opt:tt $($opts:tt)*] $owned:ident) => {
+        $crate::__foreign_type_impl! { @stats [$($opts)*] $owned }
+    };
+    (@stats [] $owned:ident) => {};
+
+    (@on_create $opts:tt $owned:ident, $ptr:expr) => {
+        $crate::__foreign_type_hooks! { @create $owned, $ptr }
+        $crate::__foreign_type_impl! { @stats_create $opts $owned, $ptr }
+        $crate::__foreign_type_impl! { @shutdown_create $opts $owned, $ptr }
+        $crate::__foreign_type_impl! { @callbacks_create $opts $ptr }
+    };
+    (@on_release $opts:tt $owned:ident, $ptr:expr) => {
+        $crate::__foreign_type_hooks! { @release $owned, $ptr }
+        $crate::__foreign_type_impl! { @stats_release $opts $owned, $ptr }
+        $crate::__foreign_type_impl! { @shutdown_release $opts $owned, $ptr }
+    };
+
+    (@shutdown_create [(drop_none) $($opts:tt)*] $owned:ident, $ptr:expr) => {};
+    (@shutdown_create [$opt:tt $($opts:tt)*] $owned:ident, $ptr:expr) => {
+        $crate::__foreign_type_impl! { @shutdown_create [$($opts)*] $owned, $ptr }
+    };
+    (@shutdown_create [] $owned:ident, $ptr:expr) => {
+        $crate::__foreign_type_shutdown! { @create $owned, $ptr }
+    };
+
+    (@shutdown_release [(drop_none) $($opts:tt)*] $owned:ident, $ptr:expr) => {};
+    (@shutdown_release [$opt:tt $($opts:tt)*] $owned:ident, $ptr:expr) => {
+        $crate::__foreign_type_impl! { @shutdown_release [$($opts)*] $owned, $ptr }
+    };
+    (@shutdown_release [] $owned:ident, $ptr:expr) => {
+        $crate::__foreign_type_shutdown! { @release $owned, $ptr }
+    };
+
+    (@stats_create [(sizeof $sizeof:expr) $($opts:tt)*] $owned:ident, $ptr:expr) => {
+        $crate::__foreign_type_stats! { @create $owned, $sizeof, $ptr }
+    };
+    (@stats_create [$opt:tt $($opts:tt)*] $owned:ident, $ptr:expr) => {
+        $crate::__foreign_type_impl! { @stats_create [$($opts)*] $owned, $ptr }
+    };
+    (@stats_create [] $owned:ident, $ptr:expr) => {};
+
+    (@stats_release [(sizeof $sizeof:expr) $($opts:tt)*] $owned:ident, $ptr:expr) => {
+        $crate::__foreign_type_stats! { @release $owned, $ptr }
+    };
+    (@stats_release [$opt:tt $($opts:tt)*] $owned:ident, $ptr:expr) => {
+        $crate::__foreign_type_impl! { @stats_release [$($opts)*] $owned, $ptr }
+    };
+    (@stats_release [] $owned:ident, $ptr:expr) => {};
+
+    (@destroy [(destroy $e:ty, $destroy:expr) $($opts:tt)*] $all:tt $owned:ident) => {
+        impl $owned {
+            /// Destroys the value, returning any error reported by the destructor.
+            #[inline]
+            pub fn destroy(self) -> Result<(), $e> {
+                $crate::__foreign_type_borrow! { exclusive $owned, self.0 }
+                $crate::__foreign_type_children! { $owned, self.0 }
+                let ptr = self.0;
+                ::std::mem::forget(self);
+                let result;
+                $crate::__foreign_type_impl! { @callbacks_drop $all $all ptr, {
+                    $crate::__foreign_type_impl! { @on_release $all $owned, ptr }
+                    result = unsafe { $destroy(ptr) };
+                } }
+                result
+            }
+        }
+    };
+    (@destroy [$opt:tt $($opts:tt)*] $all:tt $owned:ident) => {
+        $crate::__foreign_type_impl! { @destroy [$($opts)*] $all $owned }
+    };
+    (@destroy [] $all:tt $owned:ident) => {};
+
+    (@reset [(reset $reset:expr) $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @require_mut $all "`fn reset`" }
+
+        impl $borrowed {
+            /// Resets the value to its initial state, so that it can be reused.
+            #[inline]
+            pub fn reset(&mut self) {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe {
+                    $reset($crate::ForeignTypeRef::as_ptr(self));
+                }
+            }
+        }
+    };
+    (@reset [(try_reset $e:ty, $reset:expr) $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @require_mut $all "`fn reset`" }
+
+        impl $borrowed {
+            /// Resets the value to its initial state, so that it can be reused.
+            #[inline]
+            pub fn reset(&mut self) -> Result<(), $e> {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe { $reset($crate::ForeignTypeRef::as_ptr(self)) }
+            }
+        }
+    };
+    (@reset [$opt:tt $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @reset [$($opts)*] $all $borrowed }
+    };
+    (@reset [] $all:tt $borrowed:ident) => {};
+
+    (@clone [(clone $clone:expr) $($opts:tt)*] $ctype:ty, $owned:ident, $borrowed:ident) => {
+        impl Clone for $owned {
+            #[inline]
+            fn clone(&self) -> $owned {
+                $crate::__foreign_type_borrow! { shared $owned, self.0 }
+                unsafe {
+                    let handle: *mut $ctype = $clone(self.0);
+                    $crate::ForeignType::from_ptr(handle)
+                }
+            }
+        }
+
+        impl ::std::borrow::ToOwned for $borrowed {
+            type Owned = $owned;
+            #[inline]
+            fn to_owned(&self) -> $owned {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe {
+                    let handle: *mut $ctype = $clone($crate::ForeignTypeRef::as_ptr(self));
+                    $crate::ForeignType::from_ptr(handle)
+                }
+            }
+        }
+    };
+    (@clone [$opt:tt $($opts:tt)*] $ctype:ty, $owned:ident, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @clone [$($opts)*] $ctype, $owned, $borrowed }
+    };
+    (@clone [] $ctype:ty, $owned:ident, $borrowed:ident) => {};
+
+    (
+        @try_clone [(try_clone $clone:expr) $($opts:tt)*] $all:tt
+        $ctype:ty, $owned:ident, $borrowed:ident
+    ) => {
+        $crate::__foreign_type_impl! { @try_clone_error $all $clone, $ctype, $owned, $borrowed }
+    };
+    (@try_clone [$opt:tt $($opts:tt)*] $all:tt $ctype:ty, $owned:ident, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @try_clone [$($opts)*] $all $ctype, $owned, $borrowed }
+    };
+    (@try_clone [] $all:tt $ctype:ty, $owned:ident, $borrowed:ident) => {};
+
+    (
+        @try_clone_error [(error_source $e:ty, $capture:expr) $($opts:tt)*]
+        $clone:expr, $ctype:ty, $owned:ident, $borrowed:ident
+    ) => {
+        impl $borrowed {
+            /// Returns a copy of this value, or the captured error if it could not be duplicated.
+            #[inline]
+            pub fn try_clone(&self) -> Result<$owned, $e> {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe {
+                    let handle: *mut $ctype = $clone($crate::ForeignTypeRef::as_ptr(self));
+                    $owned::try_from_ptr(handle)
+                }
+            }
+        }
+    };
+    (
+        @try_clone_error [$opt:tt $($opts:tt)*]
+        $clone:expr, $ctype:ty, $owned:ident, $borrowed:ident
+    ) => {
+        $crate::__foreign_type_impl! { @try_clone_error [$($opts)*] $clone, $ctype, $owned, $borrowed }
+    };
+    (@try_clone_error [] $clone:expr, $ctype:ty, $owned:ident, $borrowed:ident) => {
+        impl $borrowed {
+            /// Returns a copy of this value, or an error if it could not be duplicated.
+            #[inline]
+            pub fn try_clone(&self) -> Result<$owned, $crate::CloneError> {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe {
+                    let handle: *mut $ctype = $clone($crate::ForeignTypeRef::as_ptr(self));
+                    match $crate::ForeignType::from_ptr_opt(handle) {
+                        Some(owned) => Ok(owned),
+                        None => Err($crate::CloneError::__new()),
+                    }
+                }
+            }
+        }
+    };
+
+    (
+        @error_source [(error_source $e:ty, $capture:expr) $($opts:tt)*] $all:tt
+        $ctype:ty, $owned:ident
+    ) => {
+        $crate::__foreign_type_impl! { @try_from_ptr $all [$e, $capture] $ctype, $owned }
+    };
+    (@error_source [$opt:tt $($opts:tt)*] $all:tt $ctype:ty, $owned:ident) => {
+        $crate::__foreign_type_impl! { @error_source [$($opts)*] $all $ctype, $owned }
+    };
+    (@error_source [] $all:tt $ctype:ty, $owned:ident) => {
+        $crate::__foreign_type_impl! { @try_from_ptr $all [] $ctype, $owned }
+    };
+
+    (
+        @try_from_ptr [(validate $validate:expr) $($opts:tt)*] [$e:ty, $capture:expr]
+        $ctype:ty, $owned:ident
+    ) => {
+        impl $owned {
+            /// Constructs an owned value from a pointer returned by a fallible C function.
+            ///
+            /// If the pointer is invalid, the error reported by the C library is captured and
+            /// returned instead. If it fails validation, a `ValidationError` is returned and the
+            /// pointer is not freed.
+            ///
+            /// # Safety
+            ///
+            /// `ptr` must either be invalid or a valid, owned instance of the C type.
+            #[inline]
+            pub unsafe fn try_from_ptr(ptr: *mut $ctype) -> Result<$owned, $e>
+            where
+                $e: ::std::convert::From<$crate::ValidationError>,
+            {
+                if ptr == <$owned as $crate::ForeignType>::INVALID {
+                    Err($capture())
+                } else if $validate(ptr) as i64 == 0 {
+                    Err(::std::convert::From::from($crate::ValidationError::__new()))
+                } else {
+                    Ok($crate::ForeignType::from_ptr(ptr))
+                }
+            }
+        }
+    };
+    (@try_from_ptr [(validate $validate:expr) $($opts:tt)*] [] $ctype:ty, $owned:ident) => {
+        impl $owned {
+            /// Constructs an owned value from a pointer, checking it with the C library first.
+            ///
+            /// If the pointer is invalid or fails validation, a `ValidationError` is returned and
+            /// the pointer is not freed.
+            ///
+            /// # Safety
+            ///
+            /// `ptr` must either be invalid or a valid, owned instance of the C type if it passes
+            /// validation.
+            #[inline]
+            pub unsafe fn try_from_ptr(ptr: *mut $ctype) -> Result<$owned, $crate::ValidationError> {
+                if ptr == <$owned as $crate::ForeignType>::INVALID || $validate(ptr) as i64 == 0 {
+                    Err($crate::ValidationError::__new())
+                } else {
+                    Ok($crate::ForeignType::from_ptr(ptr))
+                }
+            }
+        }
+    };
+    (@try_from_ptr [$opt:tt $($opts:tt)*] $source:tt $ctype:ty, $owned:ident) => {
+        $crate::__foreign_type_impl! { @try_from_ptr [$($opts)*] $source $ctype, $owned }
+    };
+    (@try_from_ptr [] [$e:ty, $capture:expr] $ctype:ty, $owned:ident) => {
+        impl $owned {
+            /// Constructs an owned value from a pointer returned by a fallible C function.
+            ///
+            /// If the pointer is invalid, the error reported by the C library is captured and
+            /// returned instead.
+            ///
+            /// # Safety
+            ///
+            /// `ptr` must either be invalid or a valid, owned instance of the C type.
+            #[inline]
+            pub unsafe fn try_from_ptr(ptr: *mut $ctype) -> Result<$owned, $e> {
+                match $crate::ForeignType::from_ptr_opt(ptr) {
+                    Some(owned) => Ok(owned),
+                    None => Err($capture()),
+                }
+            }
+        }
+    };
+    (@try_from_ptr [] [] $ctype:ty, $owned:ident) => {};
+
+    (@send [(send) $($opts:tt)*] $owned:ident, $borrowed:ident) => {
+        unsafe impl Send for $owned {}
+        unsafe impl Send for $borrowed {}
+    };
+    (@send [$opt:tt $($opts:tt)*] $owned:ident, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @send [$($opts)*] $owned, $borrowed }
+    };
+    (@send [] $owned:ident, $borrowed:ident) => {};
+
+    (@sync [(sync) $($opts:tt)*] $owned:ident, $borrowed:ident) => {
+        unsafe impl Sync for $owned {}
+        unsafe impl Sync for $borrowed {}
+    };
+    (@sync [$opt:tt $($opts:tt)*] $owned:ident, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @sync [$($opts)*] $owned, $borrowed }
+    };
+    (@sync [] $owned:ident, $borrowed:ident) => {};
+
+    (@compat [(compat) $($opts:tt)*] $owned:ident, $borrowed:ident) => {
+        unsafe impl $crate::ForeignTypeCompat for $owned {}
+        unsafe impl $crate::ForeignTypeCompat for $borrowed {}
+    };
+    (@compat [$opt:tt $($opts:tt)*] $owned:ident, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @compat [$($opts)*] $owned, $borrowed }
+    };
+    (@compat [] $owned:ident, $borrowed:ident) => {};
+
+    (@as_raw_fd [(as_raw_fd $fd:expr) $($opts:tt)*] $owned:ident, $borrowed:ident) => {
+        impl ::std::os::fd::AsRawFd for $borrowed {
+            #[inline]
+            fn as_raw_fd(&self) -> ::std::os::fd::RawFd {
+                unsafe { $fd($crate::ForeignTypeRef::as_ptr(self)) }
+            }
+        }
+
+        impl ::std::os::fd::AsRawFd for $owned {
+            #[inline]
+            fn as_raw_fd(&self) -> ::std::os::fd::RawFd {
+                ::std::os::fd::AsRawFd::as_raw_fd(&**self)
+            }
+        }
+
+        impl $borrowed {
+            /// Borrows the object's file descriptor, or returns `None` if it has none.
+            #[inline]
+            pub fn as_fd(&self) -> Option<::std::os::fd::BorrowedFd<'_>> {
+                let fd = ::std::os::fd::AsRawFd::as_raw_fd(self);
+                if fd < 0 {
+                    None
+                } else {
+                    // The descriptor stays open for as long as the object is borrowed.
+                    unsafe { Some(::std::os::fd::BorrowedFd::borrow_raw(fd)) }
+                }
+            }
+        }
+    };
+    (@as_raw_fd [$opt:tt $($opts:tt)*] $owned:ident, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @as_raw_fd [$($opts)*] $owned, $borrowed }
+    };
+    (@as_raw_fd [] $owned:ident, $borrowed:ident) => {};
+
+    (@into_raw_fd [(into_raw_fd $fd:expr) $($opts:tt)*] $owned:ident) => {
+        impl ::std::os::fd::IntoRawFd for $owned {
+            #[inline]
+            fn into_raw_fd(self) -> ::std::os::fd::RawFd {
+                unsafe { $fd($crate::ForeignType::as_ptr(&self)) }
+            }
+        }
+
+        impl ::std::convert::From<$owned> for ::std::os::fd::OwnedFd {
+            #[inline]
+            fn from(value: $owned) -> ::std::os::fd::OwnedFd {
+                let fd = ::std::os::fd::IntoRawFd::into_raw_fd(value);
+                assert!(fd >= 0, "object has no file descriptor");
+                unsafe { ::std::os::fd::FromRawFd::from_raw_fd(fd) }
+            }
+        }
+    };
+    (@into_raw_fd [$opt:tt $($opts:tt)*] $owned:ident) => {
+        $crate::__foreign_type_impl! { @into_raw_fd [$($opts)*] $owned }
+    };
+    (@into_raw_fd [] $owned:ident) => {};
+
+    (@from_bytes [(from_bytes $from_bytes:expr) $($opts:tt)*] $all:tt $owned:ident) => {
+        $crate::__foreign_type_impl! { @from_bytes_error $all $from_bytes, $owned }
+        $crate::__foreign_type_arbitrary! { $owned }
+    };
+    (@from_bytes [$opt:tt $($opts:tt)*] $all:tt $owned:ident) => {
+        $crate::__foreign_type_impl! { @from_bytes [$($opts)*] $all $owned }
+    };
+    (@from_bytes [] $all:tt $owned:ident) => {};
+
+    (
+        @from_bytes_error [(error_source $e:ty, $capture:expr) $($opts:tt)*]
+        $from_bytes:expr, $owned:ident
+    ) => {
+        impl $owned {
+            /// Parses a value from bytes, or returns the captured error if parsing fails.
+            #[inline]
+            pub fn from_bytes(bytes: &[u8]) -> Result<$owned, $e> {
+                unsafe { $owned::try_from_ptr($from_bytes(bytes.as_ptr(), bytes.len())) }
+            }
+        }
+    };
+    (@from_bytes_error [$opt:tt $($opts:tt)*] $from_bytes:expr, $owned:ident) => {
+        $crate::__foreign_type_impl! { @from_bytes_error [$($opts)*] $from_bytes, $owned }
+    };
+    (@from_bytes_error [] $from_bytes:expr, $owned:ident) => {
+        impl $owned {
+            /// Parses a value from bytes.
+            #[inline]
+            pub fn from_bytes(bytes: &[u8]) -> Result<$owned, $crate::ParseError> {
+                unsafe {
+                    let ptr = $from_bytes(bytes.as_ptr(), bytes.len());
+                    match $crate::ForeignType::from_ptr_opt(ptr) {
+                        Some(owned) => Ok(owned),
+                        None => Err($crate::ParseError::__new()),
+                    }
+                }
+            }
+        }
+    };
+
+    (@to_bytes [(to_bytes $to_bytes:expr) $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @to_bytes_error $all $to_bytes, $borrowed }
+    };
+    (@to_bytes [$opt:tt $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @to_bytes [$($opts)*] $all $borrowed }
+    };
+    (@to_bytes [] $all:tt $borrowed:ident) => {};
+
+    (
+        @to_bytes_error [(error_source $e:ty, $capture:expr) $($opts:tt)*]
+        $to_bytes:expr, $borrowed:ident
+    ) => {
+        $crate::__foreign_type_impl! { @to_bytes_impl $to_bytes, $borrowed, $e, $capture() }
+    };
+    (@to_bytes_error [$opt:tt $($opts:tt)*] $to_bytes:expr, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @to_bytes_error [$($opts)*] $to_bytes, $borrowed }
+    };
+    (@to_bytes_error [] $to_bytes:expr, $borrowed:ident) => {
+        $crate::__foreign_type_impl! {
+            @to_bytes_impl $to_bytes, $borrowed, $crate::EncodeError, $crate::EncodeError::__new()
+        }
+    };
+
+    (@to_bytes_impl $to_bytes:expr, $borrowed:ident, $e:ty, $error:expr) => {
+        impl $borrowed {
+            /// Serializes this value to bytes.
+            #[inline]
+            pub fn to_bytes(&self) -> Result<::std::vec::Vec<u8>, $e> {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                let ptr = $crate::ForeignTypeRef::as_ptr(self);
+                $crate::buffer::double_call_buffer(|buf, len| {
+                    let ret = unsafe { $to_bytes(ptr, buf, len) };
+                    match <usize as ::std::convert::TryFrom<_>>::try_from(ret) {
+                        Ok(len) => $crate::buffer::Fill::Len(len),
+                        Err(_) => $crate::buffer::Fill::Err($error),
+                    }
+                })
+            }
+        }
+    };
+
+    (@getters [(getters { $($getters:tt)* }) $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @getter $all $borrowed; $($getters)* }
+        $crate::__foreign_type_impl! { @getters [$($opts)*] $all $borrowed }
+    };
+    (@getters [$opt:tt $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @getters [$($opts)*] $all $borrowed }
+    };
+    (@getters [] $all:tt $borrowed:ident) => {};
+
+    (
+        @getter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> &mut $t:ty = $f:expr;
+        $($getters:tt)*
+    ) => {
+        $crate::__foreign_type_impl! { @require_mut $all "getters returning `&mut`" }
+
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&mut self) -> &mut $t
+            where
+                $t: $crate::ForeignTypeMut,
+            {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe {
+                    let ptr = $f($crate::ForeignTypeRef::as_ptr(self));
+                    <$t as $crate::ForeignTypeRef>::from_ptr_mut(ptr)
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @getter $all $borrowed; $($getters)* }
+    };
+    (
+        @getter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> &$t:ty = $f:expr;
+        $($getters:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&self) -> &$t {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe {
+                    let ptr = $f($crate::ForeignTypeRef::as_ptr(self));
+                    <$t as $crate::ForeignTypeRef>::from_ptr(ptr)
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @getter $all $borrowed; $($getters)* }
+    };
+    (
+        @getter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> Option<&mut $t:ty> = $f:expr;
+        $($getters:tt)*
+    ) => {
+        $crate::__foreign_type_impl! { @require_mut $all "getters returning `&mut`" }
+
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&mut self) -> Option<&mut $t>
+            where
+                $t: $crate::ForeignTypeMut,
+            {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe {
+                    let ptr = $f($crate::ForeignTypeRef::as_ptr(self));
+                    <$t as $crate::ForeignTypeRef>::from_ptr_mut_opt(ptr)
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @getter $all $borrowed; $($getters)* }
+    };
+    (
+        @getter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> Option<&$t:ty> = $f:expr;
+        $($getters:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&self) -> Option<&$t> {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe {
+                    let ptr = $f($crate::ForeignTypeRef::as_ptr(self));
+                    <$t as $crate::ForeignTypeRef>::from_ptr_opt(ptr)
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @getter $all $borrowed; $($getters)* }
+    };
+    (
+        @getter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> Option<owned $t:ty> = $f:expr;
+        $($getters:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&self) -> Option<$t> {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe {
+                    let ptr = $f($crate::ForeignTypeRef::as_ptr(self));
+                    <$t as $crate::ForeignType>::from_ptr_opt(ptr)
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @getter $all $borrowed; $($getters)* }
+    };
+    (
+        @getter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> owned $t:ty = $f:expr;
+        $($getters:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&self) -> $t {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe {
+                    let ptr = $f($crate::ForeignTypeRef::as_ptr(self));
+                    <$t as $crate::ForeignType>::from_ptr(ptr)
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @getter $all $borrowed; $($getters)* }
+    };
+    (
+        @getter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> $t:ty = $f:expr;
+        $($getters:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&self) -> $t {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe { $f($crate::ForeignTypeRef::as_ptr(self)) }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @getter $all $borrowed; $($getters)* }
+    };
+    (@getter $all:tt $borrowed:ident;) => {};
+
+    (@setters [(setters { $($setters:tt)* }) $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @require_mut $all "`setters`" }
+        $crate::__foreign_type_impl! { @setter $all $borrowed; $($setters)* }
+        $crate::__foreign_type_impl! { @setters [$($opts)*] $all $borrowed }
+    };
+    (@setters [$opt:tt $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @setters [$($opts)*] $all $borrowed }
+    };
+    (@setters [] $all:tt $borrowed:ident) => {};
+
+    (
+        @setter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident(owned $t:ty) $(-> $ret:ty)* = $f:expr;
+        $($setters:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&mut self, value: $t) $(-> $ret)* {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                let value = <$t as $crate::ForeignType>::into_ptr(value);
+                unsafe { $f($crate::ForeignTypeRef::as_ptr(self), value) }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @setter $all $borrowed; $($setters)* }
+    };
+    (
+        @setter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident(&$t:ty) $(-> $ret:ty)* = $f:expr;
+        $($setters:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&mut self, value: &$t) $(-> $ret)* {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                let value = <$t as $crate::ForeignTypeRef>::as_ptr(value);
+                unsafe { $f($crate::ForeignTypeRef::as_ptr(self), value) }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @setter $all $borrowed; $($setters)* }
+    };
+    (
+        @setter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident(cstr) = $f:expr;
+        $($setters:tt)*
+    ) => {
+        $crate::__foreign_type_impl! {
+            @setter $all $borrowed;
+            $(#[$attr])* $vis fn $name(cstr) -> () = $f;
+            $($setters)*
+        }
+    };
+    (
+        @setter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident(cstr) -> $ret:ty = $f:expr;
+        $($setters:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name<'a, S>(&mut self, value: S) -> ::std::result::Result<$ret, ::std::ffi::NulError>
+            where
+                S: $crate::strings::TryIntoCStr<'a>,
+            {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                let ptr = $crate::ForeignTypeRef::as_ptr(self);
+                $crate::strings::with_cstr(value, |value| unsafe { $f(ptr, value) })
+            }
+        }
+
+        $crate::__foreign_type_impl! { @setter $all $borrowed; $($setters)* }
+    };
+    (
+        @setter $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident($t:ty) $(-> $ret:ty)* = $f:expr;
+        $($setters:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&mut self, value: $t) $(-> $ret)* {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe { $f($crate::ForeignTypeRef::as_ptr(self), value) }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @setter $all $borrowed; $($setters)* }
+    };
+    (@setter $all:tt $borrowed:ident;) => {};
+
+    (@fields [(fields { $($fields:tt)* }) $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @field $all $borrowed; $($fields)* }
+        $crate::__foreign_type_impl! { @fields [$($opts)*] $all $borrowed }
+    };
+    (@fields [$opt:tt $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @fields [$($opts)*] $all $borrowed }
+    };
+    (@fields [] $all:tt $borrowed:ident) => {};
+
+    (
+        @field $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> &mut $t:ty = $field:ident;
+        $($fields:tt)*
+    ) => {
+        $crate::__foreign_type_impl! { @require_mut $all "fields returning `&mut`" }
+
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&mut self) -> &mut $t {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe { &mut (*$crate::ForeignTypeRef::as_ptr(self)).$field }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @field $all $borrowed; $($fields)* }
+    };
+    (
+        @field $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> &$t:ty = $field:ident;
+        $($fields:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&self) -> &$t {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe { &(*$crate::ForeignTypeRef::as_ptr(self)).$field }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @field $all $borrowed; $($fields)* }
+    };
+    (
+        @field $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> $t:ty = $field:ident;
+        $($fields:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&self) -> $t {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe { (*$crate::ForeignTypeRef::as_ptr(self)).$field }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @field $all $borrowed; $($fields)* }
+    };
+    (
+        @field $all:tt $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident($t:ty) = $field:ident;
+        $($fields:tt)*
+    ) => {
+        $crate::__foreign_type_impl! { @require_mut $all "field setters" }
+
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&mut self, value: $t) {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe { (*$crate::ForeignTypeRef::as_ptr(self)).$field = value }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @field $all $borrowed; $($fields)* }
+    };
+    (@field $all:tt $borrowed:ident;) => {};
+
+    (@callbacks [(callbacks { $($callbacks:tt)* }) $($opts:tt)*] $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @callback $borrowed; $($callbacks)* }
+        $crate::__foreign_type_impl! { @callbacks [$($opts)*] $borrowed }
+    };
+    (@callbacks [$opt:tt $($opts:tt)*] $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @callbacks [$($opts)*] $borrowed }
+    };
+    (@callbacks [] $borrowed:ident) => {};
+
+    (@callbacks_create [(callbacks $callbacks:tt) $($opts:tt)*] $ptr:expr) => {
+        $crate::callbacks::__retain($ptr as *mut _);
+    };
+    (@callbacks_create [$opt:tt $($opts:tt)*] $ptr:expr) => {
+        $crate::__foreign_type_impl! { @callbacks_create [$($opts)*] $ptr }
+    };
+    (@callbacks_create [] $ptr:expr) => {};
+
+    (@callbacks_release [(callbacks $callbacks:tt) $($opts:tt)*] $ptr:expr) => {
+        $crate::callbacks::__release($ptr as *mut _);
+    };
+    (@callbacks_release [$opt:tt $($opts:tt)*] $ptr:expr) => {
+        $crate::__foreign_type_impl! { @callbacks_release [$($opts)*] $ptr }
+    };
+    (@callbacks_release [] $ptr:expr) => {};
+
+    // Closures are only freed with the last owner of the object, and after the C object, which may
+    // call them while it is being destroyed.
+    (@callbacks_drop [(callbacks $callbacks:tt) $($opts:tt)*] $all:tt $ptr:expr, $body:block) => {
+        let last = $crate::callbacks::__release($ptr as *mut _)
+            && $crate::__foreign_type_impl! { @callbacks_unshared $all $all $ptr };
+        $body
+        if last {
+            $crate::callbacks::__clear($ptr as *mut _);
+        }
+    };
+    (@callbacks_drop [$opt:tt $($opts:tt)*] $all:tt $ptr:expr, $body:block) => {
+        $crate::__foreign_type_impl! { @callbacks_drop [$($opts)*] $all $ptr, $body }
+    };
+    (@callbacks_drop [] $all:tt $ptr:expr, $body:block) => {
+        $body
+    };
+
+    // Reference counted objects may also be referenced by the C library, which can still call
+    // their callbacks after the last Rust owner is dropped.
+    (@callbacks_unshared [(refcount_get $get:expr) $($opts:tt)*] $all:tt $ptr:expr) => {
+        unsafe { $get($ptr) as usize == 1 }
+    };
+    (@callbacks_unshared [$opt:tt $($opts:tt)*] $all:tt $ptr:expr) => {
+        $crate::__foreign_type_impl! { @callbacks_unshared [$($opts)*] $all $ptr }
+    };
+    (@callbacks_unshared [] $all:tt $ptr:expr) => {
+        $crate::__foreign_type_impl! { @callbacks_up_ref $all }
+    };
+
+    (@callbacks_up_ref [(up_ref $up_ref:expr) $($opts:tt)*]) => {
+        compile_error!("a type with `callbacks` and `fn up_ref` also requires a `fn refcount_get` entry")
+    };
+    (@callbacks_up_ref [$opt:tt $($opts:tt)*]) => {
+        $crate::__foreign_type_impl! { @callbacks_up_ref [$($opts)*] }
+    };
+    (@callbacks_up_ref []) => {
+        true
+    };
+
+    // Closures which may mutate their state are serialized with a mutex, since the C library may
+    // call them from several threads at once.
+    (
+        @callback $borrowed:ident;
+        $(#[$attr:meta])*
+        $vis:vis fn $name:ident(FnMut($($arg:ident: $arg_ty:ty),* $(,)*) $(-> $ret:ty)*) = $f:expr;
+        $($callbacks:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name<F>(&mut self, f: F)
+            where
+                F: FnMut($($arg_ty),*) $(-> $ret)* + Send + 'static,
+            {
+                unsafe extern "C" fn trampoline<F>(
+                    $($arg: $arg_ty,)*
+                    userdata: *mut ::std::os::raw::c_void,
+                ) $(-> $ret)*
+                where
+                    F: FnMut($($arg_ty),*) $(-> $ret)*,
+                {
+                    let f = &*(userdata as *const ::std::sync::Mutex<F>);
+                    let mut f = f.lock().unwrap_or_else(|e| e.into_inner());
+                    (*f)($($arg),*)
+                }
+
+                $crate::__foreign_type_impl! {
+                    @callback_set self, $borrowed, $name, $f, trampoline::<F>, ::std::sync::Mutex::new(f)
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @callback $borrowed; $($callbacks)* }
+    };
+    (
+        @callback $borrowed:ident;
+        $(#[$attr:meta])*
+        $vis:vis fn $name:ident(Fn($($arg:ident: $arg_ty:ty),* $(,)*) $(-> $ret:ty)*) = $f:expr;
+        $($callbacks:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name<F>(&mut self, f: F)
+            where
+                F: Fn($($arg_ty),*) $(-> $ret)* + Send + Sync + 'static,
+            {
+                unsafe extern "C" fn trampoline<F>(
+                    $($arg: $arg_ty,)*
+                    userdata: *mut ::std::os::raw::c_void,
+                ) $(-> $ret)*
+                where
+                    F: Fn($($arg_ty),*) $(-> $ret)*,
+                {
+                    (*(userdata as *const F))($($arg),*)
+                }
+
+                $crate::__foreign_type_impl! {
+                    @callback_set self, $borrowed, $name, $f, trampoline::<F>, f
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @callback $borrowed; $($callbacks)* }
+    };
+    (@callback $borrowed:ident;) => {};
+
+    (
+        @callback_set $this:expr, $borrowed:ident, $name:ident, $f:expr, $trampoline:expr,
+        $stored:expr
+    ) => {
+        $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr($this) }
+        let ptr = $crate::ForeignTypeRef::as_ptr($this);
+        let callback = $crate::callbacks::__Stored::new($stored);
+        let userdata = callback.userdata();
+        // The previous closure is only freed once the C library has stopped using it.
+        let previous = $crate::callbacks::__replace(
+            ptr as *mut _,
+            ::std::any::type_name::<$borrowed>(),
+            stringify!($name),
+            callback,
+        );
+        unsafe { $f(ptr, $trampoline, userdata) };
+        drop(previous);
+    };
+
+    (@foreach [(foreach { $($foreach:tt)* }) $($opts:tt)*] $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @visitor $borrowed; $($foreach)* }
+        $crate::__foreign_type_impl! { @foreach [$($opts)*] $borrowed }
+    };
+    (@foreach [$opt:tt $($opts:tt)*] $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @foreach [$($opts)*] $borrowed }
+    };
+    (@foreach [] $borrowed:ident) => {};
+
+    (
+        @visitor $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident(&$t:ty) = $f:expr;
+        $($foreach:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name<E, F>(&self, f: F) -> Result<(), E>
+            where
+                F: FnMut(&$t) -> Result<(), E>,
+            {
+                $crate::__foreign_type_borrow! { shared $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe {
+                    $crate::foreach::for_each(
+                        |callback, userdata| {
+                            $f($crate::ForeignTypeRef::as_ptr(self), callback, userdata);
+                        },
+                        f,
+                    )
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @visitor $borrowed; $($foreach)* }
+    };
+    (@visitor $borrowed:ident;) => {};
+
+    (
+        @facets [(facets {
+            $($(#[$attr:meta])* pub struct $facet:ident;)+
+        }) $($opts:tt)*] $all:tt $impl_attrs:tt $ctype:ty, $borrowed:ident
+    ) => {
+        $(
+            $crate::__foreign_type_impl! {
+                @facet $all $impl_attrs $ctype, $borrowed; $(#[$attr])* pub struct $facet;
+            }
+        )+
+
+        $crate::__foreign_type_impl! { @if_mut $all {
+            impl $borrowed {
+                /// Mutably borrows each facet of the value at once.
+                #[inline]
+                pub fn split(&mut self) -> ($(&mut $facet,)+) {
+                    let ptr = $crate::ForeignTypeRef::as_ptr(self);
+                    unsafe { ($(<$facet as $crate::ForeignTypeRef>::from_ptr_mut(ptr),)+) }
+                }
+            }
+        } }
+    };
+    (
+        @facet $all:tt [$($impl_attr:tt)*] $ctype:ty, $borrowed:ident;
+        $(#[$attr:meta])* pub struct $facet:ident;
+    ) => {
+        $(#[$attr])*
+        pub struct $facet($crate::Opaque);
+
+        $($impl_attr)*
+        impl $crate::ForeignTypeRef for $facet {
+            type CType = $ctype;
+
+            $crate::__foreign_type_impl! { @invalid $all }
+        }
+
+        impl ::std::convert::AsRef<$facet> for $borrowed {
+            #[inline]
+            fn as_ref(&self) -> &$facet {
+                unsafe { $crate::ForeignTypeRef::from_ptr($crate::ForeignTypeRef::as_ptr(self)) }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @if_mut $all {
+            unsafe impl $crate::ForeignTypeMut for $facet {}
+
+            impl ::std::convert::AsMut<$facet> for $borrowed {
+                #[inline]
+                fn as_mut(&mut self) -> &mut $facet {
+                    unsafe { $crate::ForeignTypeRef::from_ptr_mut($crate::ForeignTypeRef::as_ptr(self)) }
+                }
+            }
+        } }
+    };
+    (@facets [$opt:tt $($opts:tt)*] $all:tt $impl_attrs:tt $ctype:ty, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @facets [$($opts)*] $all $impl_attrs $ctype, $borrowed }
+    };
+    (@facets [] $all:tt $impl_attrs:tt $ctype:ty, $borrowed:ident) => {};
+
+    (@cursor [(cursor { $($cursor:tt)* }) $($opts:tt)*] $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @next $borrowed; $($cursor)* }
+        $crate::__foreign_type_impl! { @cursor [$($opts)*] $borrowed }
+    };
+    (@cursor [$opt:tt $($opts:tt)*] $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @cursor [$($opts)*] $borrowed }
+    };
+    (@cursor [] $borrowed:ident) => {};
+
+    (@guards [(guards { $($guards:tt)* }) $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @require_mut $all "`guards`" }
+        $crate::__foreign_type_impl! { @guard $all $borrowed; $($guards)* }
+        $crate::__foreign_type_impl! { @guards [$($opts)*] $all $borrowed }
+    };
+    (@guards [$opt:tt $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @guards [$($opts)*] $all $borrowed }
+    };
+    (@guards [] $all:tt $borrowed:ident) => {};
+
+    // Release functions written as `X_unmap(owner, resource)` are also passed the resource. They
+    // are matched first, since an expression would consume the argument list.
+    (
+        @guard $all:tt $borrowed:ident;
+        $(#[$guard_attr:meta])* $guard_vis:vis struct $guard:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> $ret:ident<&mut $t:ty>
+            = $acquire:expr, $($release:ident)::+(owner, resource);
+        $($guards:tt)*
+    ) => {
+        $crate::__foreign_type_impl! {
+            @guard_ptr $all [mut] [$(#[$guard_attr])*] $guard_vis $guard,
+            [$(#[$attr])*] $vis $name, $ret, $t, $borrowed, $acquire, [resource $($release)::+]
+        }
+
+        $crate::__foreign_type_impl! { @guard $all $borrowed; $($guards)* }
+    };
+    (
+        @guard $all:tt $borrowed:ident;
+        $(#[$guard_attr:meta])* $guard_vis:vis struct $guard:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> $ret:ident<&mut $t:ty>
+            = $acquire:expr, $release:expr;
+        $($guards:tt)*
+    ) => {
+        $crate::__foreign_type_impl! {
+            @guard_ptr $all [mut] [$(#[$guard_attr])*] $guard_vis $guard,
+            [$(#[$attr])*] $vis $name, $ret, $t, $borrowed, $acquire, [owner $release]
+        }
+
+        $crate::__foreign_type_impl! { @guard $all $borrowed; $($guards)* }
+    };
+    (
+        @guard $all:tt $borrowed:ident;
+        $(#[$guard_attr:meta])* $guard_vis:vis struct $guard:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> $ret:ident<&$t:ty>
+            = $acquire:expr, $($release:ident)::+(owner, resource);
+        $($guards:tt)*
+    ) => {
+        $crate::__foreign_type_impl! {
+            @guard_ptr $all [] [$(#[$guard_attr])*] $guard_vis $guard,
+            [$(#[$attr])*] $vis $name, $ret, $t, $borrowed, $acquire, [resource $($release)::+]
+        }
+
+        $crate::__foreign_type_impl! { @guard $all $borrowed; $($guards)* }
+    };
+    (
+        @guard $all:tt $borrowed:ident;
+        $(#[$guard_attr:meta])* $guard_vis:vis struct $guard:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> $ret:ident<&$t:ty>
+            = $acquire:expr, $release:expr;
+        $($guards:tt)*
+    ) => {
+        $crate::__foreign_type_impl! {
+            @guard_ptr $all [] [$(#[$guard_attr])*] $guard_vis $guard,
+            [$(#[$attr])*] $vis $name, $ret, $t, $borrowed, $acquire, [owner $release]
+        }
+
+        $crate::__foreign_type_impl! { @guard $all $borrowed; $($guards)* }
+    };
+    (
+        @guard $all:tt $borrowed:ident;
+        $(#[$guard_attr:meta])* $guard_vis:vis struct $guard:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> $ret:ident = $acquire:expr, $release:expr;
+        $($guards:tt)*
+    ) => {
+        $(#[$guard_attr])*
+        $guard_vis struct $guard<'a> {
+            owner: &'a mut $borrowed,
+        }
+
+        impl<'a> Drop for $guard<'a> {
+            #[inline]
+            fn drop(&mut self) {
+                unsafe { $release($crate::ForeignTypeRef::as_ptr(self.owner)) };
+            }
+        }
+
+        impl<'a> ::std::ops::Deref for $guard<'a> {
+            type Target = $borrowed;
+
+            #[inline]
+            fn deref(&self) -> &$borrowed {
+                self.owner
+            }
+        }
+
+        impl<'a> ::std::ops::DerefMut for $guard<'a> {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut $borrowed {
+                self.owner
+            }
+        }
+
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&mut self) -> $ret<'_> {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                unsafe { $acquire($crate::ForeignTypeRef::as_ptr(self)) };
+                $ret { owner: self }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @guard $all $borrowed; $($guards)* }
+    };
+    (@guard $all:tt $borrowed:ident;) => {};
+
+    // Both pointer forms mutably borrow the owner, so that the acquire function cannot be called
+    // again while a guard is alive.
+    (
+        @guard_ptr $all:tt $kind:tt [$($guard_attr:tt)*] $guard_vis:vis $guard:ident,
+        $attrs:tt $vis:vis $name:ident, $ret:ident, $t:ty, $borrowed:ident, $acquire:expr,
+        $release:tt
+    ) => {
+        $($guard_attr)*
+        $guard_vis struct $guard<'a> {
+            owner: &'a $borrowed,
+            resource: *mut <$t as $crate::ForeignTypeRef>::CType,
+        }
+
+        impl<'a> Drop for $guard<'a> {
+            #[inline]
+            fn drop(&mut self) {
+                let owner = $crate::ForeignTypeRef::as_ptr(self.owner);
+                $crate::__foreign_type_impl! { @guard_release $release owner, self.resource }
+            }
+        }
+
+        impl<'a> ::std::ops::Deref for $guard<'a> {
+            type Target = $t;
+
+            #[inline]
+            fn deref(&self) -> &$t {
+                unsafe { <$t as $crate::ForeignTypeRef>::from_ptr(self.resource) }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @guard_deref_mut $kind $guard, $t }
+
+        $crate::__foreign_type_impl! {
+            @guard_fn $all $attrs $vis $name, $ret, $t, $borrowed, $acquire
+        }
+    };
+
+    (@guard_release [owner $release:expr] $owner:expr, $resource:expr) => {
+        unsafe { $release($owner) };
+    };
+    (@guard_release [resource $($release:ident)::+] $owner:expr, $resource:expr) => {
+        unsafe { $($release)::+($owner, $resource) };
+    };
+
+    (@guard_deref_mut [mut] $guard:ident, $t:ty) => {
+        impl<'a> ::std::ops::DerefMut for $guard<'a>
+        where
+            $t: $crate::ForeignTypeMut,
+        {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut $t {
+                unsafe { <$t as $crate::ForeignTypeRef>::from_ptr_mut(self.resource) }
+            }
+        }
+    };
+    (@guard_deref_mut [] $guard:ident, $t:ty) => {};
+
+    (
+        @guard_fn [(error_source $e:ty, $capture:expr) $($opts:tt)*]
+        $attrs:tt $vis:vis $name:ident, $guard:ident, $t:ty, $borrowed:ident, $acquire:expr
+    ) => {
+        $crate::__foreign_type_impl! {
+            @guard_fn_impl $attrs $vis $name, $guard, $t, $borrowed, $acquire,
+            Result<$guard<'_>, $e>, Ok, Err($capture())
+        }
+    };
+    (
+        @guard_fn [$opt:tt $($opts:tt)*]
+        $attrs:tt $vis:vis $name:ident, $guard:ident, $t:ty, $borrowed:ident, $acquire:expr
+    ) => {
+        $crate::__foreign_type_impl! {
+            @guard_fn [$($opts)*] $attrs $vis $name, $guard, $t, $borrowed, $acquire
+        }
+    };
+    (
+        @guard_fn []
+        $attrs:tt $vis:vis $name:ident, $guard:ident, $t:ty, $borrowed:ident, $acquire:expr
+    ) => {
+        $crate::__foreign_type_impl! {
+            @guard_fn_impl $attrs $vis $name, $guard, $t, $borrowed, $acquire,
+            Option<$guard<'_>>, Some, None
+        }
+    };
+
+    (
+        @guard_fn_impl [$(#[$attr:meta])*] $vis:vis $name:ident, $guard:ident, $t:ty,
+        $borrowed:ident, $acquire:expr, $ret:ty, $ok:ident, $err:expr
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&mut self) -> $ret {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                let resource = unsafe { $acquire($crate::ForeignTypeRef::as_ptr(self)) };
+                if resource == <$t as $crate::ForeignTypeRef>::INVALID {
+                    return $err;
+                }
+                $ok($guard {
+                    owner: self,
+                    resource,
+                })
+            }
+        }
+    };
+
+    (
+        @next $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> &$t:ty = $f:expr;
+        $($cursor:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&mut self) -> Option<&$t> {
+                unsafe {
+                    let ptr = $f($crate::ForeignTypeRef::as_ptr(self));
+                    <$t as $crate::ForeignTypeRef>::from_ptr_opt(ptr)
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @next $borrowed; $($cursor)* }
+    };
+    (
+        @next $borrowed:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident -> owned $t:ty = $f:expr;
+        $($cursor:tt)*
+    ) => {
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name<'a>(&'a mut self) -> impl Iterator<Item = $t> + 'a {
+                ::std::iter::from_fn(move || unsafe {
+                    let ptr = $f($crate::ForeignTypeRef::as_ptr(&*self));
+                    <$t as $crate::ForeignType>::from_ptr_opt(ptr)
+                })
+            }
+        }
+
+        $crate::__foreign_type_impl! { @next $borrowed; $($cursor)* }
+    };
+    (@next $borrowed:ident;) => {};
+
+    (
+        @list_cursor [(list_cursor {
+            $(#[$attr:meta])* $vis:vis fn $name:ident -> $cursor:ident<$item:ty>;
+            fn next = $next:expr;
+            fn prev = $prev:expr;
+            fn remove = $remove:expr;
+            fn insert = $insert:expr;
+        }) $($opts:tt)*] $all:tt $borrowed:ident
+    ) => {
+        $crate::__foreign_type_impl! { @require_mut $all "`list_cursor`" }
+
+        /// A cursor over the items of a list, which can remove and insert items.
+        ///
+        /// The cursor is either at an item, or past the end of the list.
+        $vis struct $cursor<'a> {
+            list: &'a mut $borrowed,
+            current: *mut <$item as $crate::ForeignType>::CType,
+        }
+
+        impl<'a> $cursor<'a> {
+            /// Returns the item at the cursor, or `None` if the cursor is past the end of the list.
+            #[inline]
+            pub fn current(&mut self) -> Option<&mut <$item as $crate::ForeignType>::Ref>
+            where
+                <$item as $crate::ForeignType>::Ref: $crate::ForeignTypeMut,
+            {
+                unsafe { $crate::ForeignTypeRef::from_ptr_mut_opt(self.current) }
+            }
+
+            /// Moves the cursor to the next item.
+            ///
+            /// If the cursor is at the last item it moves past the end of the list, and if it is
+            /// past the end it moves to the first item.
+            #[inline]
+            pub fn move_next(&mut self) {
+                let list = $crate::ForeignTypeRef::as_ptr(&*self.list);
+                self.current = unsafe { $next(list, self.current) };
+            }
+
+            /// Moves the cursor to the previous item.
+            ///
+            /// If the cursor is at the first item it moves past the end of the list, and if it is
+            /// past the end it moves to the last item.
+            #[inline]
+            pub fn move_prev(&mut self) {
+                let list = $crate::ForeignTypeRef::as_ptr(&*self.list);
+                self.current = unsafe { $prev(list, self.current) };
+            }
+
+            /// Removes the item at the cursor from the list and returns it, moving the cursor to
+            /// the next item.
+            ///
+            /// Returns `None` if the cursor is past the end of the list.
+            #[inline]
+            pub fn remove_current(&mut self) -> Option<$item> {
+                if self.current == <$item as $crate::ForeignType>::INVALID {
+                    return None;
+                }
+                let list = $crate::ForeignTypeRef::as_ptr(&*self.list);
+                let item = self.current;
+                unsafe {
+                    self.current = $next(list, item);
+                    $remove(list, item);
+                    Some($crate::ForeignType::from_ptr(item))
+                }
+            }
+
+            /// Inserts an item before the item at the cursor, or at the end of the list if the
+            /// cursor is past the end.
+            #[inline]
+            pub fn insert_before(&mut self, item: $item) {
+                let list = $crate::ForeignTypeRef::as_ptr(&*self.list);
+                let item = $crate::ForeignType::into_ptr(item);
+                unsafe { $insert(list, self.current, item) };
+            }
+        }
+
+        impl $borrowed {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(&mut self) -> $cursor<'_> {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                let current = unsafe {
+                    $next($crate::ForeignTypeRef::as_ptr(self), <$item as $crate::ForeignType>::INVALID)
+                };
+                $cursor {
+                    list: self,
+                    current,
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @list_cursor [$($opts)*] $all $borrowed }
+    };
+    (@list_cursor [$opt:tt $($opts:tt)*] $all:tt $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @list_cursor [$($opts)*] $all $borrowed }
+    };
+    (@list_cursor [] $all:tt $borrowed:ident) => {};
+
+    (
+        @ptr [(ptr { $(#[$attr:meta])* $vis:vis struct $ptr:ident; }) $($opts:tt)*]
+        $all:tt $ctype:ty, $owned:ident, $borrowed:ident
+    ) => {
+        $(#[$attr])*
+        #[repr(transparent)]
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        $vis struct $ptr(*mut $ctype);
+
+        impl $ptr {
+            /// Returns a null pointer, or the type's `INVALID` value if it overrides it.
+            #[inline]
+            pub const fn null() -> $ptr {
+                $ptr(<$owned as $crate::ForeignType>::INVALID)
+            }
+
+            /// Wraps a raw pointer.
+            #[inline]
+            pub const fn from_raw(ptr: *mut $ctype) -> $ptr {
+                $ptr(ptr)
+            }
+
+            /// Returns the raw pointer.
+            #[inline]
+            pub const fn as_raw(self) -> *mut $ctype {
+                self.0
+            }
+
+            /// Returns `true` if the pointer is null, or the type's `INVALID` value if it
+            /// overrides it.
+            #[inline]
+            pub fn is_null(self) -> bool {
+                self.0 == <$owned as $crate::ForeignType>::INVALID
+            }
+
+            /// Returns a shared reference to the object, or `None` if the pointer is null.
+            ///
+            /// # Safety
+            ///
+            /// The pointer must either be null or point to a valid instance of the C type, which
+            /// is not mutated for the lifetime `'a`.
+            #[inline]
+            pub unsafe fn as_ref<'a>(self) -> Option<&'a $borrowed> {
+                $crate::ForeignTypeRef::from_ptr_opt(self.0)
+            }
+
+            $crate::__foreign_type_impl! { @if_mut $all {
+                /// Returns a mutable reference to the object, or `None` if the pointer is null.
+                ///
+                /// # Safety
+                ///
+                /// The pointer must either be null or point to a valid instance of the C type,
+                /// which is not otherwise accessed for the lifetime `'a`.
+                #[inline]
+                pub unsafe fn as_mut<'a>(self) -> Option<&'a mut $borrowed> {
+                    $crate::ForeignTypeRef::from_ptr_mut_opt(self.0)
+                }
+            } }
+
+            /// Takes ownership of the object, or returns `None` if the pointer is null.
+            ///
+            /// # Safety
+            ///
+            /// The pointer must either be null or point to a valid instance of the C type which is
+            /// owned by the caller, such as one produced by `From<Owned>`. No other copy of the
+            /// pointer may be used afterwards.
+            #[inline]
+            pub unsafe fn into_owned(self) -> Option<$owned> {
+                $crate::ForeignType::from_ptr_opt(self.0)
+            }
+        }
+
+        impl Default for $ptr {
+            #[inline]
+            fn default() -> $ptr {
+                $ptr::null()
+            }
+        }
+
+        impl ::std::convert::From<$owned> for $ptr {
+            /// Releases ownership of the object into the pointer.
+            #[inline]
+            fn from(owned: $owned) -> $ptr {
+                $ptr($crate::ForeignType::into_ptr(owned))
+            }
+        }
+
+        impl<'a> ::std::convert::From<&'a $borrowed> for $ptr {
+            #[inline]
+            fn from(borrowed: &'a $borrowed) -> $ptr {
+                $ptr($crate::ForeignTypeRef::as_ptr(borrowed))
+            }
+        }
+
+        impl<'a> ::std::convert::From<&'a mut $borrowed> for $ptr {
+            #[inline]
+            fn from(borrowed: &'a mut $borrowed) -> $ptr {
+                $ptr($crate::ForeignTypeRef::as_ptr(borrowed))
+            }
+        }
+
+        impl $crate::AsForeignPtr<$ctype> for $ptr {
+            #[inline]
+            fn as_foreign_ptr(&self) -> *mut $ctype {
+                self.0
+            }
+        }
+    };
+    (@ptr [$opt:tt $($opts:tt)*] $all:tt $ctype:ty, $owned:ident, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @ptr [$($opts)*] $all $ctype, $owned, $borrowed }
+    };
+    (@ptr [] $all:tt $ctype:ty, $owned:ident, $borrowed:ident) => {};
+
+    (
+        @collect [(collect { fn new = $new:expr; $($pushes:tt)* }) $($opts:tt)*]
+        $all:tt $owned:ident, $borrowed:ident
+    ) => {
+        $crate::__foreign_type_impl! { @require_mut $all "`collect`" }
+        $crate::__foreign_type_impl! { @push [$new] $owned, $borrowed; $($pushes)* }
+        $crate::__foreign_type_impl! { @collect [$($opts)*] $all $owned, $borrowed }
+    };
+    (@collect [(collect { $($pushes:tt)* }) $($opts:tt)*] $all:tt $owned:ident, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @require_mut $all "`collect`" }
+        $crate::__foreign_type_impl! { @push [] $owned, $borrowed; $($pushes)* }
+        $crate::__foreign_type_impl! { @collect [$($opts)*] $all $owned, $borrowed }
+    };
+    (@collect [$opt:tt $($opts:tt)*] $all:tt $owned:ident, $borrowed:ident) => {
+        $crate::__foreign_type_impl! { @collect [$($opts)*] $all $owned, $borrowed }
+    };
+    (@collect [] $all:tt $owned:ident, $borrowed:ident) => {};
+
+    (
+        @push [$($new:expr)*] $owned:ident, $borrowed:ident;
+        fn push(owned $t:ty) = $f:expr;
+        $($pushes:tt)*
+    ) => {
+        impl ::std::iter::Extend<$t> for $borrowed {
+            #[inline]
+            fn extend<I>(&mut self, iter: I)
+            where
+                I: ::std::iter::IntoIterator<Item = $t>,
+            {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                let ptr = $crate::ForeignTypeRef::as_ptr(self);
+                for value in iter {
+                    let value = <$t as $crate::ForeignType>::into_ptr(value);
+                    unsafe { $f(ptr, value) };
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @push_owned [$($new)*] $owned, $borrowed, $t }
+        $crate::__foreign_type_impl! { @push [$($new)*] $owned, $borrowed; $($pushes)* }
+    };
+    (
+        @push [$($new:expr)*] $owned:ident, $borrowed:ident;
+        fn push(&$t:ty) = $f:expr;
+        $($pushes:tt)*
+    ) => {
+        impl<'a> ::std::iter::Extend<&'a $t> for $borrowed {
+            #[inline]
+            fn extend<I>(&mut self, iter: I)
+            where
+                I: ::std::iter::IntoIterator<Item = &'a $t>,
+            {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                let ptr = $crate::ForeignTypeRef::as_ptr(self);
+                for value in iter {
+                    let value = <$t as $crate::ForeignTypeRef>::as_ptr(value);
+                    unsafe { $f(ptr, value) };
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @push_owned [$($new)*] $owned, $borrowed, &'a $t }
+        $crate::__foreign_type_impl! { @push [$($new)*] $owned, $borrowed; $($pushes)* }
+    };
+    (
+        @push [$($new:expr)*] $owned:ident, $borrowed:ident;
+        fn push($t:ty) = $f:expr;
+        $($pushes:tt)*
+    ) => {
+        impl ::std::iter::Extend<$t> for $borrowed {
+            #[inline]
+            fn extend<I>(&mut self, iter: I)
+            where
+                I: ::std::iter::IntoIterator<Item = $t>,
+            {
+                $crate::__foreign_type_borrow! { exclusive $borrowed, $crate::ForeignTypeRef::as_ptr(self) }
+                let ptr = $crate::ForeignTypeRef::as_ptr(self);
+                for value in iter {
+                    unsafe { $f(ptr, value) };
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @push_owned [$($new)*] $owned, $borrowed, $t }
+        $crate::__foreign_type_impl! { @push [$($new)*] $owned, $borrowed; $($pushes)* }
+    };
+    (@push [$($new:expr)*] $owned:ident, $borrowed:ident;) => {};
+
+    (@push_owned [$($new:expr)*] $owned:ident, $borrowed:ident, $item:ty) => {
+        impl<'a> ::std::iter::Extend<$item> for $owned {
+            #[inline]
+            fn extend<I>(&mut self, iter: I)
+            where
+                I: ::std::iter::IntoIterator<Item = $item>,
+            {
+                let ptr = $crate::ForeignType::as_ptr(self);
+                let borrowed = unsafe { <$borrowed as $crate::ForeignTypeRef>::from_ptr_mut(ptr) };
+                borrowed.extend(iter);
+            }
+        }
+
+        $(
+            impl<'a> ::std::iter::FromIterator<$item> for $owned {
+                #[inline]
+                fn from_iter<I>(iter: I) -> $owned
+                where
+                    I: ::std::iter::IntoIterator<Item = $item>,
+                {
+                    let mut owned = unsafe {
+                        let ptr = $new();
+                        match $crate::ForeignType::from_ptr_opt(ptr) {
+                            Some(owned) => owned,
+                            None => panic!("failed to create value"),
+                        }
+                    };
+                    ::std::iter::Extend::extend(&mut owned, iter);
+                    owned
+                }
+            }
+        )*
+    };
+
+    (@converts [(converts { $($converts:tt)* }) $($opts:tt)*] $all:tt) => {
+        $crate::__foreign_type_impl! { @convert $all; $($converts)* }
+        $crate::__foreign_type_impl! { @converts [$($opts)*] $all }
+    };
+    (@converts [$opt:tt $($opts:tt)*] $all:tt) => {
+        $crate::__foreign_type_impl! { @converts [$($opts)*] $all }
+    };
+    (@converts [] $all:tt) => {};
+
+    (@convert $all:tt; fn from(owned $src:ty) -> $dst:ty = $f:expr; $($converts:tt)*) => {
+        impl ::std::convert::From<$src> for $dst {
+            #[inline]
+            fn from(value: $src) -> $dst {
+                unsafe {
+                    let ptr = $f(<$src as $crate::ForeignType>::into_ptr(value));
+                    match $crate::ForeignType::from_ptr_opt(ptr) {
+                        Some(value) => value,
+                        None => panic!("failed to convert value"),
+                    }
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @convert $all; $($converts)* }
+    };
+    (@convert $all:tt; fn from(&$src:ty) -> $dst:ty = $f:expr; $($converts:tt)*) => {
+        impl<'a> ::std::convert::From<&'a $src> for $dst {
+            #[inline]
+            fn from(value: &'a $src) -> $dst {
+                unsafe {
+                    let ptr = $f(<$src as $crate::ForeignTypeRef>::as_ptr(value));
+                    match $crate::ForeignType::from_ptr_opt(ptr) {
+                        Some(value) => value,
+                        None => panic!("failed to convert value"),
+                    }
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @convert $all; $($converts)* }
+    };
+    (@convert $all:tt; fn try_from(owned $src:ty) -> $dst:ty = $f:expr; $($converts:tt)*) => {
+        $crate::__foreign_type_impl! { @try_convert $all [owned] $src, $dst, $f }
+        $crate::__foreign_type_impl! { @convert $all; $($converts)* }
+    };
+    (@convert $all:tt; fn try_from(&$src:ty) -> $dst:ty = $f:expr; $($converts:tt)*) => {
+        $crate::__foreign_type_impl! { @try_convert $all [&] $src, $dst, $f }
+        $crate::__foreign_type_impl! { @convert $all; $($converts)* }
+    };
+    (@convert $all:tt;) => {};
+
+    (
+        @try_convert [(error_source $e:ty, $capture:expr) $($opts:tt)*]
+        $kind:tt $src:ty, $dst:ty, $f:expr
+    ) => {
+        $crate::__foreign_type_impl! { @try_convert_impl $kind $src, $dst, $f, $e, $capture() }
+    };
+    (@try_convert [$opt:tt $($opts:tt)*] $kind:tt $src:ty, $dst:ty, $f:expr) => {
+        $crate::__foreign_type_impl! { @try_convert [$($opts)*] $kind $src, $dst, $f }
+    };
+    (@try_convert [] $kind:tt $src:ty, $dst:ty, $f:expr) => {
+        $crate::__foreign_type_impl! {
+            @try_convert_impl $kind $src, $dst, $f, $crate::ConvertError, $crate::ConvertError::__new()
+        }
+    };
+
+    (@try_convert_impl [owned] $src:ty, $dst:ty, $f:expr, $e:ty, $error:expr) => {
+        impl ::std::convert::TryFrom<$src> for $dst {
+            type Error = $e;
+
+            #[inline]
+            fn try_from(value: $src) -> Result<$dst, $e> {
+                unsafe {
+                    let ptr = $f(<$src as $crate::ForeignType>::into_ptr(value));
+                    match $crate::ForeignType::from_ptr_opt(ptr) {
+                        Some(value) => Ok(value),
+                        None => Err($error),
+                    }
+                }
+            }
+        }
+    };
+    (@try_convert_impl [&] $src:ty, $dst:ty, $f:expr, $e:ty, $error:expr) => {
+        impl<'a> ::std::convert::TryFrom<&'a $src> for $dst {
+            type Error = $e;
+
+            #[inline]
+            fn try_from(value: &'a $src) -> Result<$dst, $e> {
+                unsafe {
+                    let ptr = $f(<$src as $crate::ForeignTypeRef>::as_ptr(value));
+                    match $crate::ForeignType::from_ptr_opt(ptr) {
+                        Some(value) => Ok(value),
+                        None => Err($error),
+                    }
+                }
+            }
+        }
+    };
+
+    (
+        @builder [(builder {
+            $(#[$attr:meta])*
+            $vis:vis struct $builder:ident;
+            fn new = $new:expr;
+            fn build -> Result<$o:ty, $e:ty> = $build:expr;
+            $($setters:tt)*
+        }) $($opts:tt)*] $owned:ident
+    ) => {
+        $crate::__foreign_type_impl! { @builder_struct [$(#[$attr])*] $vis $builder, $owned, $new }
+
+        impl $builder {
+            /// Finalizes the value, returning it if successful.
+            #[inline]
+            pub fn build(self) -> Result<$o, $e> {
+                let ptr = $crate::ForeignType::as_ptr(&self.0);
+                match unsafe { $build(ptr) } {
+                    Ok(()) => Ok(self.0),
+                    Err(e) => Err(e),
+                }
+            }
+        }
+
+        $crate::__foreign_type_impl! { @builder_setter $builder; $($setters)* }
+        $crate::__foreign_type_impl! { @builder [$($opts)*] $owned }
+    };
+    (
+        @builder [(builder {
+            $(#[$attr:meta])*
+            $vis:vis struct $builder:ident;
+            fn new = $new:expr;
+            $($setters:tt)*
+        }) $($opts:tt)*] $owned:ident
+    ) => {
+        $crate::__foreign_type_impl! { @builder_struct [$(#[$attr])*] $vis $builder, $owned, $new }
+
+        impl $builder {
+            /// Returns the configured value.
+            #[inline]
+            pub fn build(self) -> $owned {
+                self.0
+            }
+        }
+
+        $crate::__foreign_type_impl! { @builder_setter $builder; $($setters)* }
+        $crate::__foreign_type_impl! { @builder [$($opts)*] $owned }
+    };
+    (@builder [$opt:tt $($opts:tt)*] $owned:ident) => {
+        $crate::__foreign_type_impl! { @builder [$($opts)*] $owned }
+    };
+    (@builder [] $owned:ident) => {};
+
+    (@builder_struct [$($attr:tt)*] $vis:vis $builder:ident, $owned:ident, $new:expr) => {
+        $($attr)*
+        #[must_use]
+        $vis struct $builder($owned);
+
+        impl $builder {
+            /// Creates a new builder.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the C constructor fails. Use `try_new` to handle the failure instead.
+            #[inline]
+            pub fn new() -> $builder {
+                match $builder::try_new() {
+                    Some(builder) => builder,
+                    None => panic!("failed to create value"),
+                }
+            }
+
+            /// Creates a new builder, returning `None` if the C constructor fails.
+            #[inline]
+            pub fn try_new() -> Option<$builder> {
+                unsafe {
+                    let ptr = $new();
+                    $crate::ForeignType::from_ptr_opt(ptr).map($builder)
+                }
+            }
+        }
+
+        impl Default for $builder {
+            /// Creates a new builder.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the C constructor fails.
+            #[inline]
+            fn default() -> $builder {
+                $builder::new()
+            }
+        }
+    };
+
+    (
+        @builder_setter $builder:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident(owned $t:ty) = $f:expr;
+        $($setters:tt)*
+    ) => {
+        impl $builder {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(self, value: $t) -> $builder {
+                let value = <$t as $crate::ForeignType>::into_ptr(value);
+                unsafe { $f($crate::ForeignType::as_ptr(&self.0), value) }
+                self
+            }
+        }
+
+        $crate::__foreign_type_impl! { @builder_setter $builder; $($setters)* }
+    };
+    (
+        @builder_setter $builder:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident(&$t:ty) = $f:expr;
+        $($setters:tt)*
+    ) => {
+        impl $builder {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(self, value: &$t) -> $builder {
+                let value = <$t as $crate::ForeignTypeRef>::as_ptr(value);
+                unsafe { $f($crate::ForeignType::as_ptr(&self.0), value) }
+                self
+            }
+        }
+
+        $crate::__foreign_type_impl! { @builder_setter $builder; $($setters)* }
+    };
+    (
+        @builder_setter $builder:ident;
+        $(#[$attr:meta])* $vis:vis fn $name:ident($t:ty) = $f:expr;
+        $($setters:tt)*
+    ) => {
+        impl $builder {
+            $(#[$attr])*
+            #[inline]
+            $vis fn $name(self, value: $t) -> $builder {
+                unsafe { $f($crate::ForeignType::as_ptr(&self.0), value) }
+                self
+            }
+        }
+
+        $crate::__foreign_type_impl! { @builder_setter $builder; $($setters)* }
+    };
+    (@builder_setter $builder:ident;) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "arbitrary")]
+macro_rules! __foreign_type_arbitrary {
+    ($owned:ident) => {
+        impl $owned {
+            #[doc(hidden)]
+            #[inline]
+            fn __arbitrary_from_bytes(bytes: &[u8]) -> $crate::__private::arbitrary::Result<$owned> {
+                match $owned::from_bytes(bytes) {
+                    Ok(owned) => Ok(owned),
+                    Err(_) => Err($crate::__private::arbitrary::Error::IncorrectFormat),
+                }
+            }
+        }
+
+        impl<'a> $crate::__private::arbitrary::Arbitrary<'a> for $owned {
+            #[inline]
+            fn arbitrary(u: &mut $crate::__private::arbitrary::Unstructured<'a>) -> $crate::__private::arbitrary::Result<$owned> {
+                let bytes = <&'a [u8] as $crate::__private::arbitrary::Arbitrary<'a>>::arbitrary(u)?;
+                $owned::__arbitrary_from_bytes(bytes)
+            }
+
+            #[inline]
+            fn arbitrary_take_rest(u: $crate::__private::arbitrary::Unstructured<'a>) -> $crate::__private::arbitrary::Result<$owned> {
+                $owned::__arbitrary_from_bytes(u.take_rest())
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "arbitrary"))]
+macro_rules! __foreign_type_arbitrary {
+    ($owned:ident) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "stable_deref_trait")]
+macro_rules! __foreign_type_stable_deref {
+    ($owned:ident) => {
+        // The owned type only holds a pointer to the C object, so moving it never moves the target
+        // of its `Deref` implementation.
+        unsafe impl $crate::__private::stable_deref_trait::StableDeref for $owned {}
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "stable_deref_trait"))]
+macro_rules! __foreign_type_stable_deref {
+    ($owned:ident) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "stats")]
+macro_rules! __foreign_type_stats {
+    (@impl $owned:ident) => {
+        impl $crate::stats::Accounted for $owned {
+            #[inline]
+            fn counters() -> &'static $crate::stats::Counters {
+                static COUNTERS: $crate::stats::Counters = $crate::stats::Counters::new();
+                &COUNTERS
+            }
+        }
+    };
+    (@create $owned:ident, $sizeof:expr, $ptr:expr) => {
+        $crate::stats::__record_create::<$owned>($ptr as *mut _, unsafe { $sizeof($ptr) } as usize);
+    };
+    (@release $owned:ident, $ptr:expr) => {
+        $crate::stats::__record_release::<$owned>($ptr as *mut _);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "stats"))]
+macro_rules! __foreign_type_stats {
+    (@impl $owned:ident) => {};
+    (@create $owned:ident, $sizeof:expr, $ptr:expr) => {};
+    (@release $owned:ident, $ptr:expr) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "hooks")]
+macro_rules! __foreign_type_hooks {
+    (@create $owned:ident, $ptr:expr) => {
+        $crate::hooks::__created(::std::any::type_name::<$owned>(), $ptr as *mut _);
+    };
+    (@release $owned:ident, $ptr:expr) => {
+        $crate::hooks::__dropped(::std::any::type_name::<$owned>(), $ptr as *mut _);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "hooks"))]
+macro_rules! __foreign_type_hooks {
+    (@create $owned:ident, $ptr:expr) => {};
+    (@release $owned:ident, $ptr:expr) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "debug-children")]
+macro_rules! __foreign_type_children {
+    ($owned:ident, $ptr:expr) => {
+        $crate::children::__check_parent(::std::any::type_name::<$owned>(), $ptr as *mut _);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "debug-children"))]
+macro_rules! __foreign_type_children {
+    ($owned:ident, $ptr:expr) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "debug-borrows")]
+macro_rules! __foreign_type_borrow {
+    (shared $t:ident, $ptr:expr) => {
+        let _borrow = $crate::borrows::__shared(::std::any::type_name::<$t>(), $ptr as *mut _);
+    };
+    (exclusive $t:ident, $ptr:expr) => {
+        let _borrow = $crate::borrows::__exclusive(::std::any::type_name::<$t>(), $ptr as *mut _);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "debug-borrows"))]
+macro_rules! __foreign_type_borrow {
+    (shared $t:ident, $ptr:expr) => {};
+    (exclusive $t:ident, $ptr:expr) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "shutdown")]
+macro_rules! __foreign_type_shutdown {
+    (@impl $owned:ident) => {
+        impl $crate::shutdown::Registered for $owned {
+            #[inline]
+            fn registry() -> &'static $crate::shutdown::Registry {
+                static REGISTRY: $crate::shutdown::Registry = $crate::shutdown::Registry::new();
+                &REGISTRY
+            }
+
+            #[inline]
+            unsafe fn __force_drop(ptr: *mut <$owned as $crate::ForeignType>::CType) {
+                drop($owned(ptr));
+            }
+        }
+    };
+    (@create $owned:ident, $ptr:expr) => {
+        $crate::shutdown::__register::<$owned>($ptr as *mut _);
+    };
+    (@release $owned:ident, $ptr:expr) => {
+        $crate::shutdown::__deregister::<$owned>($ptr as *mut _);
+    };
+    // Values whose objects were freed by `force_drop_all` are not freed again.
+    (@drop $owned:ident, $ptr:expr, $body:block) => {
+        if $crate::shutdown::__should_drop::<$owned>($ptr as *mut _) $body
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "shutdown"))]
+macro_rules! __foreign_type_shutdown {
+    (@impl $owned:ident) => {};
+    (@create $owned:ident, $ptr:expr) => {};
+    (@release $owned:ident, $ptr:expr) => {};
+    (@drop $owned:ident, $ptr:expr, $body:block) => {
+        $body
+    };
 }
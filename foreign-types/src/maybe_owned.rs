@@ -0,0 +1,211 @@
+//! Values whose ownership is decided at runtime.
+//!
+//! Some C APIs transfer responsibility for freeing an object depending on runtime conditions. For
+//! example, OpenSSL's `SSL_set_bio` takes ownership of the `BIO`s passed to it, after which the
+//! caller must no longer free them, but may still use them while the `SSL` is alive. `MaybeOwned`
+//! carries a flag recording whether it is still responsible for freeing its value.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::maybe_owned::MaybeOwned;
+//!
+//! # mod bio_sys {
+//! #     pub struct BIO;
+//! #     pub unsafe fn BIO_new() -> *mut BIO { Box::into_raw(Box::new(BIO)) }
+//! #     pub unsafe extern "C" fn BIO_free(bio: *mut BIO) { drop(Box::from_raw(bio)) }
+//! # }
+//! foreign_type! {
+//!     type CType = bio_sys::BIO;
+//!     fn drop = bio_sys::BIO_free;
+//!     /// An I/O stream.
+//!     pub struct Bio;
+//!     /// A borrowed I/O stream.
+//!     pub struct BioRef;
+//! }
+//!
+//! # fn main() {
+//! use foreign_types::ForeignType;
+//!
+//! let mut bio = MaybeOwned::new(unsafe { Bio::from_ptr(bio_sys::BIO_new()) });
+//! assert!(bio.is_owned());
+//!
+//! // Hand the stream to a C object which will free it.
+//! let ptr = bio.as_ptr();
+//! bio.disown();
+//! assert!(!bio.is_owned());
+//!
+//! drop(bio);
+//! # unsafe { bio_sys::BIO_free(ptr) };
+//! # }
+//! ```
+use core::fmt;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+
+use {AsForeignPtr, ForeignType, ForeignTypeMut, ForeignTypeRef};
+
+/// A value which is freed when dropped only if it is still owned.
+///
+/// While it is owned, the value is held as an owned `T`, so it is counted as a live value by the
+/// `stats` and `shutdown` modules. Disowning the value releases it with `into_ptr`, and taking
+/// ownership of it again recreates it with `from_ptr`.
+pub struct MaybeOwned<T>
+where
+    T: ForeignType,
+{
+    inner: Inner<T>,
+}
+
+enum Inner<T>
+where
+    T: ForeignType,
+{
+    Owned(T),
+    Unowned(*mut T::CType),
+}
+
+unsafe impl<T> Send for MaybeOwned<T> where T: ForeignType + Send + Sync {}
+
+unsafe impl<T> Sync for MaybeOwned<T> where T: ForeignType + Sync {}
+
+impl<T> MaybeOwned<T>
+where
+    T: ForeignType,
+{
+    /// Wraps an owned value.
+    #[inline]
+    pub fn new(value: T) -> MaybeOwned<T> {
+        MaybeOwned {
+            inner: Inner::Owned(value),
+        }
+    }
+
+    /// Wraps a pointer, which is freed when dropped if `owned` is `true`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid instance of the C type for the lifetime of the returned value. If
+    /// `owned` is `true`, it must be owned by the caller.
+    #[inline]
+    pub unsafe fn from_ptr(ptr: *mut T::CType, owned: bool) -> MaybeOwned<T> {
+        let inner = if owned {
+            Inner::Owned(T::from_ptr(ptr))
+        } else {
+            Inner::Unowned(ptr)
+        };
+        MaybeOwned { inner }
+    }
+
+    /// Returns `true` if the value will be freed when this is dropped.
+    #[inline]
+    pub fn is_owned(&self) -> bool {
+        match self.inner {
+            Inner::Owned(_) => true,
+            Inner::Unowned(_) => false,
+        }
+    }
+
+    /// Gives up responsibility for freeing the value.
+    ///
+    /// This is typically called after passing the value to a C function which takes ownership of
+    /// it. The value can still be used for as long as the C library keeps it alive.
+    #[inline]
+    pub fn disown(&mut self) {
+        if self.is_owned() {
+            let ptr = self.as_ptr();
+            if let Inner::Owned(value) = mem::replace(&mut self.inner, Inner::Unowned(ptr)) {
+                value.into_ptr();
+            }
+        }
+    }
+
+    /// Takes responsibility for freeing the value.
+    ///
+    /// # Safety
+    ///
+    /// Ownership of the value must have been returned to the caller, so that nothing else will
+    /// free it.
+    #[inline]
+    pub unsafe fn assume_ownership(&mut self) {
+        if let Inner::Unowned(ptr) = self.inner {
+            self.inner = Inner::Owned(T::from_ptr(ptr));
+        }
+    }
+
+    /// Returns a raw pointer to the value.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut T::CType {
+        match self.inner {
+            Inner::Owned(ref value) => value.as_ptr(),
+            Inner::Unowned(ptr) => ptr,
+        }
+    }
+
+    /// Converts into the owned value, or returns `self` if it is not owned.
+    #[inline]
+    pub fn into_owned(self) -> Result<T, MaybeOwned<T>> {
+        match self.inner {
+            Inner::Owned(value) => Ok(value),
+            inner => Err(MaybeOwned { inner }),
+        }
+    }
+}
+
+impl<T> From<T> for MaybeOwned<T>
+where
+    T: ForeignType,
+{
+    #[inline]
+    fn from(value: T) -> MaybeOwned<T> {
+        MaybeOwned::new(value)
+    }
+}
+
+impl<T> Deref for MaybeOwned<T>
+where
+    T: ForeignType,
+{
+    type Target = T::Ref;
+
+    #[inline]
+    fn deref(&self) -> &T::Ref {
+        unsafe { T::Ref::from_ptr(self.as_ptr()) }
+    }
+}
+
+impl<T> DerefMut for MaybeOwned<T>
+where
+    T: ForeignType,
+    T::Ref: ForeignTypeMut,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T::Ref {
+        unsafe { T::Ref::from_ptr_mut(self.as_ptr()) }
+    }
+}
+
+impl<T> AsForeignPtr<T::CType> for MaybeOwned<T>
+where
+    T: ForeignType,
+{
+    #[inline]
+    fn as_foreign_ptr(&self) -> *mut T::CType {
+        self.as_ptr()
+    }
+}
+
+impl<T> fmt::Debug for MaybeOwned<T>
+where
+    T: ForeignType,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("MaybeOwned")
+            .field("ptr", &self.as_ptr())
+            .field("owned", &self.is_owned())
+            .finish()
+    }
+}
@@ -0,0 +1,333 @@
+//! Shared ownership of C objects with non-atomic reference counts.
+//!
+//! Many C objects carry their own reference count, incremented by an "up-ref" function and
+//! decremented by their free function. When the count is not updated atomically, the object can
+//! only be shared within one thread. `ForeignRc` is the counterpart of `Rc` for such objects: it is
+//! cloned by incrementing the C reference count, releases its reference when dropped, and is
+//! neither `Send` nor `Sync`.
+//!
+//! Types defined with a `fn up_ref = ...;` entry in `foreign_type!` implement `RefCounted`, and can
+//! be shared through a `ForeignRc`. Types which also have a `fn refcount_get = ...;` entry implement
+//! `RefCountGet`, which allows the number of references to be read.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::rc::ForeignRc;
+//!
+//! # mod obj_sys {
+//! #     pub struct OBJ { pub refs: u32 }
+//! #     pub unsafe fn OBJ_new() -> *mut OBJ { Box::into_raw(Box::new(OBJ { refs: 1 })) }
+//! #     pub unsafe fn OBJ_up_ref(obj: *mut OBJ) { (*obj).refs += 1 }
+//! #     pub unsafe extern "C" fn OBJ_free(obj: *mut OBJ) {
+//! #         (*obj).refs -= 1;
+//! #         if (*obj).refs == 0 { drop(Box::from_raw(obj)) }
+//! #     }
+//! #     pub unsafe fn OBJ_refs(obj: *mut OBJ) -> u32 { (*obj).refs }
+//! # }
+//! foreign_type! {
+//!     type CType = obj_sys::OBJ;
+//!     fn drop = obj_sys::OBJ_free;
+//!     fn up_ref = obj_sys::OBJ_up_ref;
+//!     fn refcount_get = obj_sys::OBJ_refs;
+//!     /// An object.
+//!     pub struct Obj;
+//!     /// A borrowed object.
+//!     pub struct ObjRef;
+//! }
+//!
+//! # fn main() {
+//! use foreign_types::ForeignType;
+//!
+//! let obj = ForeignRc::new(unsafe { Obj::from_ptr(obj_sys::OBJ_new()) });
+//! let other = obj.clone();
+//! assert!(ForeignRc::ptr_eq(&obj, &other));
+//! assert_eq!(ForeignRc::strong_count(&obj), 2);
+//!
+//! let mut obj = ForeignRc::try_unwrap(obj).err().unwrap();
+//! assert!(ForeignRc::get_mut(&mut obj).is_none());
+//!
+//! drop(other);
+//! assert_eq!(ForeignRc::strong_count(&obj), 1);
+//! assert!(ForeignRc::get_mut(&mut obj).is_some());
+//! let obj: Obj = ForeignRc::try_unwrap(obj).unwrap();
+//! # drop(obj);
+//! # }
+//! ```
+//!
+//! Types which can also be duplicated with `fn clone = ...;` support copy-on-write through
+//! `ForeignRc::make_mut`, which copies the object only if it is shared:
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::rc::ForeignRc;
+//!
+//! # mod obj_sys {
+//! #     pub struct OBJ { pub refs: u32, pub value: u32 }
+//! #     pub unsafe fn OBJ_new() -> *mut OBJ { Box::into_raw(Box::new(OBJ { refs: 1, value: 0 })) }
+//! #     pub unsafe fn OBJ_dup(obj: *mut OBJ) -> *mut OBJ { Box::into_raw(Box::new(OBJ { refs: 1, value: (*obj).value })) }
+//! #     pub unsafe fn OBJ_up_ref(obj: *mut OBJ) { (*obj).refs += 1 }
+//! #     pub unsafe extern "C" fn OBJ_free(obj: *mut OBJ) {
+//! #         (*obj).refs -= 1;
+//! #         if (*obj).refs == 0 { drop(Box::from_raw(obj)) }
+//! #     }
+//! #     pub unsafe fn OBJ_refs(obj: *mut OBJ) -> u32 { (*obj).refs }
+//! #     pub unsafe fn OBJ_value(obj: *mut OBJ) -> u32 { (*obj).value }
+//! #     pub unsafe fn OBJ_set_value(obj: *mut OBJ, value: u32) { (*obj).value = value }
+//! # }
+//! foreign_type! {
+//!     type CType = obj_sys::OBJ;
+//!     fn drop = obj_sys::OBJ_free;
+//!     fn clone = obj_sys::OBJ_dup;
+//!     fn up_ref = obj_sys::OBJ_up_ref;
+//!     fn refcount_get = obj_sys::OBJ_refs;
+//!     getters {
+//!         fn value -> u32 = obj_sys::OBJ_value;
+//!     }
+//!     setters {
+//!         fn set_value(u32) = obj_sys::OBJ_set_value;
+//!     }
+//!     /// An object.
+//!     pub struct Obj;
+//!     /// A borrowed object.
+//!     pub struct ObjRef;
+//! }
+//!
+//! # fn main() {
+//! use foreign_types::ForeignType;
+//!
+//! let mut obj = ForeignRc::new(unsafe { Obj::from_ptr(obj_sys::OBJ_new()) });
+//! let other = obj.clone();
+//!
+//! ForeignRc::make_mut(&mut obj).set_value(1);
+//! assert!(!ForeignRc::ptr_eq(&obj, &other));
+//! assert_eq!((obj.value(), other.value()), (1, 0));
+//! # }
+//! ```
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+#[cfg(feature = "std")]
+use std::borrow::ToOwned;
+
+use {ForeignType, ForeignTypeMut, ForeignTypeRef};
+
+/// A type whose C objects are reference counted.
+///
+/// This is implemented by `foreign_type!` for types with a `fn up_ref = ...;` entry. The type's
+/// `Drop` implementation must release one reference.
+pub trait RefCounted: ForeignType {
+    /// Increments the reference count of the object at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid instance of the C type.
+    unsafe fn up_ref(ptr: *mut Self::CType);
+}
+
+/// A reference counted type whose reference count can be read.
+///
+/// This is implemented by `foreign_type!` for types with a `fn refcount_get = ...;` entry.
+pub trait RefCountGet: RefCounted {
+    /// Returns the reference count of the object at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid instance of the C type.
+    unsafe fn refcount_get(ptr: *mut Self::CType) -> usize;
+}
+
+/// A single-threaded shared reference to a reference counted C object.
+///
+/// Like `Rc`, the methods of `ForeignRc` are associated functions, so that they do not shadow
+/// methods of the borrowed type.
+///
+/// Each `ForeignRc` holds an owned value of `T` for its reference, so it is counted as a live
+/// value of `T` by the `stats` and `shutdown` modules, and its reference is released by the
+/// owned value's `Drop` implementation.
+pub struct ForeignRc<T>
+where
+    T: RefCounted,
+{
+    value: ManuallyDrop<T>,
+    // The reference count is not atomic.
+    _p: PhantomData<*mut ()>,
+}
+
+impl<T> Drop for ForeignRc<T>
+where
+    T: RefCounted,
+{
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::drop(&mut self.value) }
+    }
+}
+
+impl<T> ForeignRc<T>
+where
+    T: RefCounted,
+{
+    /// Shares an owned value, taking over its reference.
+    #[inline]
+    pub fn new(value: T) -> ForeignRc<T> {
+        ForeignRc {
+            value: ManuallyDrop::new(value),
+            _p: PhantomData,
+        }
+    }
+
+    /// Takes ownership of one reference to the object at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid instance of the C type, and the caller must own one of its references.
+    #[inline]
+    pub unsafe fn from_ptr(ptr: *mut T::CType) -> ForeignRc<T> {
+        ForeignRc::new(T::from_ptr(ptr))
+    }
+
+    /// Returns a raw pointer to the object.
+    #[inline]
+    pub fn as_ptr(this: &ForeignRc<T>) -> *mut T::CType {
+        this.value.as_ptr()
+    }
+
+    /// Consumes the `ForeignRc`, returning the raw pointer along with its reference.
+    ///
+    /// The reference must be released by the caller.
+    #[inline]
+    pub fn into_ptr(this: ForeignRc<T>) -> *mut T::CType {
+        ForeignRc::into_inner(this).into_ptr()
+    }
+
+    #[inline]
+    fn into_inner(this: ForeignRc<T>) -> T {
+        let mut this = ManuallyDrop::new(this);
+        unsafe { ManuallyDrop::take(&mut this.value) }
+    }
+
+    /// Returns `true` if both values refer to the same object.
+    #[inline]
+    pub fn ptr_eq(this: &ForeignRc<T>, other: &ForeignRc<T>) -> bool {
+        ForeignRc::as_ptr(this) == ForeignRc::as_ptr(other)
+    }
+
+    /// Returns the number of references to the object.
+    ///
+    /// The count is maintained by the C library, so it includes references held by C code and
+    /// other wrappers as well as those held by `ForeignRc`s.
+    #[inline]
+    pub fn strong_count(this: &ForeignRc<T>) -> usize
+    where
+        T: RefCountGet,
+    {
+        unsafe { T::refcount_get(ForeignRc::as_ptr(this)) }
+    }
+
+    /// Returns the owned value if this is the only reference to the object, or `this` otherwise.
+    ///
+    /// Other `ForeignRc`s to the object can only exist on the current thread, but the C library
+    /// may take new references to it at any time, possibly from other threads. The returned value
+    /// is only known to be unique if the C library does not do so while it is in use.
+    #[inline]
+    pub fn try_unwrap(this: ForeignRc<T>) -> Result<T, ForeignRc<T>>
+    where
+        T: RefCountGet,
+    {
+        if ForeignRc::strong_count(&this) == 1 {
+            Ok(ForeignRc::into_inner(this))
+        } else {
+            Err(this)
+        }
+    }
+
+    /// Returns a mutable reference to the object if this is the only reference to it.
+    ///
+    /// As with `try_unwrap`, the C library must not take new references to the object while the
+    /// returned reference is alive.
+    #[inline]
+    pub fn get_mut(this: &mut ForeignRc<T>) -> Option<&mut T::Ref>
+    where
+        T: RefCountGet,
+        T::Ref: ForeignTypeMut,
+    {
+        if ForeignRc::strong_count(this) == 1 {
+            unsafe { Some(T::Ref::from_ptr_mut(ForeignRc::as_ptr(this))) }
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the object, first replacing it with a copy if it is shared.
+    ///
+    /// As with `try_unwrap`, the C library must not take new references to the object while the
+    /// returned reference is alive.
+    ///
+    /// This method requires the `std` feature, which is enabled by default.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn make_mut(this: &mut ForeignRc<T>) -> &mut T::Ref
+    where
+        T: RefCountGet,
+        T::Ref: ForeignTypeMut + ToOwned<Owned = T>,
+    {
+        if ForeignRc::strong_count(this) != 1 {
+            *this = ForeignRc::new((**this).to_owned());
+        }
+        unsafe { T::Ref::from_ptr_mut(ForeignRc::as_ptr(this)) }
+    }
+}
+
+impl<T> Clone for ForeignRc<T>
+where
+    T: RefCounted,
+{
+    #[inline]
+    fn clone(&self) -> ForeignRc<T> {
+        let ptr = ForeignRc::as_ptr(self);
+        unsafe {
+            T::up_ref(ptr);
+            ForeignRc::from_ptr(ptr)
+        }
+    }
+}
+
+impl<T> From<T> for ForeignRc<T>
+where
+    T: RefCounted,
+{
+    #[inline]
+    fn from(value: T) -> ForeignRc<T> {
+        ForeignRc::new(value)
+    }
+}
+
+impl<T> Deref for ForeignRc<T>
+where
+    T: RefCounted,
+{
+    type Target = T::Ref;
+
+    #[inline]
+    fn deref(&self) -> &T::Ref {
+        unsafe { T::Ref::from_ptr(ForeignRc::as_ptr(self)) }
+    }
+}
+
+impl<T> fmt::Debug for ForeignRc<T>
+where
+    T: RefCounted,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("ForeignRc")
+            .field(&ForeignRc::as_ptr(self))
+            .finish()
+    }
+}
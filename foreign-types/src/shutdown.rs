@@ -0,0 +1,227 @@
+//! Enforcement of teardown ordering before a library is deinitialized.
+//!
+//! Many C libraries have a global `X_deinit` function, after which none of their objects may be
+//! used or freed. Calling it while wrappers are still alive is a common source of crashes at
+//! process exit.
+//!
+//! When the `shutdown` feature is enabled, every owned value created by `foreign_type!` is
+//! registered when it is created from a pointer, and deregistered when it is dropped or converted
+//! back into a raw pointer. `assert_all_dropped` checks that no values of a type remain before the
+//! library is deinitialized, and `force_drop_all` frees any which do.
+//!
+//! Each owned value is counted separately, so clones of reference counted types which share a
+//! pointer are each registered. Values held by a `ForeignRc` or an owned `MaybeOwned` remain
+//! registered. Values of types with `fn drop = none;` are not registered, since they do not free
+//! anything.
+//!
+//! This module requires the `shutdown` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::shutdown;
+//!
+//! # mod lib_sys {
+//! #     pub struct CTX;
+//! #     pub unsafe fn CTX_new() -> *mut CTX { Box::into_raw(Box::new(CTX)) }
+//! #     pub unsafe extern "C" fn CTX_free(ctx: *mut CTX) { drop(Box::from_raw(ctx)) }
+//! #     pub unsafe fn LIB_deinit() {}
+//! # }
+//! foreign_type! {
+//!     type CType = lib_sys::CTX;
+//!     fn drop = lib_sys::CTX_free;
+//!     /// A context.
+//!     pub struct Ctx;
+//!     /// A borrowed context.
+//!     pub struct CtxRef;
+//! }
+//!
+//! # fn main() {
+//! use foreign_types::ForeignType;
+//!
+//! let ctx = unsafe { Ctx::from_ptr(lib_sys::CTX_new()) };
+//! assert_eq!(shutdown::live::<Ctx>(), 1);
+//!
+//! drop(ctx);
+//! shutdown::assert_all_dropped::<Ctx>();
+//! unsafe { lib_sys::LIB_deinit() };
+//! # }
+//! ```
+use core::ffi::c_void;
+use core::fmt;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::vec::Vec;
+
+use ForeignType;
+
+struct State {
+    // The number of live values with each pointer.
+    live: BTreeMap<usize, usize>,
+    // The number of values with each pointer whose objects were freed by `force_drop_all`, and
+    // which must not be freed again when they are dropped.
+    freed: BTreeMap<usize, usize>,
+}
+
+/// The set of live values of one type.
+pub struct Registry {
+    state: Mutex<State>,
+}
+
+impl Registry {
+    /// Returns an empty registry.
+    pub const fn new() -> Registry {
+        Registry {
+            state: Mutex::new(State {
+                live: BTreeMap::new(),
+                freed: BTreeMap::new(),
+            }),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the number of live values.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.state().live.values().sum()
+    }
+
+    /// Returns `true` if there are no live values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for Registry {
+    #[inline]
+    fn default() -> Registry {
+        Registry::new()
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Registry").field("len", &self.len()).finish()
+    }
+}
+
+/// A type whose live values are registered.
+///
+/// This is implemented by `foreign_type!` for every type when the `shutdown` feature is enabled.
+pub trait Registered: ForeignType {
+    /// Returns the registry for this type.
+    fn registry() -> &'static Registry;
+
+    #[doc(hidden)]
+    unsafe fn __force_drop(ptr: *mut Self::CType);
+}
+
+/// Returns the number of live values of type `T`.
+#[inline]
+pub fn live<T>() -> usize
+where
+    T: Registered,
+{
+    T::registry().len()
+}
+
+/// Panics if any values of type `T` are still alive.
+pub fn assert_all_dropped<T>()
+where
+    T: Registered,
+{
+    let live = live::<T>();
+    if live != 0 {
+        panic!(
+            "{} values of {} are still alive",
+            live,
+            core::any::type_name::<T>()
+        );
+    }
+}
+
+/// Frees every live value of type `T`, returning the number of values freed.
+///
+/// The destructor is called once for each value, so that every reference held by clones of a
+/// reference counted object is released. The wrappers of the freed values are left in place, and
+/// do nothing when they are dropped.
+///
+/// # Safety
+///
+/// The wrappers of the freed values must not be used afterwards, except to be dropped. In
+/// particular, they must not be dereferenced, destroyed with `destroy`, or converted into raw
+/// pointers. No new values of `T` may be created while they are alive, since a new object at the
+/// same address would be freed when they are dropped.
+pub unsafe fn force_drop_all<T>() -> usize
+where
+    T: Registered,
+{
+    let registry = T::registry();
+    let live = registry
+        .state()
+        .live
+        .iter()
+        .map(|(&ptr, &count)| (ptr, count))
+        .collect::<Vec<_>>();
+    let mut freed = 0;
+    for &(ptr, count) in &live {
+        // Each call releases one value, which deregisters it.
+        for _ in 0..count {
+            T::__force_drop(ptr as *mut T::CType);
+        }
+        *registry.state().freed.entry(ptr).or_insert(0) += count;
+        freed += count;
+    }
+    freed
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __register<T>(ptr: *mut c_void)
+where
+    T: Registered,
+{
+    *T::registry().state().live.entry(ptr as usize).or_insert(0) += 1;
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __deregister<T>(ptr: *mut c_void)
+where
+    T: Registered,
+{
+    let mut state = T::registry().state();
+    if let Some(count) = state.live.get_mut(&(ptr as usize)) {
+        *count -= 1;
+        if *count == 0 {
+            state.live.remove(&(ptr as usize));
+        }
+    }
+}
+
+// Returns `false` for a value whose object was freed by `force_drop_all`.
+#[doc(hidden)]
+#[inline]
+pub fn __should_drop<T>(ptr: *mut c_void) -> bool
+where
+    T: Registered,
+{
+    let mut state = T::registry().state();
+    match state.freed.get_mut(&(ptr as usize)) {
+        Some(count) => {
+            *count -= 1;
+            if *count == 0 {
+                state.freed.remove(&(ptr as usize));
+            }
+            false
+        }
+        None => true,
+    }
+}
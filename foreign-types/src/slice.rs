@@ -0,0 +1,523 @@
+//! Views over contiguous arrays of C objects.
+//!
+//! Some C APIs return an array of structs, rather than an array of pointers, along with its
+//! length. The borrowed types generated by `foreign_type!` are zero-sized, so such an array cannot
+//! be viewed as a slice of them. `ForeignSlice` and `ForeignSliceMut` instead step through the
+//! array by the size of the C type, yielding a reference to the borrowed type for each element.
+//!
+//! The C type must be sized, so the layout of the struct must be known to Rust. Opaque types, which
+//! are declared as zero-sized types, are rejected.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::slice::ForeignSlice;
+//!
+//! mod point_sys {
+//!     #[repr(C)]
+//!     pub struct POINT {
+//!         pub x: i32,
+//!         pub y: i32,
+//!     }
+//! #   pub unsafe extern "C" fn POINT_free(_: *mut POINT) {}
+//! #   pub unsafe fn POINT_x(point: *mut POINT) -> i32 { (*point).x }
+//! #   pub unsafe fn SHAPE_points(len: *mut usize) -> *mut POINT {
+//! #       static mut POINTS: [POINT; 3] = [POINT { x: 1, y: 2 }, POINT { x: 3, y: 4 }, POINT { x: 5, y: 6 }];
+//! #       *len = 3;
+//! #       std::ptr::addr_of_mut!(POINTS) as *mut POINT
+//! #   }
+//! }
+//!
+//! foreign_type! {
+//!     type CType = point_sys::POINT;
+//!     fn drop = point_sys::POINT_free;
+//!     getters {
+//!         fn x -> i32 = point_sys::POINT_x;
+//!     }
+//!     /// A point.
+//!     pub struct Point;
+//!     /// A borrowed point.
+//!     pub struct PointRef;
+//! }
+//!
+//! fn points() -> ForeignSlice<'static, PointRef> {
+//!     let mut len = 0;
+//!     unsafe {
+//!         let ptr = point_sys::SHAPE_points(&mut len);
+//!         ForeignSlice::from_raw_parts(ptr, len)
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let points = points();
+//! assert_eq!(points.len(), 3);
+//! assert_eq!(points[1].x(), 3);
+//! assert_eq!(points.iter().map(|point| point.x()).sum::<i32>(), 9);
+//! assert_eq!(points.as_c_slice()[2].y, 6);
+//! # }
+//! ```
+use core::fmt;
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Index, IndexMut};
+use core::ptr::NonNull;
+use core::slice;
+
+use {ForeignTypeMut, ForeignTypeRef};
+
+fn check<R>(ptr: *mut R::CType, len: usize) -> NonNull<R::CType>
+where
+    R: ForeignTypeRef,
+{
+    let size = mem::size_of::<R::CType>();
+    assert!(size != 0, "foreign slices cannot contain zero-sized C types");
+    assert!(
+        len.checked_mul(size).is_some_and(|n| n <= isize::MAX as usize),
+        "foreign slice is too large"
+    );
+    if len == 0 {
+        return NonNull::dangling();
+    }
+    let ptr = NonNull::new(ptr).expect("foreign slice pointer is null");
+    assert!(ptr.as_ptr().is_aligned(), "foreign slice pointer is misaligned");
+    ptr
+}
+
+/// A shared view over a contiguous array of C objects.
+pub struct ForeignSlice<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    ptr: NonNull<R::CType>,
+    len: usize,
+    _p: PhantomData<&'a R>,
+}
+
+impl<'a, R> ForeignSlice<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    /// Creates a view over the `len` objects starting at `ptr`.
+    ///
+    /// `ptr` may be null if `len` is zero.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to `len` valid, contiguous instances of the C type, which must not be
+    /// mutated for the lifetime `'a`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the C type is zero-sized, if `ptr` is null or misaligned while `len` is nonzero,
+    /// or if the array is larger than `isize::MAX` bytes.
+    #[inline]
+    pub unsafe fn from_raw_parts(ptr: *mut R::CType, len: usize) -> ForeignSlice<'a, R> {
+        ForeignSlice {
+            ptr: check::<R>(ptr, len),
+            len,
+            _p: PhantomData,
+        }
+    }
+
+    /// Returns the number of objects in the array.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the array is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a raw pointer to the first object.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut R::CType {
+        self.ptr.as_ptr()
+    }
+
+    /// Returns a reference to the object at `index`, or `None` if it is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&'a R> {
+        if index < self.len {
+            unsafe { Some(R::from_ptr(self.ptr.as_ptr().add(index))) }
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator over the objects.
+    #[inline]
+    pub fn iter(&self) -> Iter<'a, R> {
+        Iter {
+            ptr: self.ptr,
+            len: self.len,
+            _p: PhantomData,
+        }
+    }
+
+    /// Returns the array as a slice of the C type.
+    #[inline]
+    pub fn as_c_slice(&self) -> &'a [R::CType] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<'a, R> Clone for ForeignSlice<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    #[inline]
+    fn clone(&self) -> ForeignSlice<'a, R> {
+        *self
+    }
+}
+
+impl<'a, R> Copy for ForeignSlice<'a, R> where R: ForeignTypeRef + 'a {}
+
+impl<'a, R> Index<usize> for ForeignSlice<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    type Output = R;
+
+    #[inline]
+    fn index(&self, index: usize) -> &R {
+        match self.get(index) {
+            Some(value) => value,
+            None => panic!("index {} out of bounds for foreign slice of length {}", index, self.len),
+        }
+    }
+}
+
+impl<'a, R> IntoIterator for ForeignSlice<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    type Item = &'a R;
+    type IntoIter = Iter<'a, R>;
+
+    #[inline]
+    fn into_iter(self) -> Iter<'a, R> {
+        self.iter()
+    }
+}
+
+impl<'a, R> fmt::Debug for ForeignSlice<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ForeignSlice")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// A mutable view over a contiguous array of C objects.
+pub struct ForeignSliceMut<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    ptr: NonNull<R::CType>,
+    len: usize,
+    _p: PhantomData<&'a mut R>,
+}
+
+impl<'a, R> ForeignSliceMut<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    /// Creates a mutable view over the `len` objects starting at `ptr`.
+    ///
+    /// `ptr` may be null if `len` is zero.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to `len` valid, contiguous instances of the C type, which must not be
+    /// accessed other than through the view for the lifetime `'a`.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as `ForeignSlice::from_raw_parts`.
+    #[inline]
+    pub unsafe fn from_raw_parts(ptr: *mut R::CType, len: usize) -> ForeignSliceMut<'a, R> {
+        ForeignSliceMut {
+            ptr: check::<R>(ptr, len),
+            len,
+            _p: PhantomData,
+        }
+    }
+
+    /// Returns the number of objects in the array.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the array is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a raw pointer to the first object.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut R::CType {
+        self.ptr.as_ptr()
+    }
+
+    /// Returns a shared view over the array.
+    #[inline]
+    pub fn as_slice(&self) -> ForeignSlice<'_, R> {
+        ForeignSlice {
+            ptr: self.ptr,
+            len: self.len,
+            _p: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the object at `index`, or `None` if it is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&R> {
+        self.as_slice().get(index)
+    }
+
+    /// Returns a mutable reference to the object at `index`, or `None` if it is out of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut R>
+    where
+        R: ForeignTypeMut,
+    {
+        if index < self.len {
+            unsafe { Some(R::from_ptr_mut(self.ptr.as_ptr().add(index))) }
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator over the objects.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, R> {
+        self.as_slice().iter()
+    }
+
+    /// Returns an iterator over mutable references to the objects.
+    #[inline]
+    pub fn iter_mut(&mut self) -> IterMut<'_, R>
+    where
+        R: ForeignTypeMut,
+    {
+        IterMut {
+            ptr: self.ptr,
+            len: self.len,
+            _p: PhantomData,
+        }
+    }
+
+    /// Returns the array as a slice of the C type.
+    #[inline]
+    pub fn as_c_slice(&self) -> &[R::CType] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// Returns the array as a mutable slice of the C type.
+    #[inline]
+    pub fn as_c_slice_mut(&mut self) -> &mut [R::CType] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<'a, R> Index<usize> for ForeignSliceMut<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    type Output = R;
+
+    #[inline]
+    fn index(&self, index: usize) -> &R {
+        match self.get(index) {
+            Some(value) => value,
+            None => panic!("index {} out of bounds for foreign slice of length {}", index, self.len),
+        }
+    }
+}
+
+impl<'a, R> IndexMut<usize> for ForeignSliceMut<'a, R>
+where
+    R: ForeignTypeMut + 'a,
+{
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut R {
+        let len = self.len;
+        match self.get_mut(index) {
+            Some(value) => value,
+            None => panic!("index {} out of bounds for foreign slice of length {}", index, len),
+        }
+    }
+}
+
+impl<'a, R> IntoIterator for ForeignSliceMut<'a, R>
+where
+    R: ForeignTypeMut + 'a,
+{
+    type Item = &'a mut R;
+    type IntoIter = IterMut<'a, R>;
+
+    #[inline]
+    fn into_iter(self) -> IterMut<'a, R> {
+        IterMut {
+            ptr: self.ptr,
+            len: self.len,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<'a, R> fmt::Debug for ForeignSliceMut<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ForeignSliceMut")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// An iterator over the objects of a `ForeignSlice`.
+pub struct Iter<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    ptr: NonNull<R::CType>,
+    len: usize,
+    _p: PhantomData<&'a R>,
+}
+
+impl<'a, R> Iterator for Iter<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    type Item = &'a R;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a R> {
+        if self.len == 0 {
+            return None;
+        }
+        let ptr = self.ptr.as_ptr();
+        self.len -= 1;
+        unsafe {
+            self.ptr = NonNull::new_unchecked(ptr.add(1));
+            Some(R::from_ptr(ptr))
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, R> DoubleEndedIterator for Iter<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<&'a R> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        unsafe { Some(R::from_ptr(self.ptr.as_ptr().add(self.len))) }
+    }
+}
+
+impl<'a, R> ExactSizeIterator for Iter<'a, R> where R: ForeignTypeRef + 'a {}
+
+impl<'a, R> FusedIterator for Iter<'a, R> where R: ForeignTypeRef + 'a {}
+
+impl<'a, R> fmt::Debug for Iter<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Iter")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// An iterator over mutable references to the objects of a `ForeignSliceMut`.
+pub struct IterMut<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    ptr: NonNull<R::CType>,
+    len: usize,
+    _p: PhantomData<&'a mut R>,
+}
+
+impl<'a, R> Iterator for IterMut<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    type Item = &'a mut R;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a mut R> {
+        if self.len == 0 {
+            return None;
+        }
+        let ptr = self.ptr.as_ptr();
+        self.len -= 1;
+        unsafe {
+            self.ptr = NonNull::new_unchecked(ptr.add(1));
+            Some(R::from_ptr_mut(ptr))
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, R> DoubleEndedIterator for IterMut<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<&'a mut R> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        unsafe { Some(R::from_ptr_mut(self.ptr.as_ptr().add(self.len))) }
+    }
+}
+
+impl<'a, R> ExactSizeIterator for IterMut<'a, R> where R: ForeignTypeRef + 'a {}
+
+impl<'a, R> FusedIterator for IterMut<'a, R> where R: ForeignTypeRef + 'a {}
+
+impl<'a, R> fmt::Debug for IterMut<'a, R>
+where
+    R: ForeignTypeRef + 'a,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("IterMut")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .finish()
+    }
+}
@@ -0,0 +1,198 @@
+//! Memory accounting for wrapped C objects.
+//!
+//! Types defined with a `fn sizeof = ...;` entry in `foreign_type!` record the size reported by
+//! the C library whenever an owned value is created from a pointer, and release it when the value
+//! is dropped or converted back into a raw pointer. The functions in this module report those
+//! totals for a single type or for every accounted type together.
+//!
+//! The size of each value is recorded when it is created, and exactly that size is released with
+//! it, so the byte counts reflect the sizes of live values at the time they were created. Objects
+//! which grow or shrink while they are owned from Rust are not resampled.
+//!
+//! This module requires the `stats` feature, which also enables the `std` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::stats;
+//!
+//! # mod buf_sys {
+//! #     pub struct BUF { pub data: Vec<u8> }
+//! #     pub unsafe fn BUF_new(len: usize) -> *mut BUF { Box::into_raw(Box::new(BUF { data: vec![0; len] })) }
+//! #     pub unsafe extern "C" fn BUF_free(buf: *mut BUF) { drop(Box::from_raw(buf)) }
+//! #     pub unsafe fn BUF_sizeof(buf: *const BUF) -> usize { (*buf).data.len() }
+//! # }
+//! foreign_type! {
+//!     type CType = buf_sys::BUF;
+//!     fn drop = buf_sys::BUF_free;
+//!     fn sizeof = buf_sys::BUF_sizeof;
+//!     /// A buffer.
+//!     pub struct Buf;
+//!     /// A borrowed buffer.
+//!     pub struct BufRef;
+//! }
+//!
+//! # fn main() {
+//! use foreign_types::ForeignType;
+//!
+//! let a = unsafe { Buf::from_ptr(buf_sys::BUF_new(100)) };
+//! let b = unsafe { Buf::from_ptr(buf_sys::BUF_new(28)) };
+//! assert_eq!(stats::objects_live::<Buf>(), 2);
+//! assert_eq!(stats::bytes_live::<Buf>(), 128);
+//!
+//! drop(a);
+//! assert_eq!(stats::bytes_live::<Buf>(), 28);
+//!
+//! // The size recorded when the buffer was created is released with it.
+//! unsafe { (*b.as_ptr()).data.resize(50, 0) };
+//! assert_eq!(stats::bytes_live::<Buf>(), 28);
+//! assert!(stats::total_bytes_live() >= 28);
+//! # drop(b);
+//! # }
+//! ```
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::vec::Vec;
+
+use ForeignType;
+
+/// Running totals for the live values of one type.
+#[derive(Debug)]
+pub struct Counters {
+    objects: AtomicUsize,
+    bytes: AtomicUsize,
+    // The sizes recorded for the live values with each pointer.
+    sizes: Mutex<BTreeMap<usize, Vec<usize>>>,
+}
+
+impl Counters {
+    /// Returns a set of counters with no live values.
+    pub const fn new() -> Counters {
+        Counters {
+            objects: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+            sizes: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn sizes(&self) -> MutexGuard<'_, BTreeMap<usize, Vec<usize>>> {
+        self.sizes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the number of live values.
+    #[inline]
+    pub fn objects_live(&self) -> usize {
+        self.objects.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes held by live values, as reported by the C library.
+    #[inline]
+    pub fn bytes_live(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn add(&self, size: usize) {
+        self.objects.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(size, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn sub(&self, size: usize) {
+        self.objects.fetch_sub(1, Ordering::Relaxed);
+        self.bytes.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+impl Default for Counters {
+    #[inline]
+    fn default() -> Counters {
+        Counters::new()
+    }
+}
+
+/// A type whose live values are accounted for.
+///
+/// This is implemented by `foreign_type!` for types with a `fn sizeof = ...;` entry.
+pub trait Accounted: ForeignType {
+    /// Returns the counters for this type.
+    fn counters() -> &'static Counters;
+}
+
+static TOTAL: Counters = Counters::new();
+
+/// Returns the number of live values of type `T`.
+#[inline]
+pub fn objects_live<T>() -> usize
+where
+    T: Accounted,
+{
+    T::counters().objects_live()
+}
+
+/// Returns the number of bytes held by live values of type `T`.
+#[inline]
+pub fn bytes_live<T>() -> usize
+where
+    T: Accounted,
+{
+    T::counters().bytes_live()
+}
+
+/// Returns the number of live values across all accounted types.
+#[inline]
+pub fn total_objects_live() -> usize {
+    TOTAL.objects_live()
+}
+
+/// Returns the number of bytes held by live values across all accounted types.
+#[inline]
+pub fn total_bytes_live() -> usize {
+    TOTAL.bytes_live()
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __record_create<T>(ptr: *mut c_void, size: usize)
+where
+    T: Accounted,
+{
+    let counters = T::counters();
+    counters
+        .sizes()
+        .entry(ptr as usize)
+        .or_insert_with(Vec::new)
+        .push(size);
+    counters.add(size);
+    TOTAL.add(size);
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __record_release<T>(ptr: *mut c_void)
+where
+    T: Accounted,
+{
+    let counters = T::counters();
+    let size = {
+        let mut sizes = counters.sizes();
+        let recorded = match sizes.get_mut(&(ptr as usize)) {
+            Some(recorded) => recorded,
+            None => return,
+        };
+        let size = recorded.pop();
+        if recorded.is_empty() {
+            sizes.remove(&(ptr as usize));
+        }
+        size
+    };
+    if let Some(size) = size {
+        counters.sub(size);
+        TOTAL.sub(size);
+    }
+}
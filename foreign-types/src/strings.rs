@@ -0,0 +1,149 @@
+//! Conversions of Rust strings into C strings for the duration of a call.
+//!
+//! C functions taking a `const char *` need a nul-terminated copy of a Rust string, which must
+//! stay alive until the call returns and must not contain interior nul bytes. `with_cstr` performs
+//! the conversion, reports interior nul bytes as an error, and lends the pointer to a closure, so
+//! that it cannot outlive the string it points into.
+//!
+//! Setters declared with a `(cstr)` parameter in `foreign_type!` accept any `TryIntoCStr` value
+//! through the same conversion.
+//!
+//! This module requires the `std` feature, which is enabled by default.
+//!
+//! # Examples
+//!
+//! ```
+//! extern crate foreign_types;
+//!
+//! use foreign_types::strings;
+//! use std::ffi::NulError;
+//! use std::os::raw::c_char;
+//!
+//! # mod env_sys {
+//! #     use std::os::raw::c_char;
+//! #     pub unsafe fn ENV_len(name: *const c_char) -> usize {
+//! #         std::ffi::CStr::from_ptr(name).to_bytes().len()
+//! #     }
+//! # }
+//! fn len(name: &str) -> Result<usize, NulError> {
+//!     strings::with_cstr(name, |name: *const c_char| unsafe { env_sys::ENV_len(name) })
+//! }
+//!
+//! # fn main() {
+//! assert_eq!(len("HOME").unwrap(), 4);
+//! assert!(len("HO\0ME").is_err());
+//! # }
+//! ```
+use std::borrow::Cow;
+use std::ffi::{CStr, CString, NulError};
+use std::os::raw::c_char;
+use std::string::String;
+use std::vec::Vec;
+
+/// A value which can be converted into a C string.
+///
+/// Values which already are C strings, or which end with their only nul byte, are borrowed rather
+/// than copied.
+pub trait TryIntoCStr<'a> {
+    /// Converts the value into a C string.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value contains a nul byte other than a single trailing one.
+    fn try_into_cstr(self) -> Result<Cow<'a, CStr>, NulError>;
+}
+
+fn bytes_into_cstr(bytes: &[u8]) -> Result<Cow<'_, CStr>, NulError> {
+    match CStr::from_bytes_with_nul(bytes) {
+        Ok(s) => Ok(Cow::Borrowed(s)),
+        Err(_) => CString::new(bytes).map(Cow::Owned),
+    }
+}
+
+fn vec_into_cstr<'a>(mut bytes: Vec<u8>) -> Result<Cow<'a, CStr>, NulError> {
+    if bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+    CString::new(bytes).map(Cow::Owned)
+}
+
+impl<'a> TryIntoCStr<'a> for &'a str {
+    #[inline]
+    fn try_into_cstr(self) -> Result<Cow<'a, CStr>, NulError> {
+        bytes_into_cstr(self.as_bytes())
+    }
+}
+
+impl<'a> TryIntoCStr<'a> for &'a String {
+    #[inline]
+    fn try_into_cstr(self) -> Result<Cow<'a, CStr>, NulError> {
+        bytes_into_cstr(self.as_bytes())
+    }
+}
+
+impl<'a> TryIntoCStr<'a> for &'a [u8] {
+    #[inline]
+    fn try_into_cstr(self) -> Result<Cow<'a, CStr>, NulError> {
+        bytes_into_cstr(self)
+    }
+}
+
+impl<'a> TryIntoCStr<'a> for &'a Vec<u8> {
+    #[inline]
+    fn try_into_cstr(self) -> Result<Cow<'a, CStr>, NulError> {
+        bytes_into_cstr(self)
+    }
+}
+
+impl<'a> TryIntoCStr<'a> for &'a CStr {
+    #[inline]
+    fn try_into_cstr(self) -> Result<Cow<'a, CStr>, NulError> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl<'a> TryIntoCStr<'a> for &'a CString {
+    #[inline]
+    fn try_into_cstr(self) -> Result<Cow<'a, CStr>, NulError> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl<'a> TryIntoCStr<'a> for CString {
+    #[inline]
+    fn try_into_cstr(self) -> Result<Cow<'a, CStr>, NulError> {
+        Ok(Cow::Owned(self))
+    }
+}
+
+impl<'a> TryIntoCStr<'a> for String {
+    #[inline]
+    fn try_into_cstr(self) -> Result<Cow<'a, CStr>, NulError> {
+        vec_into_cstr(self.into_bytes())
+    }
+}
+
+impl<'a> TryIntoCStr<'a> for Vec<u8> {
+    #[inline]
+    fn try_into_cstr(self) -> Result<Cow<'a, CStr>, NulError> {
+        vec_into_cstr(self)
+    }
+}
+
+/// Calls `f` with a pointer to `s` converted into a C string.
+///
+/// The pointer is only valid until `f` returns.
+///
+/// # Errors
+///
+/// Returns an error without calling `f` if `s` contains a nul byte other than a single trailing
+/// one.
+#[inline]
+pub fn with_cstr<'a, S, F, R>(s: S, f: F) -> Result<R, NulError>
+where
+    S: TryIntoCStr<'a>,
+    F: FnOnce(*const c_char) -> R,
+{
+    let s = s.try_into_cstr()?;
+    Ok(f(s.as_ptr()))
+}
@@ -0,0 +1,153 @@
+//! Runtime type tags for pointers passed through C.
+//!
+//! Pointers which come back from C as `void *`, such as callback userdata or the contents of a
+//! generic container, carry no information about which wrapper type they belong to. The functions
+//! in this module box a wrapper's pointer together with a tag identifying its type, so that it can
+//! be checked before being converted back.
+//!
+//! This module requires the `std` feature, which is enabled by default.
+use core::any::TypeId;
+use core::ffi::c_void;
+use std::boxed::Box;
+
+use ForeignType;
+
+/// A tag identifying a wrapper type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeTag(TypeId);
+
+impl TypeTag {
+    /// Returns the tag of the wrapper type `T`.
+    #[inline]
+    pub fn of<T>() -> TypeTag
+    where
+        T: ForeignType + 'static,
+    {
+        TypeTag(TypeId::of::<T>())
+    }
+}
+
+struct Tagged {
+    tag: TypeTag,
+    ptr: *mut c_void,
+    drop: unsafe fn(*mut c_void),
+}
+
+unsafe fn drop_value<T>(ptr: *mut c_void)
+where
+    T: ForeignType,
+{
+    drop(T::from_ptr(ptr as *mut T::CType));
+}
+
+/// Converts a value into a tagged pointer, transferring ownership to it.
+///
+/// The pointer must eventually be passed to `try_from_tagged_ptr` or `drop_tagged_ptr` to free
+/// the value.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate foreign_types;
+///
+/// use foreign_types::tag;
+///
+/// # mod foo_sys { pub struct FOO; pub unsafe extern "C" fn FOO_free(foo: *mut FOO) { drop(Box::from_raw(foo)) } }
+/// foreign_type! {
+///     type CType = foo_sys::FOO;
+///     fn drop = foo_sys::FOO_free;
+///     /// A Foo.
+///     pub struct Foo;
+///     /// A borrowed Foo.
+///     pub struct FooRef;
+/// }
+///
+/// foreign_type! {
+///     type CType = foo_sys::FOO;
+///     fn drop = foo_sys::FOO_free;
+///     /// A Bar.
+///     pub struct Bar;
+///     /// A borrowed Bar.
+///     pub struct BarRef;
+/// }
+///
+/// # fn main() {
+/// # let foo: Foo = unsafe { foreign_types::ForeignType::from_ptr(Box::into_raw(Box::new(foo_sys::FOO))) };
+/// let userdata = tag::tagged_into_ptr(foo);
+///
+/// unsafe {
+///     assert!(tag::try_ref_from_tagged_ptr::<Bar>(userdata).is_none());
+///     assert!(tag::try_ref_from_tagged_ptr::<Foo>(userdata).is_some());
+///     let _foo: Foo = tag::try_from_tagged_ptr(userdata).ok().unwrap();
+/// }
+/// # }
+/// ```
+pub fn tagged_into_ptr<T>(value: T) -> *mut c_void
+where
+    T: ForeignType + 'static,
+{
+    let tagged = Tagged {
+        tag: TypeTag::of::<T>(),
+        ptr: value.into_ptr() as *mut c_void,
+        drop: drop_value::<T>,
+    };
+    Box::into_raw(Box::new(tagged)) as *mut c_void
+}
+
+/// Returns the tag of a tagged pointer.
+///
+/// # Safety
+///
+/// `ptr` must have been returned by `tagged_into_ptr`, and not yet freed.
+#[inline]
+pub unsafe fn tag_of(ptr: *const c_void) -> TypeTag {
+    (*(ptr as *const Tagged)).tag
+}
+
+/// Borrows the value of a tagged pointer, returning `None` if it is not a `T`.
+///
+/// # Safety
+///
+/// `ptr` must have been returned by `tagged_into_ptr`, and not be freed while the returned
+/// reference is alive.
+#[inline]
+pub unsafe fn try_ref_from_tagged_ptr<'a, T>(ptr: *const c_void) -> Option<&'a T::Ref>
+where
+    T: ForeignType + 'static,
+{
+    let tagged = &*(ptr as *const Tagged);
+    if tagged.tag == TypeTag::of::<T>() {
+        Some(::ForeignTypeRef::from_ptr(tagged.ptr as *mut T::CType))
+    } else {
+        None
+    }
+}
+
+/// Converts a tagged pointer back into a value, returning the pointer unchanged if it is not a
+/// `T`.
+///
+/// # Safety
+///
+/// `ptr` must have been returned by `tagged_into_ptr`, and not yet freed.
+pub unsafe fn try_from_tagged_ptr<T>(ptr: *mut c_void) -> Result<T, *mut c_void>
+where
+    T: ForeignType + 'static,
+{
+    if tag_of(ptr) != TypeTag::of::<T>() {
+        return Err(ptr);
+    }
+
+    let tagged = Box::from_raw(ptr as *mut Tagged);
+    Ok(T::from_ptr(tagged.ptr as *mut T::CType))
+}
+
+/// Frees a tagged pointer and the value it owns, whatever its type.
+///
+/// # Safety
+///
+/// `ptr` must have been returned by `tagged_into_ptr`, and not yet freed.
+pub unsafe fn drop_tagged_ptr(ptr: *mut c_void) {
+    let tagged = Box::from_raw(ptr as *mut Tagged);
+    (tagged.drop)(tagged.ptr);
+}
@@ -0,0 +1,130 @@
+//! Lazily created per-thread instances of foreign types.
+//!
+//! C contexts which are cheap to create but not thread-safe, such as random number generators and
+//! scratch buffers, are often wanted as per-thread singletons. A `ThreadLocalForeign` creates one
+//! instance for each thread which uses it, and frees it with the type's `Drop` implementation when
+//! the thread exits.
+//!
+//! This module requires the `std` feature, which is enabled by default.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate foreign_types;
+//!
+//! use foreign_types::thread_local::ThreadLocalForeign;
+//! use foreign_types::ForeignType;
+//!
+//! # mod rng_sys {
+//! #     pub struct RNG(pub u32);
+//! #     pub unsafe fn RNG_new() -> *mut RNG { Box::into_raw(Box::new(RNG(0))) }
+//! #     pub unsafe extern "C" fn RNG_free(rng: *mut RNG) { drop(Box::from_raw(rng)) }
+//! #     pub unsafe fn RNG_next(rng: *mut RNG) -> u32 { (*rng).0 += 1; (*rng).0 }
+//! # }
+//! foreign_type! {
+//!     type CType = rng_sys::RNG;
+//!     fn drop = rng_sys::RNG_free;
+//!     /// A random number generator.
+//!     pub struct Rng;
+//!     /// A borrowed random number generator.
+//!     pub struct RngRef;
+//! }
+//!
+//! impl RngRef {
+//!     pub fn next(&mut self) -> u32 {
+//!         unsafe { rng_sys::RNG_next(foreign_types::ForeignTypeRef::as_ptr(self)) }
+//!     }
+//! }
+//!
+//! static RNG: ThreadLocalForeign<Rng> =
+//!     ThreadLocalForeign::new(|| unsafe { Rng::from_ptr(rng_sys::RNG_new()) });
+//!
+//! # fn main() {
+//! assert_eq!(RNG.with(|rng| rng.next()), 1);
+//! assert_eq!(RNG.with(|rng| rng.next()), 2);
+//!
+//! // Each thread has its own instance, which is freed when the thread exits.
+//! # if cfg!(not(target_arch = "wasm32")) {
+//! std::thread::spawn(|| assert_eq!(RNG.with(|rng| rng.next()), 1)).join().unwrap();
+//! # }
+//! # }
+//! ```
+use core::any::Any;
+use core::cell::RefCell;
+use core::fmt;
+use std::boxed::Box;
+use std::collections::BTreeMap;
+
+use {ForeignType, ForeignTypeMut, ForeignTypeRef};
+
+std::thread_local! {
+    static INSTANCES: RefCell<BTreeMap<usize, Box<dyn Any>>> = RefCell::new(BTreeMap::new());
+}
+
+/// A lazily created instance of a foreign type for each thread.
+///
+/// Values of this type are intended to be stored in a `static`. It is `Send` and `Sync` regardless
+/// of `T`, since instances are never shared between threads.
+pub struct ThreadLocalForeign<T>
+where
+    T: ForeignType + 'static,
+{
+    init: fn() -> T,
+}
+
+impl<T> ThreadLocalForeign<T>
+where
+    T: ForeignType + 'static,
+{
+    /// Creates a new `ThreadLocalForeign` which creates each thread's instance with `init`.
+    #[inline]
+    pub const fn new(init: fn() -> T) -> ThreadLocalForeign<T> {
+        ThreadLocalForeign { init }
+    }
+
+    /// Calls `f` with this thread's instance, creating it first if necessary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within `f` for the same instance, or while the thread's local values
+    /// are being destroyed.
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        T::Ref: ForeignTypeMut,
+        F: FnOnce(&mut T::Ref) -> R,
+    {
+        let key = self as *const ThreadLocalForeign<T> as usize;
+        let cell = INSTANCES.with(|instances| {
+            let existing = instances
+                .borrow()
+                .get(&key)
+                .and_then(|value| value.downcast_ref::<RefCell<T>>())
+                .map(|cell| cell as *const RefCell<T>);
+            match existing {
+                Some(cell) => cell,
+                None => {
+                    // `init` may itself use other instances, so the map is not borrowed while it
+                    // runs.
+                    let value = Box::new(RefCell::new((self.init)()));
+                    let cell = &*value as *const RefCell<T>;
+                    instances.borrow_mut().insert(key, value);
+                    cell
+                }
+            }
+        });
+
+        // The boxed cell is only removed from the map when the thread exits.
+        let value = unsafe { (*cell).borrow_mut() };
+        f(unsafe { T::Ref::from_ptr_mut(value.as_ptr()) })
+    }
+}
+
+impl<T> fmt::Debug for ThreadLocalForeign<T>
+where
+    T: ForeignType + 'static,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ThreadLocalForeign").finish()
+    }
+}